@@ -0,0 +1,308 @@
+//! Async handlers, shared state, and extractors.
+//!
+//! An async handler is a function or closure that takes any number of
+//! *extractors* as arguments and returns a future that produces a
+//! `Response`. Each extractor implements `FromRequest`, which builds it from
+//! the incoming request, or else returns a response to send instead, like a
+//! 400 Bad Request for a malformed query string. For example:
+//!
+//!     # use basic_router::{BasicRouter, Response};
+//!     # use basic_router::extract::{JsonBody, Query, State};
+//!     # use std::collections::HashMap;
+//!     # use std::sync::Mutex;
+//!     struct Counter(Mutex<u64>);
+//!
+//!     async fn bump(State(counter): State<Counter>,
+//!                   Query(query): Query,
+//!                   JsonBody(body): JsonBody) -> Response {
+//!         // ...
+//!         # Response { code: 200, headers: HashMap::new(), body: vec![] }
+//!     }
+//!
+//!     let mut router = BasicRouter::new();
+//!     router.add_state(Counter(Mutex::new(0)));
+//!     router.add_async_route("POST", "/bump", bump);
+//!
+//! Shared state is stored in the router by type, with `add_state`, and
+//! handed to handlers as an `Arc` by the `State<T>` extractor.
+//!
+//! Each handler's future runs to completion on the thread handling the
+//! request, using `block_on`.
+
+use crate::{Params, Request, Response};
+use json_macro::Json;
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::future::Future;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// A router's shared state: at most one value of each type.
+#[derive(Default)]
+pub struct StateMap {
+    values: HashMap<TypeId, Arc<dyn Any + Send + Sync>>,
+}
+
+impl StateMap {
+    pub fn new() -> StateMap {
+        StateMap::default()
+    }
+
+    /// Store `value`, replacing any earlier value of the same type.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.values.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Return a map with the values of all of `layers`. Where several
+    /// layers have a value of the same type, the first one's wins.
+    pub(crate) fn layered<'a, I>(layers: I) -> StateMap
+        where I: IntoIterator<Item = &'a StateMap>
+    {
+        let mut values = HashMap::new();
+        for layer in layers {
+            for (&type_id, value) in &layer.values {
+                values.entry(type_id).or_insert_with(|| value.clone());
+            }
+        }
+        StateMap { values }
+    }
+
+    /// Return the value of type `T`, if there is one.
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.values.get(&TypeId::of::<T>())
+            .map(|value| value.clone().downcast().unwrap())
+    }
+}
+
+/// A type that can be built from a request, for use as an argument to an
+/// async handler.
+pub trait FromRequest: Sized {
+    /// Build a value from `request`, or return the response to send
+    /// instead.
+    fn from_request(request: &Request, params: &Params, state: &StateMap)
+                    -> Result<Self, Response>;
+}
+
+/// A function that can serve as an async handler, given its argument types.
+pub trait Handler<Args>: Send + Sync + 'static {
+    fn call(&self, request: &Request, params: &Params, state: &StateMap) -> Response;
+}
+
+macro_rules! impl_handler {
+    ( $( $arg:ident )* ) => {
+        impl<F, Fut, $( $arg ),*> Handler<( $( $arg, )* )> for F
+            where F: Fn( $( $arg ),* ) -> Fut + Send + Sync + 'static,
+                  Fut: Future<Output = Response>,
+                  $( $arg: FromRequest ),*
+        {
+            #[allow(non_snake_case, unused_variables)]
+            fn call(&self, request: &Request, params: &Params, state: &StateMap) -> Response {
+                $(
+                    let $arg = match $arg::from_request(request, params, state) {
+                        Ok(value) => value,
+                        Err(response) => return response,
+                    };
+                )*
+                block_on::block_on(self( $( $arg ),* ))
+            }
+        }
+    };
+}
+
+impl_handler!();
+impl_handler!(A);
+impl_handler!(A B);
+impl_handler!(A B C);
+impl_handler!(A B C D);
+impl_handler!(A B C D E);
+impl_handler!(A B C D E G);
+
+/// Return a plain-text response with the given status.
+pub(crate) fn text_response(code: u32, message: String) -> Response {
+    let mut headers = HashMap::new();
+    headers.insert("Content-Type".to_string(), "text/plain; charset=utf-8".to_string());
+    Response { code, headers, body: message.into_bytes() }
+}
+
+fn bad_request(message: String) -> Response {
+    text_response(400, message)
+}
+
+impl FromRequest for Request {
+    fn from_request(request: &Request, _: &Params, _: &StateMap) -> Result<Request, Response> {
+        Ok(request.clone())
+    }
+}
+
+impl FromRequest for Params {
+    fn from_request(_: &Request, params: &Params, _: &StateMap) -> Result<Params, Response> {
+        Ok(params.clone())
+    }
+}
+
+/// Extracting an `Option` never fails: it's `None` where extracting the
+/// value itself would have failed.
+impl<T: FromRequest> FromRequest for Option<T> {
+    fn from_request(request: &Request, params: &Params, state: &StateMap)
+                    -> Result<Option<T>, Response>
+    {
+        Ok(T::from_request(request, params, state).ok())
+    }
+}
+
+/// The router's shared value of type `T`. If the router has none, the
+/// request fails with 500 Internal Server Error.
+pub struct State<T>(pub Arc<T>);
+
+impl<T> Deref for State<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Send + Sync + 'static> FromRequest for State<T> {
+    fn from_request(_: &Request, _: &Params, state: &StateMap) -> Result<State<T>, Response> {
+        state.get().map(State).ok_or_else(|| {
+            text_response(500, format!("router has no state of type {}", type_name::<T>()))
+        })
+    }
+}
+
+/// A type that can be built from the name-value pairs of a query string.
+pub trait FromQuery: Sized {
+    /// Build a value from `pairs`, or explain why not.
+    fn from_query(pairs: Vec<(String, String)>) -> Result<Self, String>;
+}
+
+/// Later pairs override earlier ones with the same name.
+impl FromQuery for HashMap<String, String> {
+    fn from_query(pairs: Vec<(String, String)>) -> Result<Self, String> {
+        Ok(pairs.into_iter().collect())
+    }
+}
+
+impl FromQuery for Vec<(String, String)> {
+    fn from_query(pairs: Vec<(String, String)>) -> Result<Self, String> {
+        Ok(pairs)
+    }
+}
+
+/// The request's query string, decoded. A malformed query string, or one
+/// `T` rejects, fails the request with 400 Bad Request.
+pub struct Query<T = HashMap<String, String>>(pub T);
+
+impl<T: FromQuery> FromRequest for Query<T> {
+    fn from_request(request: &Request, _: &Params, _: &StateMap) -> Result<Query<T>, Response> {
+        let query = request.url.split_once('?').map_or("", |(_, query)| query);
+        let pairs = parse_query(query)
+            .map_err(|message| bad_request(format!("malformed query string: {}", message)))?;
+        T::from_query(pairs)
+            .map(Query)
+            .map_err(|message| bad_request(format!("bad query parameters: {}", message)))
+    }
+}
+
+/// Split a query string into decoded name-value pairs.
+fn parse_query(query: &str) -> Result<Vec<(String, String)>, String> {
+    query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Ok((percent_decode(name, true)?, percent_decode(value, true)?))
+        })
+        .collect()
+}
+
+/// Decode `%XX` escapes in a URL component. In query strings, `+` stands
+/// for a space, so decode that too if `plus_as_space` is true.
+pub(crate) fn percent_decode(text: &str, plus_as_space: bool) -> Result<String, String> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = tail;
+        match b {
+            b'+' if plus_as_space => bytes.push(b' '),
+            b'%' => {
+                // `from_str_radix` would accept a sign, so check the digits.
+                let hex = rest.get(..2)
+                    .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                    .map(|hex| u8::from_str_radix(std::str::from_utf8(hex).unwrap(), 16).unwrap())
+                    .ok_or_else(|| format!("bad percent escape in {:?}", text))?;
+                bytes.push(hex);
+                rest = &rest[2..];
+            }
+            b => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| format!("{:?} is not UTF-8 once decoded", text))
+}
+
+/// The request body, parsed as JSON. If the body isn't valid JSON, or the
+/// request has a `Content-Type` other than `application/json`, the request
+/// fails with 400 Bad Request.
+pub struct JsonBody(pub Json);
+
+impl FromRequest for JsonBody {
+    fn from_request(request: &Request, _: &Params, _: &StateMap) -> Result<JsonBody, Response> {
+        if let Some(content_type) = find_header(&request.headers, "Content-Type") {
+            let media_type = content_type.split(';').next().unwrap().trim();
+            if !media_type.eq_ignore_ascii_case("application/json") {
+                return Err(bad_request(format!("expected a JSON body, not {}", media_type)));
+            }
+        }
+        let text = std::str::from_utf8(&request.body)
+            .map_err(|_| bad_request("JSON body is not UTF-8".to_string()))?;
+        text.parse()
+            .map(JsonBody)
+            .map_err(|error| bad_request(format!("malformed JSON body: {}", error)))
+    }
+}
+
+/// The request's headers.
+pub struct Headers(pub HashMap<String, String>);
+
+impl Headers {
+    /// Return the value of the header `name`, ignoring case.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        find_header(&self.0, name)
+    }
+}
+
+pub(crate) fn find_header<'h>(headers: &'h HashMap<String, String>, name: &str) -> Option<&'h str> {
+    headers.iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+impl FromRequest for Headers {
+    fn from_request(request: &Request, _: &Params, _: &StateMap) -> Result<Headers, Response> {
+        Ok(Headers(request.headers.clone()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_query, percent_decode};
+
+    #[test]
+    fn query_strings() {
+        assert_eq!(percent_decode("a+b%20c%2Fd%C3%A9", true).unwrap(), "a b c/dé");
+        assert_eq!(percent_decode("a+b%2B", false).unwrap(), "a+b+");
+        assert!(percent_decode("100%", true).is_err());
+        assert!(percent_decode("%zz", true).is_err());
+        assert!(percent_decode("%+f", true).is_err());
+        assert!(percent_decode("%ff", true).is_err());
+
+        let pairs = |query| parse_query(query).unwrap();
+        assert_eq!(pairs(""), vec![]);
+        assert_eq!(pairs("q=rust+lang&&flag&x=1%3D2"),
+                   vec![("q".to_string(), "rust lang".to_string()),
+                        ("flag".to_string(), "".to_string()),
+                        ("x".to_string(), "1=2".to_string())]);
+    }
+}
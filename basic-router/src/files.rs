@@ -0,0 +1,648 @@
+//! Serving static files from a directory.
+//!
+//! `BasicRouter::add_static` maps a route prefix to a directory, so that
+//! with the prefix `/static` and the directory `public`, a request for
+//! `/static/css/site.css` gets the file `public/css/site.css`. Paths that
+//! would climb out of the directory, with `..` or through symbolic links,
+//! get 404 Not Found, like files that don't exist.
+//!
+//! Responses carry a `Content-Type` chosen by file extension, and the
+//! `ETag` and `Last-Modified` headers, so that clients can revalidate their
+//! cached copies with `If-None-Match` or `If-Modified-Since` and get 304 Not
+//! Modified back. A `Range` request for a single byte range gets 206 Partial
+//! Content; requests for several ranges get the whole file.
+//!
+//! A request for a directory gets its index file, if it has one, or else a
+//! listing of its contents, if listings are enabled.
+
+use crate::extract::{find_header, percent_decode};
+use crate::reverse::{join_paths, percent_encode};
+use crate::{Params, Request, Response, Route};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A handler that serves files from a directory.
+pub struct StaticFiles {
+    root: PathBuf,
+    index_file: Option<String>,
+    listing: bool,
+}
+
+impl StaticFiles {
+    /// Serve the files under `root`, with `index.html` as the index file,
+    /// and no directory listings.
+    pub fn new<P: AsRef<Path>>(root: P) -> StaticFiles {
+        StaticFiles {
+            root: root.as_ref().to_path_buf(),
+            index_file: Some("index.html".to_string()),
+            listing: false,
+        }
+    }
+
+    /// Set the name of the file to serve for a request for a directory, or
+    /// `None` to never serve one.
+    pub fn index_file(mut self, name: Option<&str>) -> StaticFiles {
+        self.index_file = name.map(str::to_string);
+        self
+    }
+
+    /// Set whether to list the contents of directories without an index
+    /// file.
+    pub fn listing(mut self, listing: bool) -> StaticFiles {
+        self.listing = listing;
+        self
+    }
+
+    /// Serve the file named by `params`' `path` parameter, relative to the
+    /// root directory.
+    pub fn handle(&self, request: &Request, params: &Params) -> Response {
+        let path = match self.resolve(params.get("path").unwrap_or("")) {
+            Some(path) => path,
+            None => return not_found()
+        };
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) => metadata,
+            Err(_) => return not_found()
+        };
+
+        if !metadata.is_dir() {
+            // A trailing slash promises a directory.
+            if request.path().ends_with('/') {
+                return not_found();
+            }
+            return serve_file(request, &path, &metadata).unwrap_or_else(|_| not_found());
+        }
+
+        // Relative links from a directory's index or listing only work if its
+        // URL ends with a slash.
+        if !request.path().ends_with('/') {
+            let mut location = format!("{}/", request.path());
+            if let Some((_, query)) = request.url.split_once('?') {
+                location = format!("{}?{}", location, query);
+            }
+            let mut headers = HashMap::new();
+            headers.insert("Location".to_string(), location);
+            return Response { code: 301, headers, body: vec![] };
+        }
+        if let Some(index) = &self.index_file {
+            let index = path.join(index);
+            if let Ok(metadata) = fs::metadata(&index) {
+                if metadata.is_file() {
+                    return serve_file(request, &index, &metadata)
+                        .unwrap_or_else(|_| not_found());
+                }
+            }
+        }
+        if self.listing {
+            let at_root = self.root.canonicalize().is_ok_and(|root| root == path);
+            return list_directory(request, &path, at_root).unwrap_or_else(|_| not_found());
+        }
+        not_found()
+    }
+
+    /// Return the file or directory the URL path `path` refers to, or
+    /// `None` if it isn't under the root directory.
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let path = percent_decode(path, false).ok()?;
+        let mut full = self.root.clone();
+        for segment in path.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => return None,
+                s if s.contains(['\\', '\0']) => return None,
+                s => full.push(s)
+            }
+        }
+
+        // Catch symbolic links that lead out of the root directory.
+        let full = full.canonicalize().ok()?;
+        if !full.starts_with(self.root.canonicalize().ok()?) {
+            return None;
+        }
+        Some(full)
+    }
+}
+
+/// Return the GET routes, relative to the router or group adding them, that
+/// serve `files` under `prefix`: one for the directory itself, and one for
+/// everything in it.
+pub(crate) fn routes(prefix: &str, files: StaticFiles) -> [(String, Route); 2] {
+    let files = Arc::new(files);
+    let directory = files.clone();
+    [
+        (prefix.to_string(), Route::new(move |req, params| directory.handle(req, params))),
+        (join_paths(prefix, "/*path"), Route::new(move |req, params| files.handle(req, params))),
+    ]
+}
+
+fn not_found() -> Response {
+    Response {
+        code: 404,
+        headers: HashMap::new(),
+        body: b"<h1>Page not found</h1>".to_vec()
+    }
+}
+
+/// Respond to `request` with the contents of the file at `path`, or a part
+/// of it, or a 304 Not Modified.
+fn serve_file(request: &Request, path: &Path, metadata: &fs::Metadata) -> io::Result<Response> {
+    let len = metadata.len();
+    let modified = metadata.modified()?;
+    let etag = entity_tag(len, modified);
+    let last_modified = http_date(modified);
+
+    let mut headers = HashMap::new();
+    headers.insert("ETag".to_string(), etag.clone());
+    headers.insert("Last-Modified".to_string(), last_modified.clone());
+
+    if !modified_since(request, &etag, modified) {
+        return Ok(Response { code: 304, headers, body: vec![] });
+    }
+
+    headers.insert("Accept-Ranges".to_string(), "bytes".to_string());
+    let content_type = path.extension()
+        .and_then(|extension| extension.to_str())
+        .map_or("application/octet-stream", content_type);
+    headers.insert("Content-Type".to_string(), content_type.to_string());
+
+    let range = match find_header(&request.headers, "Range") {
+        Some(range) if if_range_matches(request, &etag, &last_modified) => {
+            parse_range(range, len)
+        }
+        _ => ByteRange::Whole
+    };
+
+    let mut file = File::open(path)?;
+    match range {
+        ByteRange::Whole => {
+            let mut body = Vec::with_capacity(len as usize);
+            file.read_to_end(&mut body)?;
+            Ok(Response { code: 200, headers, body })
+        }
+        ByteRange::Part(start, end) => {
+            let mut body = vec![0; (end - start + 1) as usize];
+            file.seek(SeekFrom::Start(start))?;
+            file.read_exact(&mut body)?;
+            headers.insert("Content-Range".to_string(),
+                           format!("bytes {}-{}/{}", start, end, len));
+            Ok(Response { code: 206, headers, body })
+        }
+        ByteRange::Unsatisfiable => {
+            headers.insert("Content-Range".to_string(), format!("bytes */{}", len));
+            Ok(Response { code: 416, headers, body: vec![] })
+        }
+    }
+}
+
+/// Return a validator for a file of the given length and modification time.
+fn entity_tag(len: u64, modified: SystemTime) -> String {
+    let nanos = modified.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    format!("\"{:x}-{:x}\"", len, nanos)
+}
+
+/// Return false if the request's conditional headers say the client's copy
+/// is still good.
+fn modified_since(request: &Request, etag: &str, modified: SystemTime) -> bool {
+    // `If-None-Match` takes precedence, when present; and it uses weak
+    // comparison, ignoring any `W/` prefix.
+    if let Some(tags) = find_header(&request.headers, "If-None-Match") {
+        let weak = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+        return !tags.split(',').any(|tag| tag.trim() == "*" || weak(tag) == weak(etag));
+    }
+    match find_header(&request.headers, "If-Modified-Since").and_then(parse_http_date) {
+        // HTTP dates have whole-second resolution.
+        Some(since) => whole_seconds(modified) > since,
+        None => true
+    }
+}
+
+/// Return true if the request's `If-Range` header, if any, matches the
+/// current file, so that a range of it can be served.
+fn if_range_matches(request: &Request, etag: &str, last_modified: &str) -> bool {
+    match find_header(&request.headers, "If-Range") {
+        None => true,
+        // Entity tags must match strongly, so a weak tag never matches.
+        Some(tag) if tag.starts_with('"') || tag.starts_with("W/") => tag == etag,
+        Some(date) => date == last_modified
+    }
+}
+
+fn whole_seconds(time: SystemTime) -> SystemTime {
+    let secs = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+/// The part of a file a `Range` header asks for.
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    Whole,
+    /// Inclusive start and end offsets.
+    Part(u64, u64),
+    Unsatisfiable
+}
+
+/// Interpret a `Range` header for a file of length `len`. Headers we don't
+/// understand, or that ask for several ranges, get the whole file.
+fn parse_range(header: &str, len: u64) -> ByteRange {
+    let spec = match header.trim().strip_prefix("bytes=") {
+        Some(spec) if !spec.contains(',') => spec.trim(),
+        _ => return ByteRange::Whole
+    };
+    let (first, last) = match spec.split_once('-') {
+        Some(bounds) => bounds,
+        None => return ByteRange::Whole
+    };
+    let number = |s: &str| -> Option<u64> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        s.parse().ok()
+    };
+
+    match (first, last) {
+        // A suffix: the last so many bytes.
+        ("", suffix) => match number(suffix) {
+            None => ByteRange::Whole,
+            Some(0) => ByteRange::Unsatisfiable,
+            Some(_) if len == 0 => ByteRange::Unsatisfiable,
+            Some(n) => ByteRange::Part(len.saturating_sub(n), len - 1)
+        },
+        (first, last) => {
+            let start = match number(first) {
+                Some(start) => start,
+                None => return ByteRange::Whole
+            };
+            let end = if last.is_empty() {
+                u64::MAX
+            } else {
+                match number(last) {
+                    Some(end) if end >= start => end,
+                    _ => return ByteRange::Whole
+                }
+            };
+            if start >= len {
+                ByteRange::Unsatisfiable
+            } else {
+                ByteRange::Part(start, end.min(len - 1))
+            }
+        }
+    }
+}
+
+/// Return an HTML page listing the contents of the directory `path`. Unless
+/// it's the root directory, include a link to its parent.
+fn list_directory(request: &Request, path: &Path, at_root: bool) -> io::Result<Response> {
+    let mut entries = vec![];
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let mut name = entry.file_name().to_string_lossy().into_owned();
+        if entry.file_type()?.is_dir() {
+            name.push('/');
+        }
+        entries.push(name);
+    }
+    entries.sort();
+
+    let title = html_escape(&percent_decode(request.path(), false)
+                                .unwrap_or_else(|_| request.path().to_string()));
+    let mut html = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+                            <title>Index of {0}</title>\n</head>\n<body>\n\
+                            <h1>Index of {0}</h1>\n<ul>\n", title);
+    if !at_root {
+        html.push_str("<li><a href=\"../\">../</a></li>\n");
+    }
+    for name in entries {
+        let mut href = String::new();
+        percent_encode(&name, true, &mut href);
+        html.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n",
+                               html_escape(&href), html_escape(&name)));
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+
+    let mut headers = HashMap::new();
+    headers.insert("Content-Type".to_string(), "text/html; charset=utf-8".to_string());
+    Ok(Response { code: 200, headers, body: html.into_bytes() })
+}
+
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+/// Return the media type for files with the given extension.
+fn content_type(extension: &str) -> &'static str {
+    match extension.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "pdf" => "application/pdf",
+        "wasm" => "application/wasm",
+        "zip" => "application/zip",
+        "mp4" => "video/mp4",
+        "mp3" => "audio/mpeg",
+        _ => "application/octet-stream"
+    }
+}
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun",
+                            "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+/// Format `time` as an HTTP date, like `Sun, 06 Nov 1994 08:49:37 GMT`.
+/// Times before 1970 are clamped to the epoch.
+fn http_date(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let days = (secs / 86400) as i64;
+    let (year, month, day) = civil_from_days(days);
+    let weekday = WEEKDAYS[(days + 4).rem_euclid(7) as usize]; // 1970-01-01 was a Thursday.
+    format!("{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+            weekday, day, MONTHS[month as usize - 1], year,
+            secs % 86400 / 3600, secs % 3600 / 60, secs % 60)
+}
+
+/// Parse an HTTP date in the preferred format that `http_date` produces.
+/// We don't accept the obsolete formats.
+fn parse_http_date(date: &str) -> Option<SystemTime> {
+    let fields: Vec<&str> = date.split(' ').collect();
+    let (weekday, day, month, year, time, zone) = match fields[..] {
+        [weekday, day, month, year, time, zone] => (weekday, day, month, year, time, zone),
+        _ => return None
+    };
+    let weekday = weekday.strip_suffix(',')?;
+    if !WEEKDAYS.contains(&weekday) || zone != "GMT" || day.len() != 2 || year.len() != 4 {
+        return None;
+    }
+    let number = |s: &str| -> Option<u64> {
+        if s.bytes().all(|b| b.is_ascii_digit()) { s.parse().ok() } else { None }
+    };
+    let day = number(day)?;
+    let month = MONTHS.iter().position(|&m| m == month)? as u64 + 1;
+    let year = number(year)?;
+    let hms: Vec<u64> = time.split(':').map(number).collect::<Option<_>>()?;
+    let (hour, minute, second) = match hms[..] {
+        [h, m, s] if h < 24 && m < 60 && s < 61 => (h, m, s),
+        _ => return None
+    };
+    if !(1..=31).contains(&day) || year < 1970 {
+        return None;
+    }
+
+    let days = days_from_civil(year as i64, month as u32, day as u32);
+    let secs = days as u64 * 86400 + hour * 3600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+// Conversions between days since 1970-01-01 and dates in the proleptic
+// Gregorian calendar, from Howard Hinnant's "chrono-Compatible Low-Level
+// Date Algorithms".
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524
+                       - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_from_march + 2) / 5 + 1) as u32;
+    let month = if month_from_march < 10 { month_from_march + 3 } else { month_from_march - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month as u32, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{http_date, parse_http_date, parse_range, ByteRange, StaticFiles};
+    use crate::{BasicRouter, Request, Response};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn dates() {
+        let time = UNIX_EPOCH + Duration::from_secs(784111777);
+        assert_eq!(http_date(time), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(time));
+        assert_eq!(http_date(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 GMT");
+        let leap = UNIX_EPOCH + Duration::from_secs(951782400);
+        assert_eq!(http_date(leap), "Tue, 29 Feb 2000 00:00:00 GMT");
+        assert_eq!(parse_http_date(&http_date(leap)), Some(leap));
+
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 PST"), None);
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 25:49:37 GMT"), None);
+        assert_eq!(parse_http_date("Sun, 6 Nov 1994 08:49:37 GMT"), None);
+    }
+
+    #[test]
+    fn ranges() {
+        assert_eq!(parse_range("bytes=0-9", 100), ByteRange::Part(0, 9));
+        assert_eq!(parse_range("bytes=90-", 100), ByteRange::Part(90, 99));
+        assert_eq!(parse_range("bytes=90-200", 100), ByteRange::Part(90, 99));
+        assert_eq!(parse_range("bytes=-10", 100), ByteRange::Part(90, 99));
+        assert_eq!(parse_range("bytes=-200", 100), ByteRange::Part(0, 99));
+        assert_eq!(parse_range("bytes=100-", 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=-0", 100), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-", 0), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=9-0", 100), ByteRange::Whole);
+        assert_eq!(parse_range("bytes=0-1,5-6", 100), ByteRange::Whole);
+        assert_eq!(parse_range("bytes=+1-2", 100), ByteRange::Whole);
+        assert_eq!(parse_range("lines=0-1", 100), ByteRange::Whole);
+    }
+
+    /// Create a fresh directory of files to serve, returning its path.
+    fn fixture(name: &str) -> PathBuf {
+        let root = std::env::temp_dir()
+            .join(format!("basic-router-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("public/docs")).unwrap();
+        fs::create_dir_all(root.join("public/site")).unwrap();
+        fs::write(root.join("public/hello.txt"), "Hello, world!\n").unwrap();
+        fs::write(root.join("public/style.CSS"), "body {}").unwrap();
+        fs::write(root.join("public/docs/a <b>.md"), "# A").unwrap();
+        fs::write(root.join("public/site/index.html"), "<p>home</p>").unwrap();
+        fs::write(root.join("secret.txt"), "hunter2").unwrap();
+        root
+    }
+
+    fn get(router: &BasicRouter, url: &str, headers: &[(&str, &str)]) -> Response {
+        router.handle(Request {
+            method: "GET".to_string(),
+            url: url.to_string(),
+            headers: headers.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect(),
+            body: vec![]
+        })
+    }
+
+    #[test]
+    fn serve_files() {
+        let root = fixture("serve");
+        let mut router = BasicRouter::new();
+        router.add_static("/static", StaticFiles::new(root.join("public")));
+
+        let response = get(&router, "/static/hello.txt", &[]);
+        assert_eq!(response.code, 200);
+        assert_eq!(response.body, b"Hello, world!\n");
+        assert_eq!(response.headers["Content-Type"], "text/plain; charset=utf-8");
+        assert_eq!(response.headers["Accept-Ranges"], "bytes");
+        let etag = response.headers["ETag"].clone();
+        let last_modified = response.headers["Last-Modified"].clone();
+
+        assert_eq!(get(&router, "/static/style.CSS", &[]).headers["Content-Type"],
+                   "text/css; charset=utf-8");
+        assert_eq!(get(&router, "/static/docs/a%20%3Cb%3E.md", &[]).body, b"# A");
+
+        // HEAD comes for free.
+        let mut head = Request {
+            method: "HEAD".to_string(),
+            url: "/static/hello.txt".to_string(),
+            headers: HashMap::new(),
+            body: vec![]
+        };
+        let response = router.handle_request(&head);
+        assert_eq!((response.code, response.headers["Content-Length"].as_str()), (200, "14"));
+        assert!(response.body.is_empty());
+
+        // No escaping the root directory.
+        for url in &["/static/../secret.txt", "/static/%2e%2e/secret.txt",
+                     "/static/docs/..%2f..%2fsecret.txt", "/static/missing.txt",
+                     "/static/hello.txt/"] {
+            assert_eq!(get(&router, url, &[]).code, 404, "{}", url);
+        }
+        head.url = "/static/..%2Fsecret.txt".to_string();
+        assert_eq!(router.handle(head).code, 404);
+
+        // Conditional requests.
+        let response = get(&router, "/static/hello.txt", &[("If-None-Match", &etag)]);
+        assert_eq!((response.code, &response.headers["ETag"]), (304, &etag));
+        assert!(response.body.is_empty());
+        let weak = format!("\"nope\", W/{}", etag);
+        assert_eq!(get(&router, "/static/hello.txt", &[("if-none-match", &weak)]).code, 304);
+        assert_eq!(get(&router, "/static/hello.txt", &[("If-None-Match", "\"nope\"")]).code, 200);
+        assert_eq!(get(&router, "/static/hello.txt",
+                       &[("If-Modified-Since", &last_modified)]).code, 304);
+        assert_eq!(get(&router, "/static/hello.txt",
+                       &[("If-Modified-Since", "Thu, 01 Jan 1970 00:00:00 GMT")]).code, 200);
+        // `If-None-Match` trumps `If-Modified-Since`.
+        assert_eq!(get(&router, "/static/hello.txt",
+                       &[("If-Modified-Since", &last_modified),
+                         ("If-None-Match", "\"nope\"")]).code, 200);
+
+        // Ranges.
+        let response = get(&router, "/static/hello.txt", &[("Range", "bytes=7-11")]);
+        assert_eq!((response.code, response.body.as_slice()), (206, &b"world"[..]));
+        assert_eq!(response.headers["Content-Range"], "bytes 7-11/14");
+        let response = get(&router, "/static/hello.txt", &[("Range", "bytes=-2")]);
+        assert_eq!((response.code, response.body.as_slice()), (206, &b"!\n"[..]));
+        let response = get(&router, "/static/hello.txt", &[("Range", "bytes=14-")]);
+        assert_eq!((response.code, response.headers["Content-Range"].as_str()),
+                   (416, "bytes */14"));
+        let response = get(&router, "/static/hello.txt",
+                           &[("Range", "bytes=0-4"), ("If-Range", &etag)]);
+        assert_eq!(response.code, 206);
+        let response = get(&router, "/static/hello.txt",
+                           &[("Range", "bytes=0-4"), ("If-Range", "\"stale\"")]);
+        assert_eq!((response.code, response.body.len()), (200, 14));
+
+        // Directories have no listing by default, but do get index files.
+        assert_eq!(get(&router, "/static/docs/", &[]).code, 404);
+        let response = get(&router, "/static/site?x=1", &[]);
+        assert_eq!((response.code, response.headers["Location"].as_str()),
+                   (301, "/static/site/?x=1"));
+        assert_eq!(get(&router, "/static/site/", &[]).body, b"<p>home</p>");
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks() {
+        use std::os::unix::fs::symlink;
+
+        let root = fixture("symlinks");
+        symlink(root.join("public/hello.txt"), root.join("public/alias.txt")).unwrap();
+        symlink(root.join("secret.txt"), root.join("public/leak.txt")).unwrap();
+        symlink(&root, root.join("public/docs/up")).unwrap();
+        let mut router = BasicRouter::new();
+        router.add_static("/static", StaticFiles::new(root.join("public")));
+
+        // Links within the root directory are fine; links out of it aren't.
+        assert_eq!(get(&router, "/static/alias.txt", &[]).body, b"Hello, world!\n");
+        for url in &["/static/leak.txt", "/static/docs/up/secret.txt", "/static/docs/up/"] {
+            assert_eq!(get(&router, url, &[]).code, 404, "{}", url);
+        }
+
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn directory_listings() {
+        let root = fixture("listing");
+        let mut router = BasicRouter::new();
+        router.group("/files", |group| {
+            group.add_static("/", StaticFiles::new(root.join("public"))
+                                     .listing(true)
+                                     .index_file(None));
+        });
+
+        let response = get(&router, "/files", &[]);
+        assert_eq!((response.code, response.headers["Location"].as_str()), (301, "/files/"));
+
+        let response = get(&router, "/files/", &[]);
+        assert_eq!(response.headers["Content-Type"], "text/html; charset=utf-8");
+        let html = String::from_utf8(response.body).unwrap();
+        assert!(html.contains("<h1>Index of /files/</h1>"));
+        assert!(html.contains("<ul>\n\
+                               <li><a href=\"docs/\">docs/</a></li>\n\
+                               <li><a href=\"hello.txt\">hello.txt</a></li>\n\
+                               <li><a href=\"site/\">site/</a></li>\n\
+                               <li><a href=\"style.CSS\">style.CSS</a></li>\n"), "{}", html);
+
+        let html = String::from_utf8(get(&router, "/files/docs/", &[]).body).unwrap();
+        assert!(html.contains("<li><a href=\"../\">../</a></li>"));
+        assert!(html.contains("<li><a href=\"a%20%3Cb%3E.md\">a &lt;b&gt;.md</a></li>"),
+                "{}", html);
+
+        let html = String::from_utf8(get(&router, "/files/site/", &[]).body).unwrap();
+        assert!(html.contains("index.html"));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,91 @@
+//! Route groups: sets of routes that share a path prefix and middleware.
+//!
+//! A group collects routes and middleware, and hands them to its router (or
+//! to its enclosing group) once the closure that fills it in returns. So the
+//! group's middleware applies to all its routes, whether it was added
+//! before or after them. Nested groups' prefixes are appended to those of
+//! the groups around them, and their middleware runs inside.
+
+use crate::extract::Handler;
+use crate::files::{self, StaticFiles};
+use crate::middleware::Middleware;
+use crate::reverse::{join_paths, Names, RouteName};
+use crate::{Params, Request, Response, Route};
+use std::sync::Arc;
+
+pub struct Group {
+    prefix: String,
+    middleware: Vec<Arc<dyn Middleware>>,
+    routes: Vec<(String, String, Route)>,
+    names: Names,
+}
+
+impl Group {
+    pub(crate) fn new(prefix: &str) -> Group {
+        Group {
+            prefix: prefix.to_string(),
+            middleware: vec![],
+            routes: vec![],
+            names: Names::default(),
+        }
+    }
+
+    /// Run `middleware` around every route in this group.
+    pub fn wrap<M: Middleware + 'static>(&mut self, middleware: M) {
+        self.middleware.push(Arc::new(middleware));
+    }
+
+    /// Add a route to this group, relative to its prefix. See
+    /// `BasicRouter::add_route`.
+    pub fn add_route<C>(&mut self, method: &str, url: &str, callback: C) -> RouteName<'_>
+        where C: Fn(&Request, &Params) -> Response + Send + Sync + 'static
+    {
+        self.push(method, url, Route::new(callback))
+    }
+
+    /// Add an async route to this group, relative to its prefix. See
+    /// `BasicRouter::add_async_route`.
+    pub fn add_async_route<H: Handler<Args>, Args>(&mut self, method: &str, url: &str,
+                                                   handler: H) -> RouteName<'_>
+    {
+        self.push(method, url, Route::new_async(handler))
+    }
+
+    /// Serve the files in `files`' directory under `prefix`, relative to
+    /// this group's prefix. See `BasicRouter::add_static`.
+    pub fn add_static(&mut self, prefix: &str, files: StaticFiles) {
+        for (url, route) in files::routes(prefix, files) {
+            self.push("GET", &url, route);
+        }
+    }
+
+    fn push(&mut self, method: &str, url: &str, route: Route) -> RouteName<'_> {
+        self.routes.push((method.to_string(), url.to_string(), route));
+        RouteName::new(&mut self.names, url)
+    }
+
+    /// Add a group nested within this one, with routes under `prefix`,
+    /// filled in by `build`.
+    pub fn group<F: FnOnce(&mut Group)>(&mut self, prefix: &str, build: F) {
+        let mut inner = Group::new(prefix);
+        build(&mut inner);
+        let (routes, names) = inner.finish();
+        self.routes.extend(routes);
+        self.names.extend("", names);
+    }
+
+    /// Return this group's routes, with this group's prefix applied and its
+    /// middleware outside their own, and its route names.
+    pub(crate) fn finish(self) -> (Vec<(String, String, Route)>, Names) {
+        let Group { prefix, middleware, routes, names } = self;
+        let routes = routes.into_iter()
+            .map(|(method, url, mut route)| {
+                route.middleware.splice(0..0, middleware.iter().cloned());
+                (method, join_paths(&prefix, &url), route)
+            })
+            .collect();
+        let mut prefixed = Names::default();
+        prefixed.extend(&prefix, names);
+        (routes, prefixed)
+    }
+}
@@ -1,48 +1,292 @@
 #![allow(dead_code)]
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 
-struct Request {
-    method: String,
-    url: String,
-    headers: HashMap<String, String>,
-    body: Vec<u8>
+pub mod extract;
+pub mod files;
+pub mod group;
+pub mod middleware;
+pub mod reverse;
+pub mod server;
+pub mod tree;
+
+use extract::{Handler, StateMap};
+pub use files::StaticFiles;
+pub use group::Group;
+pub use middleware::{Middleware, Next};
+use reverse::{join_paths, Names, RouteName, UrlError};
+pub use server::Server;
+pub use tree::Params;
+use tree::Tree;
+
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    pub url: String,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>
+}
+
+impl Request {
+    // The path part of the URL, without any query string.
+    pub fn path(&self) -> &str {
+        match self.url.find('?') {
+            Some(i) => &self.url[..i],
+            None => &self.url
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Response {
+    pub code: u32,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>
 }
 
-struct Response {
-    code: u32,
-    headers: HashMap<String, String>,
-    body: Vec<u8>
+pub type BoxedCallback = Box<dyn Fn(&Request, &Params) -> Response + Send + Sync>;
+
+// What a route ultimately calls: either a `BoxedCallback`, or an async
+// handler, which also needs the router's state.
+type Endpoint = Box<dyn Fn(&Request, &Params, &StateMap) -> Response + Send + Sync>;
+
+// An endpoint for one method on one route pattern, together with the
+// middleware of the groups it was added in, outermost first, and the state
+// of the routers it was mounted from, innermost first.
+pub(crate) struct Route {
+    middleware: Vec<Arc<dyn Middleware>>,
+    state: Vec<Arc<StateMap>>,
+    endpoint: Endpoint
+}
+
+impl Route {
+    fn new<C>(callback: C) -> Route
+        where C: Fn(&Request, &Params) -> Response + Send + Sync + 'static
+    {
+        let endpoint = move |request: &Request, params: &Params, _: &StateMap| {
+            callback(request, params)
+        };
+        Route { middleware: vec![], state: vec![], endpoint: Box::new(endpoint) }
+    }
+
+    fn new_async<H: Handler<Args>, Args>(handler: H) -> Route {
+        let endpoint = move |request: &Request, params: &Params, state: &StateMap| {
+            handler.call(request, params, state)
+        };
+        Route { middleware: vec![], state: vec![], endpoint: Box::new(endpoint) }
+    }
 }
 
-type BoxedCallback = Box<dyn Fn(&Request) -> Response>;
+// The routes for a single route pattern, keyed by method.
+type Methods = BTreeMap<String, Route>;
 
-struct BasicRouter {
-    routes: HashMap<String, BoxedCallback>
+pub struct BasicRouter {
+    routes: Tree<Methods>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    state: StateMap,
+    names: Names
 }
 
 impl BasicRouter {
     // Create an empty router.
-    fn new() -> BasicRouter {
-        BasicRouter { routes: HashMap::new() }
+    pub fn new() -> BasicRouter {
+        BasicRouter {
+            routes: Tree::new(),
+            middleware: vec![],
+            state: StateMap::new(),
+            names: Names::default()
+        }
+    }
+
+    // Share `value` with all async handlers, which can get at it with the
+    // `State<T>` extractor. A router holds at most one value of each type.
+    pub fn add_state<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.state.insert(value);
+    }
+
+    // Run `middleware` around every request this router handles.
+    pub fn wrap<M: Middleware + 'static>(&mut self, middleware: M) {
+        self.middleware.push(Arc::new(middleware));
+    }
+
+    // Add a group of routes under `prefix`, sharing middleware, filled in
+    // by `build`. Within the group, the route `/` refers to `prefix` itself.
+    pub fn group<F: FnOnce(&mut Group)>(&mut self, prefix: &str, build: F) {
+        let mut group = Group::new(prefix);
+        build(&mut group);
+        let (routes, names) = group.finish();
+        for (method, url, route) in routes {
+            self.insert_route(&method, &url, route);
+        }
+        self.names.extend("", names);
     }
 
-    // Add a route to the router.
-    fn add_route<C>(&mut self, url: &str, callback: C)
-        where C: Fn(&Request) -> Response + 'static
+    // Move all of `router`'s routes into this one, under `prefix`.
+    //
+    // The mounted router's middleware runs around its own routes, inside
+    // this router's middleware; but requests under `prefix` that match none
+    // of its routes never reach it. Its route names come along too.
+    //
+    // The mounted router's handlers keep its state: where both routers have
+    // state of the same type, they see the mounted router's value, and this
+    // router's handlers see this router's. For other types, they see this
+    // router's state, including values added after mounting.
+    pub fn mount(&mut self, prefix: &str, router: BasicRouter) {
+        let BasicRouter { routes, middleware, state, names } = router;
+        let state = Arc::new(state);
+        for (pattern, methods) in routes.into_entries() {
+            let url = join_paths(prefix, &pattern);
+            for (method, mut route) in methods {
+                route.middleware.splice(0..0, middleware.iter().cloned());
+                if !state.is_empty() {
+                    route.state.push(state.clone());
+                }
+                self.insert_route(&method, &url, route);
+            }
+        }
+        self.names.extend(prefix, names);
+    }
+
+    // Add a route to the router, for requests with the given method. The
+    // route may contain `:name` segments, which match any one path segment,
+    // and may end with a `*name` segment, which matches the rest of the
+    // path; see the `tree` module for details. The route can be named for
+    // use with `url_for` by calling `name` on the result.
+    pub fn add_route<C>(&mut self, method: &str, url: &str, callback: C) -> RouteName<'_>
+        where C: Fn(&Request, &Params) -> Response + Send + Sync + 'static
+    {
+        self.insert_route(method, url, Route::new(callback));
+        RouteName::new(&mut self.names, url)
+    }
+
+    // Add a route with an async handler: a function whose arguments are all
+    // extractors, returning a future of a `Response`. See the `extract`
+    // module for details.
+    pub fn add_async_route<H: Handler<Args>, Args>(&mut self, method: &str, url: &str,
+                                                   handler: H) -> RouteName<'_>
     {
-        self.routes.insert(url.to_string(), Box::new(callback));
+        self.insert_route(method, url, Route::new_async(handler));
+        RouteName::new(&mut self.names, url)
+    }
+
+    // Serve the files in `files`' directory under `prefix`, for GET and HEAD
+    // requests. See the `files` module for details.
+    pub fn add_static(&mut self, prefix: &str, files: StaticFiles) {
+        for (url, route) in files::routes(prefix, files) {
+            self.insert_route("GET", &url, route);
+        }
+    }
+
+    // Return the URL for the route named `name`, with `params` supplying
+    // values for its placeholders, escaped as needed. It's an error if
+    // there's no such route, or a placeholder has no value.
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        self.names.url_for(name, params)
+    }
+
+    fn insert_route(&mut self, method: &str, url: &str, route: Route) {
+        match self.routes.get_mut(url) {
+            Some(methods) => {
+                methods.insert(method.to_string(), route);
+            }
+            None => {
+                let mut methods = Methods::new();
+                methods.insert(method.to_string(), route);
+                self.routes.insert(url, methods);
+            }
+        }
     }
 }
 
+impl Default for BasicRouter {
+    fn default() -> BasicRouter {
+        BasicRouter::new()
+    }
+}
 
 impl BasicRouter {
-    fn handle_request(&self, request: &Request) -> Response {
-        match self.routes.get(&request.url) {
-            None => not_found_response(),
-            Some(callback) => callback(request)
+    // Handle `request`, passing it through the router's middleware and then
+    // on to the callback for its method and path.
+    pub fn handle(&self, mut request: Request) -> Response {
+        let dispatch = |request: &mut Request| self.dispatch(request);
+        Next::new(&self.middleware, &dispatch).run(&mut request)
+    }
+
+    // Like `handle`, but for a borrowed request.
+    pub fn handle_request(&self, request: &Request) -> Response {
+        self.handle(request.clone())
+    }
+
+    // Dispatch `request` to the callback for its method and path.
+    //
+    // If the path matches a route but the method doesn't, answer with 405
+    // Method Not Allowed, unless the method is HEAD and the route has a GET
+    // callback, in which case we run that and drop the body; or the method
+    // is OPTIONS, in which case we just list the methods the route allows.
+    fn dispatch(&self, request: &mut Request) -> Response {
+        let (methods, params) = match self.routes.lookup(request.path()) {
+            None => return not_found_response(),
+            Some(found) => found
+        };
+
+        if let Some(route) = methods.get(&request.method) {
+            return self.run_route(route, request, &params);
+        }
+
+        match request.method.as_str() {
+            "HEAD" if methods.contains_key("GET") => {
+                let mut response = self.run_route(&methods["GET"], request, &params);
+                let length = response.body.len().to_string();
+                response.headers.entry("Content-Length".to_string()).or_insert(length);
+                response.body.clear();
+                response
+            }
+            "OPTIONS" => {
+                let mut headers = HashMap::new();
+                headers.insert("Allow".to_string(), allowed_methods(methods));
+                Response { code: 204, headers, body: vec![] }
+            }
+            _ => method_not_allowed_response(allowed_methods(methods))
         }
     }
+
+    // Pass `request` through `route`'s middleware to its endpoint.
+    fn run_route(&self, route: &Route, request: &mut Request, params: &Params) -> Response {
+        let layered;
+        let state = if route.state.is_empty() {
+            &self.state
+        } else {
+            let layers = route.state.iter().map(|state| &**state);
+            layered = StateMap::layered(layers.chain(Some(&self.state)));
+            &layered
+        };
+        let endpoint = |request: &mut Request| (route.endpoint)(request, params, state);
+        Next::new(&route.middleware, &endpoint).run(request)
+    }
+}
+
+// Return the value of the `Allow` header for a route with `methods`: the
+// methods it has callbacks for, plus those we answer automatically.
+fn allowed_methods(methods: &Methods) -> String {
+    let mut allowed: Vec<&str> = methods.keys().map(String::as_str).collect();
+    if methods.contains_key("GET") {
+        allowed.push("HEAD");
+    }
+    allowed.push("OPTIONS");
+    allowed.sort_unstable();
+    allowed.dedup();
+    allowed.join(", ")
+}
+
+fn method_not_allowed_response(allow: String) -> Response {
+    let mut headers = HashMap::new();
+    headers.insert("Allow".to_string(), allow);
+    Response {
+        code: 405,
+        headers,
+        body: b"<h1>Method not allowed</h1>".to_vec()
+    }
 }
 
 fn not_found_response() -> Response {
@@ -70,8 +314,12 @@ fn get_gcd_response(_req: &Request) -> Response {
 }
 
 fn req(url: &str) -> Request {
+    req_with_method("GET", url)
+}
+
+fn req_with_method(method: &str, url: &str) -> Request {
     Request {
-        method: "GET".to_string(),
+        method: method.to_string(),
         url: url.to_string(),
         headers: HashMap::new(),
         body: vec![]
@@ -81,10 +329,346 @@ fn req(url: &str) -> Request {
 #[test]
 fn test_router() {
     let mut router = BasicRouter::new();
-    router.add_route("/", |_| get_form_response());
-    router.add_route("/gcd", |req| get_gcd_response(req));
+    router.add_route("GET", "/", |_, _| get_form_response());
+    router.add_route("GET", "/gcd", |req, _| get_gcd_response(req));
 
     assert_eq!(router.handle_request(&req("/piano")).code, 404);
     assert_eq!(router.handle_request(&req("/")).code, 200);
     assert_eq!(router.handle_request(&req("/gcd")).code, 500);
 }
+
+#[test]
+fn test_router_params() {
+    fn echo(params: &Params) -> Response {
+        let text: Vec<String> = params.iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        Response {
+            code: 200,
+            headers: HashMap::new(),
+            body: text.join("&").into_bytes()
+        }
+    }
+
+    let mut router = BasicRouter::new();
+    router.add_route("GET", "/users/new", |_, _| get_form_response());
+    router.add_route("GET", "/users/:id", |_, params| echo(params));
+    router.add_route("GET", "/users/:id/posts/:post", |_, params| echo(params));
+    router.add_route("GET", "/static/*path", |_, params| echo(params));
+
+    let body = |url| router.handle_request(&req(url)).body;
+    assert_eq!(body("/users/new"), b"<form>");
+    assert_eq!(body("/users/42"), b"id=42");
+    assert_eq!(body("/users/42?tab=posts"), b"id=42");
+    assert_eq!(body("/users/42/posts/7"), b"id=42&post=7");
+    assert_eq!(body("/static/css/site.css"), b"path=css/site.css");
+    assert_eq!(router.handle_request(&req("/users/42/posts")).code, 404);
+}
+
+#[test]
+fn test_router_methods() {
+    let mut router = BasicRouter::new();
+    router.add_route("GET", "/gcd", |_, _| get_form_response());
+    router.add_route("POST", "/gcd", |req, _| get_gcd_response(req));
+    router.add_route("DELETE", "/users/:id", |_, _| get_form_response());
+
+    let handle = |method, url| router.handle_request(&req_with_method(method, url));
+    assert_eq!(handle("GET", "/gcd").code, 200);
+    assert_eq!(handle("POST", "/gcd").code, 500);
+    assert_eq!(handle("POST", "/nowhere").code, 404);
+
+    let response = handle("PUT", "/gcd");
+    assert_eq!(response.code, 405);
+    assert_eq!(response.headers["Allow"], "GET, HEAD, OPTIONS, POST");
+
+    let response = handle("GET", "/users/7");
+    assert_eq!(response.code, 405);
+    assert_eq!(response.headers["Allow"], "DELETE, OPTIONS");
+
+    let response = handle("OPTIONS", "/gcd");
+    assert_eq!(response.code, 204);
+    assert_eq!(response.headers["Allow"], "GET, HEAD, OPTIONS, POST");
+    assert!(response.body.is_empty());
+
+    let response = handle("HEAD", "/gcd");
+    assert_eq!(response.code, 200);
+    assert_eq!(response.headers["Content-Length"], "6");
+    assert!(response.body.is_empty());
+    assert_eq!(handle("HEAD", "/users/7").code, 405);
+
+    // Explicit HEAD and OPTIONS callbacks take priority.
+    router.add_route("OPTIONS", "/gcd", |_, _| not_found_response());
+    router.add_route("HEAD", "/gcd", |req, _| get_gcd_response(req));
+    assert_eq!(router.handle_request(&req_with_method("OPTIONS", "/gcd")).code, 404);
+    assert_eq!(router.handle_request(&req_with_method("HEAD", "/gcd")).code, 500);
+}
+
+#[test]
+fn test_router_middleware() {
+    use std::sync::Mutex;
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let logger = |name: &'static str| {
+        let log = log.clone();
+        move |request: &mut Request, next: Next<'_>| {
+            log.lock().unwrap().push(format!("{} before {}", name, request.path()));
+            let response = next.run(request);
+            log.lock().unwrap().push(format!("{} after {}", name, response.code));
+            response
+        }
+    };
+
+    let mut router = BasicRouter::new();
+    router.wrap(logger("outer"));
+    router.wrap(|request: &mut Request, next: Next<'_>| {
+        let mut response = next.run(request);
+        response.headers.insert("Access-Control-Allow-Origin".to_string(), "*".to_string());
+        response
+    });
+    router.add_route("GET", "/", |_, _| get_form_response());
+    router.group("", |group| {
+        group.add_route("GET", "/admin", |req, _| Response {
+            code: 200,
+            headers: HashMap::new(),
+            body: req.headers["User"].clone().into_bytes()
+        });
+        group.group("", |inner| {
+            inner.wrap(logger("inner"));
+            inner.add_route("GET", "/admin/gcd", |req, _| get_gcd_response(req));
+        });
+        // Added after the routes, but still applies to them all.
+        group.wrap(logger("group"));
+        group.wrap(|request: &mut Request, next: Next<'_>| {
+            match request.headers.get("Authorization").map(String::as_str) {
+                Some("Bearer sesame") => {
+                    request.headers.insert("User".to_string(), "ali".to_string());
+                    next.run(request)
+                }
+                _ => Response { code: 401, headers: HashMap::new(), body: vec![] }
+            }
+        });
+    });
+
+    let take_log = || std::mem::take(&mut *log.lock().unwrap());
+
+    let response = router.handle(req("/"));
+    assert_eq!(response.code, 200);
+    assert_eq!(response.headers["Access-Control-Allow-Origin"], "*");
+    assert_eq!(take_log(), ["outer before /", "outer after 200"]);
+
+    // Router middleware sees requests that match no route.
+    let response = router.handle(req("/piano"));
+    assert_eq!(response.code, 404);
+    assert_eq!(response.headers["Access-Control-Allow-Origin"], "*");
+    assert_eq!(take_log(), ["outer before /piano", "outer after 404"]);
+
+    // Group middleware can short-circuit...
+    let response = router.handle(req("/admin/gcd"));
+    assert_eq!(response.code, 401);
+    assert_eq!(response.headers["Access-Control-Allow-Origin"], "*");
+    assert_eq!(take_log(), ["outer before /admin/gcd", "group before /admin/gcd",
+                            "group after 401", "outer after 401"]);
+
+    // ...or modify the request on its way to the callback.
+    let mut authorized = req("/admin");
+    authorized.headers.insert("Authorization".to_string(), "Bearer sesame".to_string());
+    assert_eq!(router.handle_request(&authorized).body, b"ali");
+    assert_eq!(take_log(), ["outer before /admin", "group before /admin",
+                            "group after 200", "outer after 200"]);
+
+    authorized.url = "/admin/gcd".to_string();
+    assert_eq!(router.handle(authorized).code, 500);
+    assert_eq!(take_log(), ["outer before /admin/gcd", "group before /admin/gcd",
+                            "inner before /admin/gcd", "inner after 500",
+                            "group after 500", "outer after 500"]);
+}
+
+#[test]
+fn test_router_async_handlers() {
+    use extract::{FromQuery, Headers, JsonBody, Query, State};
+    use json_macro::{json, Json};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Mutex;
+    use std::task::{Context, Poll};
+
+    // A stand-in for an async service: not ready the first time it's polled.
+    struct Slow(bool);
+    impl Future for Slow {
+        type Output = ();
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 {
+                return Poll::Ready(());
+            }
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    struct Db(Mutex<Vec<Json>>);
+
+    struct Page(usize);
+    impl FromQuery for Page {
+        fn from_query(pairs: Vec<(String, String)>) -> Result<Page, String> {
+            match pairs.iter().find(|(name, _)| name == "page") {
+                None => Ok(Page(1)),
+                Some((_, page)) => {
+                    page.parse().map(Page).map_err(|_| format!("bad page {:?}", page))
+                }
+            }
+        }
+    }
+
+    fn text(body: String) -> Response {
+        Response { code: 200, headers: HashMap::new(), body: body.into_bytes() }
+    }
+
+    async fn insert(State(db): State<Db>, JsonBody(value): JsonBody) -> Response {
+        Slow(false).await;
+        let mut rows = db.0.lock().unwrap();
+        rows.push(value);
+        text(format!("{} rows", rows.len()))
+    }
+
+    let mut router = BasicRouter::new();
+    router.add_async_route("POST", "/rows", insert);
+    router.add_async_route("GET", "/rows/:id",
+                           |State(db): State<Db>, params: Params, Query(page): Query<Page>| {
+        async move {
+            let id: usize = params.get("id").unwrap().parse().unwrap();
+            let rows = db.0.lock().unwrap();
+            match rows.get(id) {
+                Some(row) => text(format!("{} page {}", row, page.0)),
+                None => text(format!("no row {}", id))
+            }
+        }
+    });
+    router.group("", |group| {
+        group.wrap(|request: &mut Request, next: Next<'_>| {
+            request.headers.insert("X-Group".to_string(), "yes".to_string());
+            next.run(request)
+        });
+        group.add_async_route("GET", "/headers", |headers: Headers, query: Option<Query<Page>>| {
+            async move {
+                let page = query.map(|Query(page)| page.0);
+                text(format!("{:?} {:?}", headers.get("x-group"), page))
+            }
+        });
+    });
+    router.add_async_route("GET", "/nostate", |_: State<String>| async { get_form_response() });
+    router.add_state(Db(Mutex::new(vec![])));
+
+    let post = |body: &str, content_type: Option<&str>| {
+        let mut request = req_with_method("POST", "/rows");
+        request.body = body.as_bytes().to_vec();
+        if let Some(content_type) = content_type {
+            request.headers.insert("Content-Type".to_string(), content_type.to_string());
+        }
+        router.handle(request)
+    };
+    let body = |response: Response| (response.code, String::from_utf8(response.body).unwrap());
+
+    assert_eq!(body(post(r#"{"name": "ferris", "id": 18446744073709551615}"#, None)),
+               (200, "1 rows".to_string()));
+    assert_eq!(body(post("[1, 2]", Some("application/json; charset=utf-8"))),
+               (200, "2 rows".to_string()));
+    assert_eq!(body(post("[1, 2", None)),
+               (400, "malformed JSON body: expected ',' or ']' in array at line 1, column 6".to_string()));
+    assert_eq!(body(post("[]", Some("text/plain"))),
+               (400, "expected a JSON body, not text/plain".to_string()));
+
+    assert_eq!(body(router.handle(req("/rows/0"))),
+               (200, r#"{"name":"ferris","id":18446744073709551615} page 1"#.to_string()));
+    assert_eq!(body(router.handle(req("/rows/1?page=3"))),
+               (200, format!("{} page 3", json!([1, 2]))));
+    assert_eq!(body(router.handle(req("/rows/1?page=three"))),
+               (400, "bad query parameters: bad page \"three\"".to_string()));
+    assert_eq!(body(router.handle(req("/rows/1?page=%"))).0, 400);
+
+    assert_eq!(body(router.handle(req("/headers?page=2"))),
+               (200, "Some(\"yes\") Some(2)".to_string()));
+    assert_eq!(body(router.handle(req("/headers?page=x"))),
+               (200, "Some(\"yes\") None".to_string()));
+
+    assert_eq!(body(router.handle(req("/nostate"))),
+               (500, "router has no state of type alloc::string::String".to_string()));
+}
+
+#[test]
+fn test_router_groups_and_mounting() {
+    fn echo(req: &Request, params: &Params) -> Response {
+        let mut text = req.path().to_string();
+        for (name, value) in params.iter() {
+            text += &format!(" {}={}", name, value);
+        }
+        for (name, value) in &req.headers {
+            text += &format!(" [{}: {}]", name, value);
+        }
+        Response { code: 200, headers: HashMap::new(), body: text.into_bytes() }
+    }
+    fn tag(name: &'static str) -> impl Middleware {
+        move |request: &mut Request, next: Next<'_>| {
+            request.headers.insert("Via".to_string(), name.to_string());
+            next.run(request)
+        }
+    }
+
+    let mut users = BasicRouter::new();
+    users.wrap(tag("users"));
+    users.add_route("GET", "/", echo).name("users");
+    users.add_route("GET", "/:id", echo).name("user");
+    users.add_state(String::from("users state"));
+    users.add_async_route("GET", "/state/here", |state: extract::State<String>| async move {
+        Response { code: 200, headers: HashMap::new(), body: state.0.as_bytes().to_vec() }
+    });
+    users.add_async_route("GET", "/state/count", |count: extract::State<u32>| async move {
+        Response { code: 200, headers: HashMap::new(), body: count.0.to_string().into_bytes() }
+    });
+
+    let mut router = BasicRouter::new();
+    router.add_route("GET", "/", echo).name("home");
+    router.add_state(String::from("router state"));
+    router.add_async_route("GET", "/state", |state: extract::State<String>| async move {
+        Response { code: 200, headers: HashMap::new(), body: state.0.as_bytes().to_vec() }
+    });
+    router.group("/api", |api| {
+        api.add_route("GET", "/", echo).name("api");
+        api.group("/v1/", |v1| {
+            v1.wrap(tag("v1"));
+            v1.add_route("GET", "/files/*path", echo).name("file");
+            v1.add_route("POST", "/files/*path", echo).name("file");
+        });
+    });
+    router.mount("/api/v1/users", users);
+    router.add_state(7u32);
+
+    let body = |method, url| {
+        let response = router.handle(req_with_method(method, url));
+        (response.code, String::from_utf8(response.body).unwrap())
+    };
+    assert_eq!(body("GET", "/"), (200, "/".to_string()));
+    assert_eq!(body("GET", "/api"), (200, "/api".to_string()));
+    assert_eq!(body("GET", "/api/"), (404, "<h1>Page not found</h1>".to_string()));
+    assert_eq!(body("POST", "/api/v1/files/a/b"),
+               (200, "/api/v1/files/a/b path=a/b [Via: v1]".to_string()));
+    assert_eq!(body("GET", "/api/v1/users"), (200, "/api/v1/users [Via: users]".to_string()));
+    assert_eq!(body("GET", "/api/v1/users/7"),
+               (200, "/api/v1/users/7 id=7 [Via: users]".to_string()));
+    // Each router's handlers see its own state, and the mounted router's
+    // fall back on the outer router's for other types.
+    assert_eq!(body("GET", "/api/v1/users/state/here"), (200, "users state".to_string()));
+    assert_eq!(body("GET", "/state"), (200, "router state".to_string()));
+    assert_eq!(body("GET", "/api/v1/users/state/count"), (200, "7".to_string()));
+    assert_eq!(body("GET", "/users/7").0, 404);
+
+    assert_eq!(router.url_for("home", &[]), Ok("/".to_string()));
+    assert_eq!(router.url_for("api", &[]), Ok("/api".to_string()));
+    assert_eq!(router.url_for("file", &[("path", "docs/read me.txt")]),
+               Ok("/api/v1/files/docs/read%20me.txt".to_string()));
+    assert_eq!(router.url_for("users", &[]), Ok("/api/v1/users".to_string()));
+    assert_eq!(router.url_for("user", &[("id", "42")]), Ok("/api/v1/users/42".to_string()));
+    assert_eq!(router.url_for("user", &[]).unwrap_err().to_string(),
+               "route \"user\" needs a value for \"id\"");
+    assert_eq!(router.url_for("nobody", &[]), Err(UrlError::UnknownRoute("nobody".to_string())));
+}
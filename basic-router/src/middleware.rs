@@ -0,0 +1,50 @@
+//! Middleware: code that runs around route callbacks.
+//!
+//! A middleware receives the request and a `Next` standing for the rest of
+//! the chain. It can inspect or modify the request before passing it on
+//! with `next.run(request)`; return a response of its own without calling
+//! `next` at all; or modify the response `next.run` returns.
+//!
+//! Middleware added to a `BasicRouter` with `wrap` runs on every request,
+//! before the router even looks up the route, so it sees 404 and 405
+//! responses too, and may rewrite the URL it routes on. Middleware added to
+//! a `Group` runs only on requests that match one of the group's routes,
+//! after the router's own. In either case, middleware runs in the order it
+//! was added, with the first added outermost.
+
+use crate::{Request, Response};
+use std::sync::Arc;
+
+pub trait Middleware: Send + Sync {
+    fn handle(&self, request: &mut Request, next: Next<'_>) -> Response;
+}
+
+/// Any suitable closure can serve as middleware.
+impl<F> Middleware for F
+    where F: Fn(&mut Request, Next<'_>) -> Response + Send + Sync
+{
+    fn handle(&self, request: &mut Request, next: Next<'_>) -> Response {
+        self(request, next)
+    }
+}
+
+/// The remainder of a middleware chain, ending in a route callback.
+pub struct Next<'a> {
+    chain: &'a [Arc<dyn Middleware>],
+    endpoint: &'a dyn Fn(&mut Request) -> Response,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(chain: &'a [Arc<dyn Middleware>],
+                      endpoint: &'a dyn Fn(&mut Request) -> Response) -> Next<'a> {
+        Next { chain, endpoint }
+    }
+
+    /// Pass `request` to the rest of the chain, and return its response.
+    pub fn run(self, request: &mut Request) -> Response {
+        match self.chain.split_first() {
+            Some((first, rest)) => first.handle(request, Next::new(rest, self.endpoint)),
+            None => (self.endpoint)(request),
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Named routes, and reverse routing: building a URL from a route's name and
+//! values for its placeholders.
+
+use crate::tree::{tokenize, Token};
+use std::collections::HashMap;
+use std::fmt;
+
+/// A route just added, which may be given a name for use with
+/// `BasicRouter::url_for`.
+pub struct RouteName<'a> {
+    names: &'a mut Names,
+    pattern: String,
+}
+
+impl<'a> RouteName<'a> {
+    pub(crate) fn new(names: &'a mut Names, pattern: &str) -> RouteName<'a> {
+        RouteName { names, pattern: pattern.to_string() }
+    }
+
+    /// Name this route.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is already in use by another route. Since routes
+    /// for different methods on the same path share a URL, giving them the
+    /// same name is fine.
+    pub fn name(self, name: &str) {
+        self.names.insert(name, self.pattern);
+    }
+}
+
+/// A table of route patterns by name.
+#[derive(Default)]
+pub(crate) struct Names {
+    patterns: HashMap<String, String>,
+}
+
+impl Names {
+    pub(crate) fn insert(&mut self, name: &str, pattern: String) {
+        if let Some(existing) = self.patterns.get(name) {
+            assert!(*existing == pattern,
+                    "route name {:?} is already used for {:?}", name, existing);
+        }
+        self.patterns.insert(name.to_string(), pattern);
+    }
+
+    /// Add the names from `other`, whose patterns are relative to `prefix`.
+    pub(crate) fn extend(&mut self, prefix: &str, other: Names) {
+        for (name, pattern) in other.patterns {
+            self.insert(&name, join_paths(prefix, &pattern));
+        }
+    }
+
+    pub(crate) fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        let pattern = self.patterns.get(name)
+            .ok_or_else(|| UrlError::UnknownRoute(name.to_string()))?;
+        let mut url = String::new();
+        for token in tokenize(pattern) {
+            let (param, is_wildcard) = match token {
+                Token::Static(text) => {
+                    url.push_str(text);
+                    continue;
+                }
+                Token::Param(param) => (param, false),
+                Token::Wildcard(param) => (param, true),
+            };
+            let value = params.iter()
+                .find(|(n, _)| *n == param)
+                .map(|(_, value)| *value)
+                .filter(|value| is_wildcard || !value.is_empty())
+                .ok_or_else(|| UrlError::MissingParam {
+                    route: name.to_string(),
+                    param: param.to_string(),
+                })?;
+            percent_encode(value, is_wildcard, &mut url);
+        }
+        Ok(url)
+    }
+}
+
+/// An error from `BasicRouter::url_for`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UrlError {
+    /// No route has this name.
+    UnknownRoute(String),
+
+    /// The route has a placeholder no value was given for.
+    MissingParam { route: String, param: String },
+}
+
+impl fmt::Display for UrlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UrlError::UnknownRoute(name) => write!(f, "no route named {:?}", name),
+            UrlError::MissingParam { route, param } => {
+                write!(f, "route {:?} needs a value for {:?}", route, param)
+            }
+        }
+    }
+}
+
+impl std::error::Error for UrlError {}
+
+/// Append `path` to the route prefix `prefix`. The path `/` on its own
+/// refers to the prefix itself, so mounting `/` under `/api` gives `/api`.
+pub(crate) fn join_paths(prefix: &str, path: &str) -> String {
+    let prefix = prefix.trim_end_matches('/');
+    if prefix.is_empty() {
+        path.to_string()
+    } else if path == "/" {
+        prefix.to_string()
+    } else {
+        format!("{}{}", prefix, path)
+    }
+}
+
+/// Append `value` to `url`, escaping anything that isn't safe in a path
+/// segment. Slashes are escaped too, unless `keep_slashes` is true.
+pub(crate) fn percent_encode(value: &str, keep_slashes: bool, url: &mut String) {
+    for b in value.bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~!$&'()*+,;=:@".contains(&b)
+            || (keep_slashes && b == b'/')
+        {
+            url.push(b as char);
+        } else {
+            url.push_str(&format!("%{:02X}", b));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{join_paths, Names, UrlError};
+
+    #[test]
+    fn urls() {
+        let mut names = Names::default();
+        names.insert("home", "/".to_string());
+        names.insert("post", "/users/:id/posts/:post".to_string());
+        names.insert("file", "/static/*path".to_string());
+
+        assert_eq!(names.url_for("home", &[]), Ok("/".to_string()));
+        assert_eq!(names.url_for("post", &[("post", "hello world"), ("id", "a/b")]),
+                   Ok("/users/a%2Fb/posts/hello%20world".to_string()));
+        assert_eq!(names.url_for("file", &[("path", "css/site é.css")]),
+                   Ok("/static/css/site%20%C3%A9.css".to_string()));
+        assert_eq!(names.url_for("file", &[("path", "")]), Ok("/static/".to_string()));
+
+        assert_eq!(names.url_for("post", &[("id", "7")]),
+                   Err(UrlError::MissingParam { route: "post".to_string(),
+                                                param: "post".to_string() }));
+        assert_eq!(names.url_for("post", &[("id", ""), ("post", "1")]).unwrap_err().to_string(),
+                   "route \"post\" needs a value for \"id\"");
+        assert_eq!(names.url_for("nope", &[]), Err(UrlError::UnknownRoute("nope".to_string())));
+    }
+
+    #[test]
+    #[should_panic(expected = "route name \"home\" is already used for \"/\"")]
+    fn duplicate_names() {
+        let mut names = Names::default();
+        names.insert("home", "/".to_string());
+        names.insert("home", "/index".to_string());
+    }
+
+    #[test]
+    fn joining() {
+        assert_eq!(join_paths("", "/users"), "/users");
+        assert_eq!(join_paths("/api", "/users"), "/api/users");
+        assert_eq!(join_paths("/api/", "/users"), "/api/users");
+        assert_eq!(join_paths("/api", "/"), "/api");
+        assert_eq!(join_paths("/", "/"), "/");
+    }
+}
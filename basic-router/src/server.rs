@@ -0,0 +1,762 @@
+//! A small HTTP/1.1 server for a `BasicRouter`, using only the standard
+//! library.
+//!
+//! An acceptor thread hands incoming connections to a fixed pool of worker
+//! threads. Each worker reads requests from its connection, passes them to
+//! the router, and writes back the responses, for as long as the client
+//! keeps the connection alive. Request bodies may be sent with
+//! `Content-Length` or chunked transfer coding. A handler that panics gets
+//! its client a 500 response, and doesn't take the worker down with it.
+//!
+//! A worker waiting on an idle keep-alive connection closes it as soon as
+//! another connection is waiting for a worker, or the server is shutting
+//! down, so idle clients can't starve busy ones.
+//!
+//! Header names are stored in the `Request` in canonical capitalization, as
+//! in `Content-Type`, so that handlers needn't worry about the case the
+//! client used.
+
+use crate::{BasicRouter, Request, Response};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// The longest request line or header line we accept, in bytes.
+const MAX_LINE: usize = 8 * 1024;
+
+/// The most header lines we accept in a single request.
+const MAX_HEADERS: usize = 100;
+
+/// The largest request body we accept, in bytes.
+const MAX_BODY: usize = 16 * 1024 * 1024;
+
+/// How long a connection may sit idle before we close it.
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How often a worker waiting on an idle connection checks whether it
+/// should give up on it.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A running server. Dropping it shuts it down.
+pub struct Server {
+    local_addr: SocketAddr,
+    shared: Arc<Shared>,
+    acceptor: Option<JoinHandle<()>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+/// What the acceptor and workers share.
+struct Shared {
+    router: BasicRouter,
+    shutdown: AtomicBool,
+
+    /// The number of accepted connections no worker has taken yet.
+    waiting: AtomicUsize,
+}
+
+impl Server {
+    /// Start serving `router` on `addr`, with `threads` worker threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `threads` is zero.
+    pub fn bind<A: ToSocketAddrs>(addr: A, router: BasicRouter, threads: usize)
+                                  -> io::Result<Server>
+    {
+        assert!(threads > 0, "a server needs at least one worker thread");
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        let shared = Arc::new(Shared {
+            router,
+            shutdown: AtomicBool::new(false),
+            waiting: AtomicUsize::new(0),
+        });
+
+        let (sender, receiver) = mpsc::channel::<TcpStream>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads)
+            .map(|_| {
+                let receiver = receiver.clone();
+                let shared = shared.clone();
+                thread::spawn(move || loop {
+                    // Hold the lock only while waiting, not while serving.
+                    let stream = match receiver.lock().unwrap().recv() {
+                        Ok(stream) => stream,
+                        Err(_) => return,
+                    };
+                    shared.waiting.fetch_sub(1, Ordering::SeqCst);
+                    // A failing connection is the client's problem, not ours.
+                    let _ = serve_connection(stream, &shared);
+                })
+            })
+            .collect();
+
+        let acceptor = {
+            let shared = shared.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming() {
+                    if shared.shutdown.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        shared.waiting.fetch_add(1, Ordering::SeqCst);
+                        if sender.send(stream).is_err() {
+                            break;
+                        }
+                    }
+                }
+            })
+        };
+
+        Ok(Server { local_addr, shared, acceptor: Some(acceptor), workers })
+    }
+
+    /// Return the address the server is listening on. This is how to find
+    /// the port, when binding to port zero.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop accepting connections, and wait for the workers to finish
+    /// the requests they're serving. Idle keep-alive connections are closed
+    /// right away, rather than waiting for the client.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        let acceptor = match self.acceptor.take() {
+            Some(acceptor) => acceptor,
+            None => return,
+        };
+        self.shared.shutdown.store(true, Ordering::SeqCst);
+        // Wake the acceptor, which is probably blocked in `accept`. If this
+        // fails, the listener is already broken and the acceptor has exited.
+        let _ = TcpStream::connect(self.local_addr);
+        let _ = acceptor.join();
+        // The acceptor has dropped the channel's sender, so each worker
+        // exits once it finishes its current connection.
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Handle requests on `stream` until the client or the server is done with
+/// it.
+fn serve_connection(stream: TcpStream, shared: &Shared) -> io::Result<()> {
+    let mut writer = BufWriter::new(stream.try_clone()?);
+    let mut reader = BufReader::new(stream);
+
+    loop {
+        if !wait_for_request(&mut reader, shared)? {
+            return Ok(());
+        }
+        let head = match read_head(&mut reader) {
+            Ok(Some(head)) => head,
+            Ok(None) => return Ok(()),
+            Err(error) => return write_error(&mut writer, error),
+        };
+        if head.expect_continue {
+            writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+            writer.flush()?;
+        }
+        let mut request = head.request;
+        request.body = match read_body(&mut reader, &request.headers) {
+            Ok(body) => body,
+            Err(error) => return write_error(&mut writer, error),
+        };
+
+        let head_only = request.method == "HEAD";
+        let response = panic::catch_unwind(AssertUnwindSafe(|| shared.router.handle(request)))
+            .unwrap_or_else(|_| Response {
+                code: 500,
+                headers: HashMap::new(),
+                body: b"<h1>Internal Server Error</h1>".to_vec()
+            });
+        let keep_alive = head.keep_alive
+            && !shared.shutdown.load(Ordering::SeqCst)
+            && !header_has_token(&response.headers, "Connection", "close");
+        write_response(&mut writer, &response, head_only, keep_alive)?;
+        writer.flush()?;
+        if !keep_alive {
+            return Ok(());
+        }
+    }
+}
+
+/// Wait for the client to start sending a request, or close the
+/// connection. Return false if we should give up on the connection instead:
+/// because it's been idle too long, or another connection is waiting for a
+/// worker, or the server is shutting down.
+fn wait_for_request(reader: &mut BufReader<TcpStream>, shared: &Shared) -> io::Result<bool> {
+    reader.get_ref().set_read_timeout(Some(IDLE_POLL_INTERVAL))?;
+    let idle_since = Instant::now();
+    loop {
+        match reader.fill_buf() {
+            Ok(_) => break,
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock
+                || error.kind() == io::ErrorKind::TimedOut =>
+            {
+                if idle_since.elapsed() >= KEEP_ALIVE_TIMEOUT
+                    || shared.waiting.load(Ordering::SeqCst) > 0
+                    || shared.shutdown.load(Ordering::SeqCst)
+                {
+                    return Ok(false);
+                }
+            }
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+    // Once a request has begun, give the client the full timeout between
+    // reads.
+    reader.get_ref().set_read_timeout(Some(KEEP_ALIVE_TIMEOUT))?;
+    Ok(true)
+}
+
+/// Why we couldn't read a request.
+#[derive(Debug)]
+enum ReadError {
+    /// The connection failed, or timed out. There's no point replying.
+    Io(io::Error),
+
+    /// The request was unacceptable; reply with this status and close.
+    Status(u32),
+}
+
+impl From<io::Error> for ReadError {
+    fn from(error: io::Error) -> ReadError {
+        ReadError::Io(error)
+    }
+}
+
+fn write_error<W: Write>(writer: &mut W, error: ReadError) -> io::Result<()> {
+    match error {
+        ReadError::Io(error) => Err(error),
+        ReadError::Status(code) => {
+            let response = Response {
+                code,
+                headers: HashMap::new(),
+                body: format!("<h1>{}</h1>", reason_phrase(code)).into_bytes()
+            };
+            write_response(writer, &response, false, false)?;
+            writer.flush()
+        }
+    }
+}
+
+/// A request line and headers, read before the body.
+#[derive(Debug)]
+struct Head {
+    /// The request, without its body.
+    request: Request,
+
+    /// True if the client is willing to send another request on this
+    /// connection.
+    keep_alive: bool,
+
+    /// True if the client is waiting for `100 Continue` before sending the
+    /// body.
+    expect_continue: bool,
+}
+
+/// Read a request line and headers from `reader`. Return `None` if the
+/// connection closes cleanly before a new request begins.
+fn read_head<R: BufRead>(reader: &mut R) -> Result<Option<Head>, ReadError> {
+    // Clients may send stray blank lines between requests.
+    let line = loop {
+        match read_line(reader)? {
+            None => return Ok(None),
+            Some(line) if line.is_empty() => continue,
+            Some(line) => break line,
+        }
+    };
+
+    let mut parts = line.split(' ');
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version), None)
+            if !method.is_empty() && method.bytes().all(is_token_byte) && !target.is_empty() =>
+        {
+            (method, target, version)
+        }
+        _ => return Err(ReadError::Status(400)),
+    };
+    let http_1_1 = match version {
+        "HTTP/1.1" => true,
+        "HTTP/1.0" => false,
+        v if v.starts_with("HTTP/") => return Err(ReadError::Status(505)),
+        _ => return Err(ReadError::Status(400)),
+    };
+    let url = origin_form(target).ok_or(ReadError::Status(400))?;
+
+    let mut headers: HashMap<String, String> = HashMap::new();
+    loop {
+        let line = read_line(reader)?.ok_or(ReadError::Status(400))?;
+        if line.is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(ReadError::Status(431));
+        }
+        // Folded header values are obsolete, and we don't accept them.
+        let colon = line.find(':').ok_or(ReadError::Status(400))?;
+        let (name, value) = (&line[..colon], line[colon + 1..].trim_matches([' ', '\t']));
+        if name.is_empty() || !name.bytes().all(is_token_byte) {
+            return Err(ReadError::Status(400));
+        }
+        headers.entry(canonical_header_name(name))
+            .and_modify(|existing| {
+                existing.push_str(", ");
+                existing.push_str(value);
+            })
+            .or_insert_with(|| value.to_string());
+    }
+
+    let keep_alive = if http_1_1 {
+        !header_has_token(&headers, "Connection", "close")
+    } else {
+        header_has_token(&headers, "Connection", "keep-alive")
+    };
+    let expect_continue = http_1_1 && header_has_token(&headers, "Expect", "100-continue");
+
+    let request = Request {
+        method: method.to_string(),
+        url,
+        headers,
+        body: vec![]
+    };
+    Ok(Some(Head { request, keep_alive, expect_continue }))
+}
+
+/// Read the body of a request with the given headers.
+fn read_body<R: BufRead>(reader: &mut R, headers: &HashMap<String, String>)
+                         -> Result<Vec<u8>, ReadError>
+{
+    match (headers.get("Transfer-Encoding"), headers.get("Content-Length")) {
+        // A request with both is ambiguous, and a classic way to smuggle
+        // one request past a proxy inside another.
+        (Some(_), Some(_)) => Err(ReadError::Status(400)),
+        (Some(coding), None) => {
+            if coding.eq_ignore_ascii_case("chunked") {
+                read_chunked(reader)
+            } else {
+                Err(ReadError::Status(501))
+            }
+        }
+        (None, Some(length)) => {
+            if length.is_empty() || !length.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(ReadError::Status(400));
+            }
+            let length: usize = length.parse().map_err(|_| ReadError::Status(413))?;
+            if length > MAX_BODY {
+                return Err(ReadError::Status(413));
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body)?;
+            Ok(body)
+        }
+        (None, None) => Ok(vec![]),
+    }
+}
+
+/// Read a body in chunked transfer coding. We discard chunk extensions and
+/// trailers.
+fn read_chunked<R: BufRead>(reader: &mut R) -> Result<Vec<u8>, ReadError> {
+    let mut body = vec![];
+    loop {
+        let line = read_line(reader)?.ok_or(ReadError::Status(400))?;
+        let size = line.split(';').next().unwrap().trim_end_matches([' ', '\t']);
+        if size.is_empty() || size.len() > 8 || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ReadError::Status(400));
+        }
+        let size = usize::from_str_radix(size, 16).unwrap();
+        if size == 0 {
+            break;
+        }
+        if body.len() + size > MAX_BODY {
+            return Err(ReadError::Status(413));
+        }
+
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        if read_line(reader)?.as_deref() != Some("") {
+            return Err(ReadError::Status(400));
+        }
+    }
+
+    // Skip the trailer section, up to the blank line that ends it.
+    loop {
+        match read_line(reader)? {
+            None => return Err(ReadError::Status(400)),
+            Some(line) if line.is_empty() => return Ok(body),
+            Some(_) => {}
+        }
+    }
+}
+
+/// Read a line terminated by CRLF (or just LF), and return it without the
+/// terminator. Return `None` at end of input.
+fn read_line<R: BufRead>(reader: &mut R) -> Result<Option<String>, ReadError> {
+    let mut line = vec![];
+    reader.by_ref().take(MAX_LINE as u64 + 1).read_until(b'\n', &mut line)?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if line.pop() != Some(b'\n') {
+        // Either the line is too long, or the input ended in the middle.
+        return Err(ReadError::Status(if line.len() >= MAX_LINE { 431 } else { 400 }));
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    String::from_utf8(line).map(Some).map_err(|_| ReadError::Status(400))
+}
+
+/// Return the path and query of a request target, which may be either in
+/// origin form, like `/search?q=rust`, or absolute form, like
+/// `http://example.com/search?q=rust`. `*`, used with `OPTIONS`, is left as
+/// it is.
+fn origin_form(target: &str) -> Option<String> {
+    if target.starts_with('/') || target == "*" {
+        return Some(target.to_string());
+    }
+    let rest = target.strip_prefix("http://")
+        .or_else(|| target.strip_prefix("https://"))?;
+    match rest.find(['/', '?']) {
+        Some(i) if rest[i..].starts_with('/') => Some(rest[i..].to_string()),
+        Some(i) => Some(format!("/{}", &rest[i..])),
+        None => Some("/".to_string()),
+    }
+}
+
+/// Return true if `b` may appear in a method or header name.
+fn is_token_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b)
+}
+
+/// Capitalize each hyphen-separated word of a header name, as in
+/// `Content-Length`.
+fn canonical_header_name(name: &str) -> String {
+    let mut canonical = String::with_capacity(name.len());
+    let mut word_start = true;
+    for c in name.chars() {
+        canonical.push(if word_start { c.to_ascii_uppercase() } else { c.to_ascii_lowercase() });
+        word_start = c == '-';
+    }
+    canonical
+}
+
+/// Return true if the comma-separated header `name` includes `token`,
+/// ignoring case.
+fn header_has_token(headers: &HashMap<String, String>, name: &str, token: &str) -> bool {
+    headers.iter()
+        .filter(|(n, _)| n.eq_ignore_ascii_case(name))
+        .flat_map(|(_, value)| value.split(','))
+        .any(|t| t.trim().eq_ignore_ascii_case(token))
+}
+
+fn write_response<W: Write>(writer: &mut W, response: &Response, head_only: bool,
+                            keep_alive: bool) -> io::Result<()>
+{
+    // These statuses never have a body, nor a `Content-Length` for one.
+    let bodiless = (100..200).contains(&response.code)
+        || response.code == 204 || response.code == 304;
+
+    write!(writer, "HTTP/1.1 {} {}\r\n", response.code, reason_phrase(response.code))?;
+    let mut has_length = false;
+    for (name, value) in &response.headers {
+        if name.eq_ignore_ascii_case("Connection") {
+            continue;
+        }
+        has_length |= name.eq_ignore_ascii_case("Content-Length");
+        write!(writer, "{}: {}\r\n", name, value)?;
+    }
+    if !has_length && !bodiless {
+        write!(writer, "Content-Length: {}\r\n", response.body.len())?;
+    }
+    if !keep_alive {
+        writer.write_all(b"Connection: close\r\n")?;
+    }
+    writer.write_all(b"\r\n")?;
+    if !head_only && !bodiless {
+        writer.write_all(&response.body)?;
+    }
+    Ok(())
+}
+
+fn reason_phrase(code: u32) -> &'static str {
+    match code {
+        100 => "Continue",
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        206 => "Partial Content",
+        301 => "Moved Permanently",
+        302 => "Found",
+        304 => "Not Modified",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        416 => "Range Not Satisfiable",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        501 => "Not Implemented",
+        505 => "HTTP Version Not Supported",
+        _ => "Unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_body, read_head, Head, ReadError, Server};
+    use crate::{BasicRouter, Params, Request, Response};
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpStream;
+    use std::time::Instant;
+
+    fn parse(input: &str) -> Result<Head, ReadError> {
+        let mut reader = input.as_bytes();
+        let mut head = read_head(&mut reader)?.expect("no request");
+        head.request.body = read_body(&mut reader, &head.request.headers)?;
+        Ok(head)
+    }
+
+    fn status(input: &str) -> u32 {
+        match parse(input) {
+            Err(ReadError::Status(code)) => code,
+            other => panic!("expected an error status, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parse_requests() {
+        let head = parse("\r\nGET /search?q=rust HTTP/1.1\r\nhost: example.com\r\n\
+                          X-TAGS: a\r\nx-tags:  b \r\n\r\n").unwrap();
+        assert_eq!(head.request.method, "GET");
+        assert_eq!(head.request.url, "/search?q=rust");
+        assert_eq!(head.request.headers["Host"], "example.com");
+        assert_eq!(head.request.headers["X-Tags"], "a, b");
+        assert!(head.keep_alive);
+        assert!(!head.expect_continue);
+
+        let head = parse("POST http://example.com?x=1 HTTP/1.0\n\
+                          Content-Length: 5\n\nhello").unwrap();
+        assert_eq!(head.request.url, "/?x=1");
+        assert_eq!(head.request.body, b"hello");
+        assert!(!head.keep_alive);
+
+        let head = parse("POST /upload HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\
+                          Connection: close\r\nExpect: 100-continue\r\n\r\n\
+                          5;name=value\r\nhello\r\n7\r\n, world\r\n0\r\nTrailer: x\r\n\r\n").unwrap();
+        assert_eq!(head.request.body, b"hello, world");
+        assert!(!head.keep_alive);
+        assert!(head.expect_continue);
+
+        assert!(read_head(&mut &b""[..]).unwrap().is_none());
+    }
+
+    #[test]
+    fn reject_bad_requests() {
+        assert_eq!(status("GET /\r\n\r\n"), 400);
+        assert_eq!(status("GET  / HTTP/1.1\r\n\r\n"), 400);
+        assert_eq!(status("GET / HTTP/2.0\r\n\r\n"), 505);
+        assert_eq!(status("GET nowhere HTTP/1.1\r\n\r\n"), 400);
+        assert_eq!(status("GET / HTTP/1.1\r\nNo colon\r\n\r\n"), 400);
+        assert_eq!(status("GET / HTTP/1.1\r\nBad name: x\r\n\r\n"), 400);
+        assert_eq!(status("GET / HTTP/1.1\r\nHost: x\r\n"), 400);
+        assert_eq!(status("POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n"), 400);
+        assert_eq!(status("POST / HTTP/1.1\r\nContent-Length: 99999999999\r\n\r\n"), 413);
+        assert_eq!(status("POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n"), 501);
+        assert_eq!(status("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\
+                           Content-Length: 3\r\n\r\n"), 400);
+        assert_eq!(status("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+                           zz\r\n"), 400);
+        assert_eq!(status("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+                           3\r\nabcd\r\n0\r\n\r\n"), 400);
+
+        let long = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(super::MAX_LINE));
+        assert_eq!(status(&long), 431);
+    }
+
+    fn test_router() -> BasicRouter {
+        fn text(code: u32, body: String) -> Response {
+            Response { code, headers: HashMap::new(), body: body.into_bytes() }
+        }
+
+        let mut router = BasicRouter::new();
+        router.add_route("GET", "/hello", |_, _| text(200, "Hello, world!".to_string()));
+        router.add_route("GET", "/users/:id", |req: &Request, params: &Params| {
+            text(200, format!("user {} via {}", params.get("id").unwrap(), req.url))
+        });
+        router.add_route("POST", "/echo", |req, _| {
+            text(201, String::from_utf8(req.body.clone()).unwrap())
+        });
+        router
+    }
+
+    /// Read one response from `reader`, returning its status, headers and
+    /// body. Assumes the response has a `Content-Length`, unless `head_only`.
+    fn read_response<R: BufRead>(reader: &mut R, head_only: bool)
+                                 -> (u32, HashMap<String, String>, String)
+    {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        let code = line.split(' ').nth(1).unwrap().parse().unwrap();
+        let mut headers = HashMap::new();
+        loop {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line.split_once(": ").unwrap();
+            headers.insert(name.to_string(), value.to_string());
+        }
+        let length = if head_only { 0 } else {
+            headers.get("Content-Length").map_or(0, |l| l.parse().unwrap())
+        };
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+        (code, headers, String::from_utf8(body).unwrap())
+    }
+
+    #[test]
+    fn serve_over_loopback() {
+        let server = Server::bind("127.0.0.1:0", test_router(), 4).unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        // Several requests on one connection, some pipelined.
+        stream.write_all(b"GET /hello HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let (code, headers, body) = read_response(&mut reader, false);
+        assert_eq!((code, body.as_str()), (200, "Hello, world!"));
+        assert_eq!(headers["Content-Length"], "13");
+
+        stream.write_all(b"GET /users/42?x=y HTTP/1.1\r\n\r\n\
+                           POST /echo HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+                           3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n\
+                           HEAD /hello HTTP/1.1\r\n\r\n\
+                           GET /nowhere HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(read_response(&mut reader, false).2, "user 42 via /users/42?x=y");
+        assert_eq!(read_response(&mut reader, false).2, "abcde");
+        let (code, headers, _) = read_response(&mut reader, true);
+        assert_eq!((code, headers["Content-Length"].as_str()), (200, "13"));
+        assert_eq!(read_response(&mut reader, false).0, 404);
+
+        // Expecting `100 Continue`.
+        stream.write_all(b"POST /echo HTTP/1.1\r\nContent-Length: 4\r\n\
+                           Expect: 100-continue\r\n\r\n").unwrap();
+        assert_eq!(read_response(&mut reader, true).0, 100);
+        stream.write_all(b"ping").unwrap();
+        assert_eq!(read_response(&mut reader, false), (201, HashMap::from([
+            ("Content-Length".to_string(), "4".to_string())
+        ]), "ping".to_string()));
+
+        // Closing the connection on request.
+        stream.write_all(b"OPTIONS /echo HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+        let (code, headers, _) = read_response(&mut reader, true);
+        assert_eq!(code, 204);
+        assert_eq!(headers["Allow"], "OPTIONS, POST");
+        assert_eq!(headers["Connection"], "close");
+        assert!(!headers.contains_key("Content-Length"));
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 0);
+
+        // A malformed request gets a 400, and the connection closed.
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream.write_all(b"GET / HTTP/1.1\r\nbroken header\r\n\r\n").unwrap();
+        let mut reader = BufReader::new(stream);
+        let (code, headers, _) = read_response(&mut reader, false);
+        assert_eq!((code, headers["Connection"].as_str()), (400, "close"));
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 0);
+
+        server.shutdown();
+    }
+
+    #[test]
+    fn concurrent_clients() {
+        let server = Server::bind("127.0.0.1:0", test_router(), 3).unwrap();
+        let addr = server.local_addr();
+        std::thread::scope(|scope| {
+            for client in 0..8 {
+                scope.spawn(move || {
+                    let mut stream = TcpStream::connect(addr).unwrap();
+                    let mut reader = BufReader::new(stream.try_clone().unwrap());
+                    for i in 0..20 {
+                        let body = format!("client {} request {}", client, i);
+                        let request = format!("POST /echo HTTP/1.1\r\nContent-Length: {}\r\n\
+                                               {}\r\n{}",
+                                              body.len(),
+                                              if i == 19 { "Connection: close\r\n" } else { "" },
+                                              body);
+                        stream.write_all(request.as_bytes()).unwrap();
+                        assert_eq!(read_response(&mut reader, false).2, body);
+                    }
+                });
+            }
+        });
+        server.shutdown();
+    }
+
+    #[test]
+    fn handler_panics() {
+        let mut router = test_router();
+        router.add_route("GET", "/panic", |_, _| panic!("handler failed"));
+        let server = Server::bind("127.0.0.1:0", router, 1).unwrap();
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        // The client gets a 500, and the connection stays usable.
+        stream.write_all(b"GET /panic HTTP/1.1\r\n\r\nGET /hello HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(read_response(&mut reader, false).0, 500);
+        assert_eq!(read_response(&mut reader, false).2, "Hello, world!");
+        drop((stream, reader));
+
+        // The only worker is still there to serve new connections.
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream.write_all(b"GET /hello HTTP/1.1\r\nConnection: close\r\n\r\n").unwrap();
+        assert_eq!(read_response(&mut BufReader::new(stream), false).0, 200);
+        server.shutdown();
+    }
+
+    #[test]
+    fn idle_connections() {
+        let server = Server::bind("127.0.0.1:0", test_router(), 1).unwrap();
+        let start = Instant::now();
+
+        // Leave a keep-alive connection idle on the only worker.
+        let mut idle = TcpStream::connect(server.local_addr()).unwrap();
+        let mut idle_reader = BufReader::new(idle.try_clone().unwrap());
+        idle.write_all(b"GET /hello HTTP/1.1\r\n\r\n").unwrap();
+        assert_eq!(read_response(&mut idle_reader, false).0, 200);
+
+        // Another client doesn't have to wait for it to time out.
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        stream.write_all(b"GET /hello HTTP/1.1\r\n\r\n").unwrap();
+        let mut reader = BufReader::new(stream);
+        assert_eq!(read_response(&mut reader, false).0, 200);
+        assert_eq!(idle_reader.read(&mut [0; 1]).unwrap(), 0);
+
+        // Nor does shutting down.
+        server.shutdown();
+        assert_eq!(reader.read(&mut [0; 1]).unwrap(), 0);
+        assert!(start.elapsed() < super::KEEP_ALIVE_TIMEOUT);
+    }
+}
@@ -0,0 +1,433 @@
+//! A radix tree for matching URL paths against route patterns.
+//!
+//! A pattern is a path in which whole segments may be replaced by
+//! placeholders:
+//!
+//! - `:name` matches any one non-empty segment, like `/users/:id`;
+//! - `*name` matches everything that remains, including further slashes,
+//!   like `/static/*path`. It must come last.
+//!
+//! When several patterns could match a path, static text wins over a
+//! `:param`, which wins over a `*wildcard`. The matcher backtracks, so
+//! `/users/new` and `/users/:id/edit` can coexist: `/users/new/edit`
+//! matches the latter even though `new` was first tried as static text.
+
+/// The values a path supplied for a route's placeholders.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Params {
+    pairs: Vec<(String, String)>,
+}
+
+impl Params {
+    pub fn new() -> Params {
+        Params { pairs: vec![] }
+    }
+
+    /// Return the value of the placeholder called `name`, if any.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.pairs.iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn insert(&mut self, name: &str, value: &str) {
+        self.pairs.push((name.to_string(), value.to_string()));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.pairs.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    fn pop(&mut self) {
+        self.pairs.pop();
+    }
+}
+
+/// One piece of a parsed route pattern.
+#[derive(Debug, PartialEq)]
+pub(crate) enum Token<'p> {
+    Static(&'p str),
+    Param(&'p str),
+    Wildcard(&'p str),
+}
+
+/// Break `pattern` into static text and placeholders.
+///
+/// # Panics
+///
+/// Panics if a placeholder has no name, or a wildcard isn't last.
+pub(crate) fn tokenize(pattern: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut rest = pattern;
+    while !rest.is_empty() {
+        // Placeholders may only start right after a slash, or at the start.
+        let start = rest.char_indices()
+            .find(|&(i, c)| (c == ':' || c == '*')
+                            && (i == 0 && tokens.is_empty() || rest[..i].ends_with('/')))
+            .map(|(i, _)| i);
+        let start = match start {
+            Some(i) => i,
+            None => {
+                tokens.push(Token::Static(rest));
+                break;
+            }
+        };
+        if start > 0 {
+            tokens.push(Token::Static(&rest[..start]));
+        }
+
+        let end = rest[start..].find('/').map_or(rest.len(), |i| start + i);
+        let name = &rest[start + 1..end];
+        assert!(!name.is_empty(), "route {:?} has a placeholder with no name", pattern);
+        if rest[start..].starts_with('*') {
+            assert!(end == rest.len(), "wildcard must come last in route {:?}", pattern);
+            tokens.push(Token::Wildcard(name));
+        } else {
+            tokens.push(Token::Param(name));
+        }
+        rest = &rest[end..];
+    }
+    tokens
+}
+
+/// A node in the tree. The node has already matched its `prefix`; what
+/// follows is tried in order of precedence: its static children, then its
+/// parameter child, then its wildcard.
+struct Node<T> {
+    prefix: String,
+    value: Option<T>,
+
+    /// Children whose prefixes all begin with different characters.
+    children: Vec<Node<T>>,
+
+    /// The name of the parameter matched here, and the node that follows it.
+    param: Option<(String, Box<Node<T>>)>,
+
+    /// The name of the wildcard matched here, and its value.
+    wildcard: Option<(String, T)>,
+}
+
+impl<T> Node<T> {
+    fn new(prefix: &str) -> Node<T> {
+        Node {
+            prefix: prefix.to_string(),
+            value: None,
+            children: vec![],
+            param: None,
+            wildcard: None,
+        }
+    }
+
+    /// Insert `value` at the position `tokens` describe, relative to this
+    /// node. Return the value it replaced, if any.
+    fn insert(&mut self, tokens: &[Token<'_>], value: T) -> Option<T> {
+        let (first, rest) = match tokens.split_first() {
+            None => return self.value.replace(value),
+            Some(split) => split,
+        };
+
+        match *first {
+            Token::Static(text) => self.insert_static(text, rest, value),
+            Token::Param(name) => {
+                let (existing, node) = self.param
+                    .get_or_insert_with(|| (name.to_string(), Box::new(Node::new(""))));
+                assert!(existing == name,
+                        "route parameter :{} conflicts with existing :{} in the same position",
+                        name, existing);
+                node.insert(rest, value)
+            }
+            Token::Wildcard(name) => {
+                if let Some((existing, _)) = &self.wildcard {
+                    assert!(existing == name,
+                            "route wildcard *{} conflicts with existing *{} in the same position",
+                            name, existing);
+                }
+                self.wildcard.replace((name.to_string(), value)).map(|(_, old)| old)
+            }
+        }
+    }
+
+    fn insert_static(&mut self, text: &str, rest: &[Token<'_>], value: T) -> Option<T> {
+        let position = self.children.iter()
+            .position(|child| child.prefix.chars().next() == text.chars().next());
+        let index = match position {
+            Some(index) => index,
+            None => {
+                self.children.push(Node::new(text));
+                return self.children.last_mut().unwrap().insert(rest, value);
+            }
+        };
+
+        let child = &mut self.children[index];
+        let common = common_prefix_len(&child.prefix, text);
+        if common < child.prefix.len() {
+            // Split the child: it keeps the tail of its prefix, under a new
+            // node holding the part it shares with `text`.
+            let mut shared = Node::new(&child.prefix[..common]);
+            child.prefix = child.prefix[common..].to_string();
+            std::mem::swap(child, &mut shared);
+            child.children.push(shared);
+        }
+
+        if common == text.len() {
+            child.insert(rest, value)
+        } else {
+            child.insert_static(&text[common..], rest, value)
+        }
+    }
+
+    /// Look for a value matching `path`, which follows this node's prefix,
+    /// recording any placeholders' values in `params`.
+    fn lookup<'a>(&'a self, path: &str, params: &mut Params) -> Option<&'a T> {
+        if path.is_empty() {
+            if let Some(value) = &self.value {
+                return Some(value);
+            }
+        }
+
+        for child in &self.children {
+            if let Some(rest) = path.strip_prefix(child.prefix.as_str()) {
+                if let Some(value) = child.lookup(rest, params) {
+                    return Some(value);
+                }
+            }
+        }
+
+        if let Some((name, node)) = &self.param {
+            let end = path.find('/').unwrap_or(path.len());
+            if end > 0 {
+                params.insert(name, &path[..end]);
+                if let Some(value) = node.lookup(&path[end..], params) {
+                    return Some(value);
+                }
+                params.pop();
+            }
+        }
+
+        if let Some((name, value)) = &self.wildcard {
+            params.insert(name, path);
+            return Some(value);
+        }
+
+        None
+    }
+
+    /// Move this node's values into `entries`, each with its pattern, given
+    /// that `pattern` is the pattern leading up to this node.
+    fn drain_into(self, mut pattern: String, entries: &mut Vec<(String, T)>) {
+        pattern.push_str(&self.prefix);
+        if let Some(value) = self.value {
+            entries.push((pattern.clone(), value));
+        }
+        for child in self.children {
+            child.drain_into(pattern.clone(), entries);
+        }
+        if let Some((name, node)) = self.param {
+            node.drain_into(format!("{}:{}", pattern, name), entries);
+        }
+        if let Some((name, value)) = self.wildcard {
+            entries.push((format!("{}*{}", pattern, name), value));
+        }
+    }
+}
+
+fn common_prefix_len(a: &str, b: &str) -> usize {
+    a.char_indices()
+        .zip(b.chars())
+        .find(|&((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((i, _), _)| i)
+}
+
+/// A set of route patterns, each associated with a value of type `T`.
+pub struct Tree<T> {
+    root: Node<T>,
+}
+
+impl<T> Tree<T> {
+    pub fn new() -> Tree<T> {
+        Tree { root: Node::new("") }
+    }
+
+    /// Associate `value` with `pattern`, returning the value previously
+    /// associated with exactly that pattern, if any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` is malformed, or if it names a placeholder
+    /// differently from an existing pattern with a placeholder in the same
+    /// position.
+    pub fn insert(&mut self, pattern: &str, value: T) -> Option<T> {
+        self.root.insert(&tokenize(pattern), value)
+    }
+
+    /// Find the value whose pattern best matches `path`, and the values
+    /// the path supplied for its placeholders.
+    pub fn lookup(&self, path: &str) -> Option<(&T, Params)> {
+        let mut params = Params::new();
+        let value = self.root.lookup(path, &mut params)?;
+        Some((value, params))
+    }
+
+    /// Return the value associated with exactly `pattern`, if any.
+    pub fn get_mut(&mut self, pattern: &str) -> Option<&mut T> {
+        let mut node = &mut self.root;
+        for token in tokenize(pattern) {
+            match token {
+                Token::Static(mut text) => {
+                    while !text.is_empty() {
+                        node = node.children.iter_mut()
+                            .find(|child| text.starts_with(child.prefix.as_str()))?;
+                        text = &text[node.prefix.len()..];
+                    }
+                }
+                Token::Param(name) => match &mut node.param {
+                    Some((n, next)) if n == name => node = next,
+                    _ => return None,
+                },
+                Token::Wildcard(name) => return match &mut node.wildcard {
+                    Some((n, value)) if n == name => Some(value),
+                    _ => None,
+                },
+            }
+        }
+        node.value.as_mut()
+    }
+
+    /// Return all the values in the tree, each with its pattern.
+    pub fn into_entries(self) -> Vec<(String, T)> {
+        let mut entries = vec![];
+        self.root.drain_into(String::new(), &mut entries);
+        entries
+    }
+}
+
+impl<T> Default for Tree<T> {
+    fn default() -> Tree<T> {
+        Tree::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{tokenize, Token, Tree};
+
+    fn matched(tree: &Tree<&'static str>, path: &str)
+                   -> Option<(&'static str, Vec<(String, String)>)>
+    {
+        tree.lookup(path).map(|(value, params)| {
+            (*value, params.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect())
+        })
+    }
+
+    fn p(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(n, v)| (n.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(tokenize("/users/:id/posts/*rest"),
+                   vec![Token::Static("/users/"), Token::Param("id"),
+                        Token::Static("/posts/"), Token::Wildcard("rest")]);
+        assert_eq!(tokenize("/a:b/c*d"), vec![Token::Static("/a:b/c*d")]);
+        assert_eq!(tokenize(":x"), vec![Token::Param("x")]);
+    }
+
+    #[test]
+    #[should_panic(expected = "wildcard must come last")]
+    fn wildcard_not_last() {
+        Tree::new().insert("/files/*path/edit", ());
+    }
+
+    #[test]
+    #[should_panic(expected = "conflicts with existing :id")]
+    fn conflicting_param_names() {
+        let mut tree = Tree::new();
+        tree.insert("/users/:id", ());
+        tree.insert("/users/:name/posts", ());
+    }
+
+    #[test]
+    fn static_routes_share_prefixes() {
+        let mut tree = Tree::new();
+        for route in &["/", "/team", "/teams", "/test", "/tea", "/contact"] {
+            assert_eq!(tree.insert(route, *route), None);
+        }
+        for route in &["/", "/team", "/teams", "/test", "/tea", "/contact"] {
+            assert_eq!(matched(&tree, route), Some((*route, vec![])));
+        }
+        assert_eq!(matched(&tree, "/te"), None);
+        assert_eq!(matched(&tree, "/teamsters"), None);
+        assert_eq!(matched(&tree, ""), None);
+
+        assert_eq!(tree.insert("/team", "replaced"), Some("/team"));
+        assert_eq!(matched(&tree, "/team"), Some(("replaced", vec![])));
+    }
+
+    #[test]
+    fn params_and_wildcards() {
+        let mut tree = Tree::new();
+        tree.insert("/users/:id", "user");
+        tree.insert("/users/:id/posts/:post", "post");
+        tree.insert("/static/*path", "static");
+
+        assert_eq!(matched(&tree, "/users/42"), Some(("user", p(&[("id", "42")]))));
+        assert_eq!(matched(&tree, "/users/42/posts/7"),
+                   Some(("post", p(&[("id", "42"), ("post", "7")]))));
+        assert_eq!(matched(&tree, "/static/css/site.css"),
+                   Some(("static", p(&[("path", "css/site.css")]))));
+        assert_eq!(matched(&tree, "/static/"), Some(("static", p(&[("path", "")]))));
+
+        // A parameter must match a non-empty segment.
+        assert_eq!(matched(&tree, "/users/"), None);
+        assert_eq!(matched(&tree, "/users/42/"), None);
+        assert_eq!(matched(&tree, "/users/42/posts"), None);
+    }
+
+    #[test]
+    fn precedence() {
+        let mut tree = Tree::new();
+        tree.insert("/users/*rest", "wildcard");
+        tree.insert("/users/:id", "param");
+        tree.insert("/users/new", "static");
+        tree.insert("/users/:id/edit", "edit");
+
+        assert_eq!(matched(&tree, "/users/new"), Some(("static", vec![])));
+        assert_eq!(matched(&tree, "/users/newer"), Some(("param", p(&[("id", "newer")]))));
+        assert_eq!(matched(&tree, "/users/7"), Some(("param", p(&[("id", "7")]))));
+
+        // Backtracking: `new` is tried as static text first, and then as a
+        // parameter.
+        assert_eq!(matched(&tree, "/users/new/edit"), Some(("edit", p(&[("id", "new")]))));
+
+        // Failing the parameter route falls back to the wildcard, without
+        // leaving a stray `id` behind.
+        assert_eq!(matched(&tree, "/users/7/delete"),
+                   Some(("wildcard", p(&[("rest", "7/delete")]))));
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut tree = Tree::new();
+        tree.insert("/users/:id", 1);
+        tree.insert("/users/new", 2);
+        tree.insert("/files/*path", 3);
+
+        *tree.get_mut("/users/:id").unwrap() += 10;
+        assert_eq!(tree.get_mut("/users/:id"), Some(&mut 11));
+        assert_eq!(tree.get_mut("/users/new"), Some(&mut 2));
+        assert_eq!(tree.get_mut("/files/*path"), Some(&mut 3));
+        assert_eq!(tree.get_mut("/users/:name"), None);
+        assert_eq!(tree.get_mut("/users/ne"), None);
+        assert_eq!(tree.get_mut("/users/42"), None);
+
+        let mut entries = tree.into_entries();
+        entries.sort();
+        assert_eq!(entries, [("/files/*path".to_string(), 3),
+                             ("/users/:id".to_string(), 11),
+                             ("/users/new".to_string(), 2)]);
+    }
+}
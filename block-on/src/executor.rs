@@ -0,0 +1,444 @@
+//! A single-threaded executor for many futures at once.
+//!
+//! `block_on` drives one future; a `LocalExecutor` drives any number of
+//! tasks, on the thread that owns it. The tasks needn't be `Send`. Each task
+//! has a waker, built with `waker_fn` like `block_on`'s, that pushes the
+//! task's id onto the executor's run queue and unparks the executor. The
+//! executor polls tasks in the order they were woken, which makes runs
+//! repeatable. A `Simulation` can shuffle that order instead, with a seeded
+//! random number generator, and record what the executor does.
+
+use crate::sim::{Event, Rng, Trace};
+use crossbeam::sync::Parker;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use waker_fn::waker_fn;
+
+/// An executor for tasks on the current thread. Clones share the same tasks.
+#[derive(Clone)]
+pub struct LocalExecutor {
+    inner: Rc<Inner>,
+}
+
+struct Inner {
+    tasks: RefCell<HashMap<usize, Task>>,
+    next_id: Cell<usize>,
+    queue: Arc<Mutex<VecDeque<usize>>>,
+    parker: Parker,
+
+    /// If set, pick which woken task to poll next at random, rather than
+    /// the one that was woken first.
+    shuffle: Option<RefCell<Rng>>,
+
+    trace: Trace,
+}
+
+struct Task {
+    future: Pin<Box<dyn Future<Output = ()>>>,
+    waker: Waker,
+
+    /// True if the task's id is already in the run queue, or it's being
+    /// polled right now; either way, waking it again needn't queue it.
+    scheduled: Arc<AtomicBool>,
+}
+
+impl LocalExecutor {
+    pub fn new() -> LocalExecutor {
+        LocalExecutor::with_options(None, Trace::default())
+    }
+
+    pub(crate) fn with_options(shuffle: Option<Rng>, trace: Trace) -> LocalExecutor {
+        LocalExecutor {
+            inner: Rc::new(Inner {
+                tasks: RefCell::new(HashMap::new()),
+                // Task 0 is the future passed to `run`.
+                next_id: Cell::new(1),
+                queue: Arc::new(Mutex::new(VecDeque::new())),
+                parker: Parker::new(),
+                shuffle: shuffle.map(RefCell::new),
+                trace,
+            }),
+        }
+    }
+
+    /// Add a task that runs `future`, and return a handle to await its
+    /// result. Dropping the handle detaches the task; it keeps running.
+    pub fn spawn_local<F>(&self, future: F) -> JoinHandle<F::Output>
+        where F: Future + 'static
+    {
+        let slot = Rc::new(RefCell::new(Slot { output: None, waker: None }));
+        let task_slot = slot.clone();
+        let future = async move {
+            let output = future.await;
+            let waker = {
+                let mut slot = task_slot.borrow_mut();
+                slot.output = Some(output);
+                slot.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        };
+
+        let id = self.inner.next_id.get();
+        self.inner.next_id.set(id + 1);
+        let scheduled = Arc::new(AtomicBool::new(true));
+        let waker = {
+            let scheduled = scheduled.clone();
+            let queue = self.inner.queue.clone();
+            let unparker = self.inner.parker.unparker().clone();
+            let trace = self.inner.trace.clone();
+            waker_fn(move || {
+                trace.record(Event::Wake(id));
+                if !scheduled.swap(true, Ordering::SeqCst) {
+                    queue.lock().unwrap().push_back(id);
+                    unparker.unpark();
+                }
+            })
+        };
+        let task = Task { future: Box::pin(future), waker, scheduled };
+        self.inner.tasks.borrow_mut().insert(id, task);
+        self.inner.queue.lock().unwrap().push_back(id);
+        self.inner.trace.record(Event::Spawn(id));
+
+        JoinHandle { slot, completed: false }
+    }
+
+    /// Poll tasks until none of them can make progress without being woken.
+    pub fn run_until_stalled(&self) {
+        let _current = CurrentGuard::enter(self);
+        crate::timer::fire_expired();
+        self.poll_ready_tasks();
+    }
+
+    /// Run `future` to completion on this thread, polling the executor's
+    /// tasks while it's waiting. Return its value.
+    ///
+    /// Tasks that are still unfinished when `future` completes stay in the
+    /// executor, and can be resumed later.
+    pub fn run<F: Future>(&self, future: F) -> F::Output {
+        let _current = CurrentGuard::enter(self);
+
+        let woken = Arc::new(AtomicBool::new(true));
+        let waker = {
+            let woken = woken.clone();
+            let unparker = self.inner.parker.unparker().clone();
+            let trace = self.inner.trace.clone();
+            waker_fn(move || {
+                trace.record(Event::Wake(0));
+                woken.store(true, Ordering::SeqCst);
+                unparker.unpark();
+            })
+        };
+        let mut context = Context::from_waker(&waker);
+        futures_lite::pin!(future);
+
+        loop {
+            if woken.swap(false, Ordering::SeqCst) {
+                self.inner.trace.record(Event::Poll(0));
+                if let Poll::Ready(value) = future.as_mut().poll(&mut context) {
+                    self.inner.trace.record(Event::Finish(0));
+                    return value;
+                }
+            }
+            self.poll_ready_tasks();
+            if !woken.load(Ordering::SeqCst) {
+                crate::timer::park(&self.inner.parker);
+            }
+        }
+    }
+
+    /// Return the number of tasks that haven't finished yet.
+    pub fn pending_tasks(&self) -> usize {
+        self.inner.tasks.borrow().len()
+    }
+
+    fn poll_ready_tasks(&self) {
+        loop {
+            let id = match self.next_ready_task() {
+                Some(id) => id,
+                None => return,
+            };
+
+            // Take the task out of the table while we poll it, so that it
+            // can spawn more tasks.
+            let mut task = match self.inner.tasks.borrow_mut().remove(&id) {
+                Some(task) => task,
+                None => continue,
+            };
+            task.scheduled.store(false, Ordering::SeqCst);
+            self.inner.trace.record(Event::Poll(id));
+            let mut context = Context::from_waker(&task.waker);
+            if task.future.as_mut().poll(&mut context).is_pending() {
+                self.inner.tasks.borrow_mut().insert(id, task);
+            } else {
+                self.inner.trace.record(Event::Finish(id));
+            }
+        }
+    }
+
+    /// Take a task off the run queue.
+    fn next_ready_task(&self) -> Option<usize> {
+        let mut queue = self.inner.queue.lock().unwrap();
+        match &self.inner.shuffle {
+            Some(rng) if !queue.is_empty() => {
+                let index = rng.borrow_mut().below(queue.len());
+                queue.remove(index)
+            }
+            _ => queue.pop_front(),
+        }
+    }
+}
+
+impl Default for LocalExecutor {
+    fn default() -> LocalExecutor {
+        LocalExecutor::new()
+    }
+}
+
+thread_local! {
+    static CURRENT: RefCell<Option<LocalExecutor>> = const { RefCell::new(None) };
+}
+
+/// Makes an executor the current one for `spawn_local`, until dropped.
+struct CurrentGuard {
+    previous: Option<LocalExecutor>,
+}
+
+impl CurrentGuard {
+    fn enter(executor: &LocalExecutor) -> CurrentGuard {
+        let previous = CURRENT.with(|current| current.replace(Some(executor.clone())));
+        CurrentGuard { previous }
+    }
+}
+
+impl Drop for CurrentGuard {
+    fn drop(&mut self) {
+        CURRENT.with(|current| *current.borrow_mut() = self.previous.take());
+    }
+}
+
+/// Spawn a task on the executor that's running the current task.
+///
+/// # Panics
+///
+/// Panics if called from outside `LocalExecutor::run` or
+/// `LocalExecutor::run_until_stalled`.
+pub fn spawn_local<F>(future: F) -> JoinHandle<F::Output>
+    where F: Future + 'static
+{
+    let executor = CURRENT.with(|current| current.borrow().clone())
+        .expect("spawn_local called outside of a LocalExecutor");
+    executor.spawn_local(future)
+}
+
+struct Slot<T> {
+    output: Option<T>,
+    waker: Option<Waker>,
+}
+
+/// A future for the result of a spawned task.
+pub struct JoinHandle<T> {
+    slot: Rc<RefCell<Slot<T>>>,
+    completed: bool,
+}
+
+impl<T> JoinHandle<T> {
+    /// Return true if the task has finished.
+    pub fn is_finished(&self) -> bool {
+        self.completed || self.slot.borrow().output.is_some()
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        assert!(!self.completed, "JoinHandle polled after completion");
+        let mut slot = self.slot.borrow_mut();
+        match slot.output.take() {
+            Some(output) => {
+                drop(slot);
+                self.completed = true;
+                Poll::Ready(output)
+            }
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{spawn_local, LocalExecutor};
+    use std::cell::RefCell;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::task::{Context, Poll, Waker};
+
+    /// A future that's pending until its `Trigger` fires.
+    #[derive(Clone, Default)]
+    struct Trigger(Rc<RefCell<(bool, Option<Waker>)>>);
+
+    impl Trigger {
+        fn fire(&self) {
+            let waker = {
+                let mut state = self.0.borrow_mut();
+                state.0 = true;
+                state.1.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+
+    impl Future for Trigger {
+        type Output = ();
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            let mut state = self.0.borrow_mut();
+            if state.0 {
+                Poll::Ready(())
+            } else {
+                state.1 = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+
+    /// A future that's pending the first `n` times it's polled, waking
+    /// itself each time.
+    struct YieldNow(usize);
+
+    impl Future for YieldNow {
+        type Output = ();
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 == 0 {
+                return Poll::Ready(());
+            }
+            self.0 -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn tasks_interleave_in_wake_order() {
+        let executor = LocalExecutor::new();
+        let log = Rc::new(RefCell::new(vec![]));
+        for name in ["a", "b", "c"] {
+            let log = log.clone();
+            executor.spawn_local(async move {
+                for i in 0..3 {
+                    log.borrow_mut().push(format!("{}{}", name, i));
+                    YieldNow(1).await;
+                }
+            });
+        }
+        assert_eq!(executor.pending_tasks(), 3);
+        executor.run_until_stalled();
+        assert_eq!(executor.pending_tasks(), 0);
+        assert_eq!(*log.borrow(), ["a0", "b0", "c0", "a1", "b1", "c1", "a2", "b2", "c2"]);
+    }
+
+    #[test]
+    fn run_until_stalled_stops_at_pending_tasks() {
+        let executor = LocalExecutor::new();
+        let trigger = Trigger::default();
+        let handle = executor.spawn_local({
+            let trigger = trigger.clone();
+            async move {
+                trigger.await;
+                "done"
+            }
+        });
+
+        executor.run_until_stalled();
+        assert!(!handle.is_finished());
+        assert_eq!(executor.pending_tasks(), 1);
+
+        // Running again does nothing until the task is woken.
+        executor.run_until_stalled();
+        assert_eq!(executor.pending_tasks(), 1);
+
+        trigger.fire();
+        executor.run_until_stalled();
+        assert!(handle.is_finished());
+        assert_eq!(executor.run(handle), "done");
+    }
+
+    #[test]
+    fn join_handles_and_nested_spawns() {
+        let executor = LocalExecutor::new();
+        let total = executor.run(async {
+            // Non-`Send` futures are fine.
+            let shared = Rc::new(RefCell::new(0));
+            let handles: Vec<_> = (1..=10)
+                .map(|i| {
+                    let shared = shared.clone();
+                    spawn_local(async move {
+                        YieldNow(i % 3).await;
+                        *shared.borrow_mut() += i;
+                        spawn_local(async move { i * i }).await
+                    })
+                })
+                .collect();
+
+            let mut squares = 0;
+            for handle in handles {
+                squares += handle.await;
+            }
+            let sum = *shared.borrow();
+            (squares, sum)
+        });
+        assert_eq!(total, (385, 55));
+        assert_eq!(executor.pending_tasks(), 0);
+    }
+
+    #[test]
+    fn wakes_from_other_threads() {
+        let executor = LocalExecutor::new();
+        let (sender, receiver) = std::sync::mpsc::channel::<Waker>();
+        let thread = std::thread::spawn(move || {
+            for waker in receiver {
+                waker.wake();
+            }
+        });
+
+        // A future that sends its waker to the other thread to be woken.
+        struct Remote(std::sync::mpsc::Sender<Waker>, bool);
+        impl Future for Remote {
+            type Output = ();
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+                if self.1 {
+                    return Poll::Ready(());
+                }
+                self.1 = true;
+                self.0.send(cx.waker().clone()).unwrap();
+                Poll::Pending
+            }
+        }
+
+        let task = executor.spawn_local(Remote(sender.clone(), false));
+        executor.run(async {
+            Remote(sender, false).await;
+            task.await;
+        });
+        thread.join().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "spawn_local called outside of a LocalExecutor")]
+    fn spawn_local_needs_an_executor() {
+        spawn_local(async {});
+    }
+}
@@ -4,6 +4,14 @@ use crossbeam::sync::Parker; // Cargo.toml: crossbeam = "0.8"
 use std::future::Future;
 use std::task::{Context, Poll};
 
+mod executor;
+mod sim;
+mod timer;
+mod wheel;
+pub use executor::{spawn_local, JoinHandle, LocalExecutor};
+pub use sim::{Event, Simulation};
+pub use timer::{interval, sleep, sleep_until, timeout, Elapsed, Interval, Sleep};
+
 pub fn block_on<F: Future>(future: F) -> F::Output {
     let parker = Parker::new();
     let unparker = parker.unparker().clone();
@@ -15,7 +23,7 @@ pub fn block_on<F: Future>(future: F) -> F::Output {
     loop {
         match future.as_mut().poll(&mut context) {
             Poll::Ready(value) => return value,
-            Poll::Pending => parker.park(),
+            Poll::Pending => timer::park(&parker),
         }
     }
 }
@@ -24,7 +32,6 @@ pub fn block_on<F: Future>(future: F) -> F::Output {
 fn test() {
     assert_eq!(block_on(std::future::ready(42)), 42);
 
-    use async_std::task::{spawn, sleep};
     use futures_lite::FutureExt;
     use std::time::Duration;
 
@@ -38,7 +45,7 @@ fn test() {
                 sleep(Duration::from_millis(500)).await;
                 44
             };
-            spawn(one_sec.race(half_sec))
+            one_sec.race(half_sec)
         }),
         44);
 }
@@ -0,0 +1,319 @@
+//! Deterministic simulation, for testing code that depends on timing.
+//!
+//! A `Simulation` replaces the current thread's clock with a virtual one,
+//! which stands still while there's work to do, and jumps straight to the
+//! next timer when everything is waiting. A test that sleeps for an hour
+//! runs in microseconds, and always sees the same times. Tasks that are
+//! woken together are polled in an order chosen by a seeded random number
+//! generator, so a test can try many interleavings, and replay any one of
+//! them from its seed. Everything the executor does is recorded in a trace.
+//!
+//! ```
+//! use block_on::{sleep, spawn_local, Simulation};
+//! use std::time::Duration;
+//!
+//! let sim = Simulation::new(42);
+//! let value = sim.run(async {
+//!     let task = spawn_local(async {
+//!         sleep(Duration::from_secs(60)).await;
+//!         7
+//!     });
+//!     task.await * 6
+//! });
+//! assert_eq!(value, 42);
+//! assert_eq!(sim.elapsed(), Duration::from_secs(60));
+//! ```
+
+use crate::executor::{JoinHandle, LocalExecutor};
+use crate::timer::Timers;
+use std::fmt;
+use std::future::Future;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A single-threaded executor running on virtual time.
+///
+/// While a `Simulation` exists, timers created on its thread use its
+/// clock, whether or not they run on it. Simulations on the same thread must
+/// be dropped in the reverse of the order they were created.
+pub struct Simulation {
+    executor: LocalExecutor,
+    timers: Rc<Timers>,
+    previous: Option<Rc<Timers>>,
+    trace: Trace,
+    seed: u64,
+}
+
+impl Simulation {
+    /// Start a simulation whose scheduling decisions are determined by
+    /// `seed`. The virtual clock starts at the current time.
+    pub fn new(seed: u64) -> Simulation {
+        let trace = Trace::enabled();
+        let timers = Rc::new(Timers::simulated(trace.clone()));
+        let previous = Timers::install(timers.clone());
+        Simulation {
+            executor: LocalExecutor::with_options(Some(Rng::new(seed)), trace.clone()),
+            timers,
+            previous: Some(previous),
+            trace,
+            seed,
+        }
+    }
+
+    /// Return the seed the simulation was created with.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Add a task to the simulation. See `LocalExecutor::spawn_local`.
+    pub fn spawn_local<F>(&self, future: F) -> JoinHandle<F::Output>
+        where F: Future + 'static
+    {
+        self.executor.spawn_local(future)
+    }
+
+    /// Run `future` to completion, along with the simulation's tasks, and
+    /// return its value. See `LocalExecutor::run`.
+    ///
+    /// If nothing can make progress and no timers are pending, this blocks
+    /// until another thread wakes something, as `LocalExecutor::run` would.
+    pub fn run<F: Future>(&self, future: F) -> F::Output {
+        self.executor.run(future)
+    }
+
+    /// Return the current virtual time.
+    pub fn now(&self) -> Instant {
+        self.timers.now()
+    }
+
+    /// Return how far the virtual clock has moved on since the simulation
+    /// started.
+    pub fn elapsed(&self) -> Duration {
+        self.timers.now() - self.timers.origin()
+    }
+
+    /// Return the events recorded so far.
+    pub fn trace(&self) -> Vec<Event> {
+        self.trace.events()
+    }
+}
+
+impl Drop for Simulation {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            Timers::install(previous);
+        }
+    }
+}
+
+/// Something that happened in a `Simulation`. Tasks are numbered in the
+/// order they were spawned, from 1; task 0 is the future passed to `run`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    Spawn(usize),
+    Poll(usize),
+    Wake(usize),
+    Finish(usize),
+
+    /// The virtual clock jumped to the next timer, this long after the
+    /// simulation started.
+    Advance(Duration),
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::Spawn(task) => write!(f, "spawn {}", task),
+            Event::Poll(task) => write!(f, "poll {}", task),
+            Event::Wake(task) => write!(f, "wake {}", task),
+            Event::Finish(task) => write!(f, "finish {}", task),
+            Event::Advance(elapsed) => write!(f, "advance to {:?}", elapsed),
+        }
+    }
+}
+
+/// A shared log of events, or nothing, if tracing is off. Wakers can be
+/// called from any thread, so this needs a lock.
+#[derive(Clone, Default)]
+pub(crate) struct Trace {
+    events: Option<Arc<Mutex<Vec<Event>>>>,
+}
+
+impl Trace {
+    fn enabled() -> Trace {
+        Trace { events: Some(Arc::new(Mutex::new(vec![]))) }
+    }
+
+    pub(crate) fn record(&self, event: Event) {
+        if let Some(events) = &self.events {
+            events.lock().unwrap().push(event);
+        }
+    }
+
+    fn events(&self) -> Vec<Event> {
+        match &self.events {
+            Some(events) => events.lock().unwrap().clone(),
+            None => vec![],
+        }
+    }
+}
+
+/// A small, fast pseudo-random number generator: SplitMix64. Good enough
+/// for shuffling tasks, and the same everywhere for a given seed.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Rng {
+        Rng(seed)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Return a number in `0..n`.
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Event, Simulation};
+    use crate::{interval, sleep, spawn_local, timeout, Elapsed};
+    use futures_lite::future::yield_now;
+    use futures_lite::FutureExt;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::{Duration, Instant};
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn virtual_time() {
+        let real_start = Instant::now();
+        let sim = Simulation::new(1);
+
+        let winner = sim.run({
+            let one_sec = async {
+                sleep(Duration::from_secs(1)).await;
+                43
+            };
+            let half_sec = async {
+                sleep(Duration::from_millis(500)).await;
+                44
+            };
+            one_sec.race(half_sec)
+        });
+        assert_eq!(winner, 44);
+        assert_eq!(sim.elapsed(), ms(500));
+
+        // Timers made before `run` use the simulation's clock too.
+        sim.run(sleep(Duration::from_secs(3600)));
+        assert_eq!(sim.elapsed(), ms(3_600_500));
+
+        assert_eq!(sim.run(timeout(sleep(ms(2000)), ms(1000))), Err(Elapsed));
+        assert_eq!(sim.run(timeout(sleep(ms(1000)), ms(2000))), Ok(()));
+        assert_eq!(sim.elapsed(), ms(3_602_500));
+
+        let start = sim.now();
+        let ticks = sim.run(async {
+            let mut ticks = interval(ms(250));
+            let mut due = vec![];
+            for _ in 0..4 {
+                due.push(ticks.tick().await - start);
+            }
+            due
+        });
+        assert_eq!(ticks, [ms(250), ms(500), ms(750), ms(1000)]);
+
+        assert!(real_start.elapsed() < ms(500));
+    }
+
+    /// Run four tasks that each yield a few times, and return the order
+    /// they ran in, and the trace.
+    fn interleave(seed: u64) -> (Vec<String>, Vec<Event>) {
+        let sim = Simulation::new(seed);
+        let log = Rc::new(RefCell::new(vec![]));
+        let tasks: Vec<_> = ["a", "b", "c", "d"].iter().map(|&name| {
+            let log = log.clone();
+            sim.spawn_local(async move {
+                for i in 0..3 {
+                    log.borrow_mut().push(format!("{}{}", name, i));
+                    yield_now().await;
+                }
+            })
+        }).collect();
+        sim.run(async {
+            for task in tasks {
+                task.await;
+            }
+        });
+        let order = log.borrow().clone();
+        (order, sim.trace())
+    }
+
+    #[test]
+    fn seeded_scheduling() {
+        let (order, trace) = interleave(5);
+        assert_eq!(order.len(), 12);
+        for name in ["a", "b", "c", "d"] {
+            let steps: Vec<_> = order.iter().filter(|step| step.starts_with(name)).collect();
+            assert_eq!(steps, [&format!("{}0", name), &format!("{}1", name), &format!("{}2", name)]);
+        }
+
+        // The same seed gives the same run.
+        assert_eq!(interleave(5), (order.clone(), trace));
+
+        // Other seeds give other runs.
+        let orders: std::collections::HashSet<_> = (0..20).map(|seed| interleave(seed).0).collect();
+        assert!(orders.len() > 10, "only {} distinct orders", orders.len());
+    }
+
+    #[test]
+    fn tracing() {
+        let sim = Simulation::new(7);
+        assert_eq!(sim.seed(), 7);
+        let value = sim.run(async {
+            let task = spawn_local(async {
+                sleep(ms(10)).await;
+                5
+            });
+            task.await
+        });
+        assert_eq!(value, 5);
+        assert_eq!(sim.trace(), [
+            Event::Poll(0),
+            Event::Spawn(1),
+            Event::Poll(1),
+            Event::Advance(ms(10)),
+            Event::Wake(1),
+            Event::Poll(1),
+            Event::Wake(0),
+            Event::Finish(1),
+            Event::Poll(0),
+            Event::Finish(0),
+        ]);
+        let lines: Vec<_> = sim.trace()[2..5].iter().map(Event::to_string).collect();
+        assert_eq!(lines, ["poll 1", "advance to 10ms", "wake 1"]);
+    }
+
+    #[test]
+    fn dropping_restores_the_real_clock() {
+        let sim = Simulation::new(0);
+        sim.run(sleep(Duration::from_secs(10)));
+        drop(sim);
+
+        let start = Instant::now();
+        crate::block_on(sleep(ms(20)));
+        let elapsed = start.elapsed();
+        assert!(elapsed >= ms(20) && elapsed < Duration::from_secs(5), "took {:?}", elapsed);
+    }
+}
@@ -0,0 +1,393 @@
+//! Timers: `sleep`, `sleep_until`, `timeout` and `interval`.
+//!
+//! Each thread has its own timer wheel, with a resolution of a millisecond.
+//! Timer futures register their wakers in the wheel of the thread that
+//! created them, which is why they aren't `Send`. When `block_on` or
+//! `LocalExecutor::run` has nothing to poll, it parks until the wheel's next
+//! deadline rather than indefinitely, then wakes the timers that are due.
+
+use crate::sim::{Event, Trace};
+use crate::wheel::{TimerId, Wheel};
+use crossbeam::sync::Parker;
+use futures_lite::{FutureExt, Stream};
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+const TICK: Duration = Duration::from_millis(1);
+
+/// A thread's timers.
+pub(crate) struct Timers {
+    /// The time at tick zero.
+    origin: Instant,
+    clock: Clock,
+    wheel: RefCell<Wheel>,
+}
+
+enum Clock {
+    Real,
+
+    /// A simulated clock, which stands still until everything is waiting
+    /// for a timer, and then jumps to the next one. See `Simulation`.
+    Virtual { now: Cell<Instant>, trace: Trace },
+}
+
+thread_local! {
+    static TIMERS: RefCell<Rc<Timers>> = RefCell::new(Rc::new(Timers::new(Clock::Real)));
+}
+
+impl Timers {
+    fn new(clock: Clock) -> Timers {
+        let origin = match &clock {
+            Clock::Real => Instant::now(),
+            Clock::Virtual { now, .. } => now.get(),
+        };
+        Timers { origin, clock, wheel: RefCell::new(Wheel::new()) }
+    }
+
+    /// Return timers with a virtual clock starting at the current time,
+    /// that record each jump of the clock in `trace`.
+    pub(crate) fn simulated(trace: Trace) -> Timers {
+        Timers::new(Clock::Virtual { now: Cell::new(Instant::now()), trace })
+    }
+
+    fn current() -> Rc<Timers> {
+        TIMERS.with(|timers| timers.borrow().clone())
+    }
+
+    /// Make `timers` the current thread's timers, and return the ones they
+    /// replace.
+    pub(crate) fn install(timers: Rc<Timers>) -> Rc<Timers> {
+        TIMERS.with(|current| current.replace(timers))
+    }
+
+    pub(crate) fn now(&self) -> Instant {
+        match &self.clock {
+            Clock::Real => Instant::now(),
+            Clock::Virtual { now, .. } => now.get(),
+        }
+    }
+
+    /// Return the time at tick zero.
+    pub(crate) fn origin(&self) -> Instant {
+        self.origin
+    }
+
+    /// Return the time at `tick`.
+    fn instant_at(&self, tick: u64) -> Instant {
+        self.origin + Duration::from_nanos((TICK.as_nanos() as u64).saturating_mul(tick))
+    }
+
+    /// Return the first tick at or after `instant`.
+    fn tick_after(&self, instant: Instant) -> u64 {
+        let since = instant.saturating_duration_since(self.origin);
+        let ticks = since.as_nanos().div_ceil(TICK.as_nanos());
+        ticks.min(u64::MAX as u128) as u64
+    }
+
+    /// Return the last tick at or before `instant`.
+    fn tick_before(&self, instant: Instant) -> u64 {
+        let since = instant.saturating_duration_since(self.origin);
+        (since.as_nanos() / TICK.as_nanos()) as u64
+    }
+
+    /// Wake the timers that are due.
+    fn fire(&self) {
+        let now = self.tick_before(self.now());
+        let wakers = self.wheel.borrow_mut().advance(now);
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    fn park(&self, parker: &Parker) {
+        let next = self.wheel.borrow().next_expiration();
+        match (&self.clock, next) {
+            (Clock::Real, Some(tick)) => parker.park_deadline(self.instant_at(tick)),
+            (Clock::Real, None) => parker.park(),
+
+            // Nothing's happening until a timer fires, so move the clock on
+            // to the next one. Slots on the wheel's higher levels can come
+            // up with no timer due yet, so keep going until one is.
+            (Clock::Virtual { now, trace }, Some(_)) => {
+                let mut wheel = self.wheel.borrow_mut();
+                while let Some(tick) = wheel.next_expiration() {
+                    let wakers = wheel.advance(tick);
+                    if !wakers.is_empty() {
+                        now.set(self.instant_at(tick));
+                        trace.record(Event::Advance(now.get() - self.origin));
+                        drop(wheel);
+                        wakers.into_iter().for_each(Waker::wake);
+                        return;
+                    }
+                }
+            }
+
+            // With no timers pending, only another thread can wake us.
+            (Clock::Virtual { .. }, None) => parker.park(),
+        }
+        self.fire();
+    }
+}
+
+/// Park the current thread until `parker` is unparked or the next timer is
+/// due, then wake any timers that are.
+pub(crate) fn park(parker: &Parker) {
+    Timers::current().park(parker);
+}
+
+/// Wake any of the current thread's timers that are due, without blocking.
+pub(crate) fn fire_expired() {
+    Timers::current().fire();
+}
+
+/// Return a future that completes after `duration`.
+pub fn sleep(duration: Duration) -> Sleep {
+    let timers = Timers::current();
+    let deadline = timers.now() + duration;
+    Sleep { timers, deadline, timer: None }
+}
+
+/// Return a future that completes at `deadline`.
+pub fn sleep_until(deadline: Instant) -> Sleep {
+    Sleep { timers: Timers::current(), deadline, timer: None }
+}
+
+/// A future that completes at a given time. See `sleep` and `sleep_until`.
+pub struct Sleep {
+    timers: Rc<Timers>,
+    deadline: Instant,
+
+    /// Our entry in the wheel, once we've been polled.
+    timer: Option<TimerId>,
+}
+
+impl Sleep {
+    /// Return the time this future completes.
+    pub fn deadline(&self) -> Instant {
+        self.deadline
+    }
+
+    /// Change the time this future completes, even if it already has.
+    pub fn reset(&mut self, deadline: Instant) {
+        self.cancel();
+        self.deadline = deadline;
+    }
+
+    fn cancel(&mut self) {
+        if let Some(timer) = self.timer.take() {
+            self.timers.wheel.borrow_mut().remove(timer);
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.timers.now() >= self.deadline {
+            self.cancel();
+            return Poll::Ready(());
+        }
+
+        let this = &mut *self;
+        let mut wheel = this.timers.wheel.borrow_mut();
+        let registered = match this.timer {
+            Some(timer) => wheel.set_waker(timer, cx.waker()),
+            None => false,
+        };
+        if !registered {
+            let tick = this.timers.tick_after(this.deadline);
+            this.timer = Some(wheel.insert(tick, cx.waker().clone()));
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+impl fmt::Debug for Sleep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sleep").field("deadline", &self.deadline).finish()
+    }
+}
+
+/// Run `future`, giving up if it hasn't completed within `duration`.
+pub async fn timeout<F: Future>(future: F, duration: Duration) -> Result<F::Output, Elapsed> {
+    let give_up = async {
+        sleep(duration).await;
+        Err(Elapsed)
+    };
+    async { Ok(future.await) }.or(give_up).await
+}
+
+/// The error returned by `timeout` when the future took too long.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Elapsed;
+
+impl fmt::Display for Elapsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline has elapsed")
+    }
+}
+
+impl std::error::Error for Elapsed {}
+
+/// Return an `Interval` that ticks every `period`, starting one `period`
+/// from now.
+///
+/// # Panics
+///
+/// Panics if `period` is zero.
+pub fn interval(period: Duration) -> Interval {
+    assert!(period > Duration::from_millis(0), "interval period must be non-zero");
+    Interval { sleep: sleep(period), period }
+}
+
+/// A stream of evenly spaced instants. See `interval`.
+///
+/// Ticks stay on the schedule set when the interval was created. If the
+/// consumer falls behind by more than a period, the missed ticks are
+/// skipped, rather than delivered in a burst to catch up.
+#[derive(Debug)]
+pub struct Interval {
+    sleep: Sleep,
+    period: Duration,
+}
+
+impl Interval {
+    /// Wait for the next tick, and return the time it was due.
+    pub async fn tick(&mut self) -> Instant {
+        futures_lite::future::poll_fn(|cx| self.poll_tick(cx)).await
+    }
+
+    /// Return the interval's period.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    fn poll_tick(&mut self, cx: &mut Context<'_>) -> Poll<Instant> {
+        if Pin::new(&mut self.sleep).poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+        let due = self.sleep.deadline();
+        let now = self.sleep.timers.now();
+        let mut next = due + self.period;
+        if next <= now {
+            let behind = (now - due).as_nanos() / self.period.as_nanos();
+            next = due + self.period * (behind as u32 + 1);
+        }
+        self.sleep.reset(next);
+        Poll::Ready(due)
+    }
+}
+
+impl Stream for Interval {
+    type Item = Instant;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Instant>> {
+        self.get_mut().poll_tick(cx).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{interval, sleep, sleep_until, timeout, Elapsed};
+    use crate::{spawn_local, Simulation};
+    use futures_lite::StreamExt;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+
+    fn ms(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    // These run on a simulation's virtual clock, so that they can check
+    // times exactly, however busy the machine is.
+
+    #[test]
+    fn sleeping() {
+        let sim = Simulation::new(0);
+        let start = sim.now();
+        sim.run(sleep(ms(50)));
+        assert_eq!(sim.now() - start, ms(50));
+
+        // A deadline in the past is ready straight away.
+        sim.run(sleep_until(start));
+        assert_eq!(sim.now() - start, ms(50));
+
+        // Sleeps on the same thread share the wheel, and wake in order.
+        let log = Rc::new(RefCell::new(vec![]));
+        let start = sim.now();
+        sim.run(async {
+            let handles: Vec<_> = [30, 10, 20].iter().map(|&n| {
+                let log = log.clone();
+                spawn_local(async move {
+                    sleep(ms(n)).await;
+                    log.borrow_mut().push(n);
+                })
+            }).collect();
+            for handle in handles {
+                handle.await;
+            }
+        });
+        assert_eq!(*log.borrow(), [10, 20, 30]);
+        assert_eq!(sim.now() - start, ms(30));
+    }
+
+    #[test]
+    fn timeouts() {
+        let sim = Simulation::new(0);
+        assert_eq!(sim.run(timeout(async { 7 }, ms(10))), Ok(7));
+        assert_eq!(sim.elapsed(), ms(0));
+        assert_eq!(sim.run(timeout(sleep(ms(1000)), ms(20))), Err(Elapsed));
+        assert_eq!(sim.elapsed(), ms(20));
+        assert_eq!(sim.run(timeout(sleep(ms(10)), ms(1000))), Ok(()));
+        assert_eq!(sim.elapsed(), ms(30));
+        assert_eq!(Elapsed.to_string(), "deadline has elapsed");
+
+        // The timers the timeouts abandoned are gone, and hold nothing up.
+        sim.run(sleep(ms(5)));
+        assert_eq!(sim.elapsed(), ms(35));
+    }
+
+    #[test]
+    fn intervals() {
+        let sim = Simulation::new(0);
+        let start = sim.now();
+        let mut ticks = interval(ms(10));
+        assert_eq!(ticks.period(), ms(10));
+        let due: Vec<_> = sim.run(async {
+            let mut due = vec![ticks.tick().await];
+            due.extend(ticks.next().await);
+            due.push(ticks.tick().await);
+            due
+        });
+        assert_eq!(due, [start + ms(10), start + ms(20), start + ms(30)]);
+        assert_eq!(sim.now(), due[2]);
+
+        // Falling behind skips ticks rather than bursting, but stays on
+        // schedule.
+        sim.run(sleep(ms(35)));
+        let late = sim.run(ticks.tick());
+        assert_eq!(late, start + ms(40));
+        let next = sim.run(ticks.tick());
+        assert_eq!(next, start + ms(70));
+        assert_eq!(sim.now(), next);
+    }
+
+    #[test]
+    #[should_panic(expected = "interval period must be non-zero")]
+    fn zero_interval() {
+        interval(ms(0));
+    }
+}
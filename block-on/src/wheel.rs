@@ -0,0 +1,307 @@
+//! A hierarchical timer wheel.
+//!
+//! Time is measured in ticks. The wheel has `LEVELS` levels of `SLOTS` slots
+//! each; a slot on level 0 covers one tick, a slot on level 1 covers
+//! `SLOTS` ticks, and so on. A timer goes in the lowest level whose slots
+//! are coarse enough to tell its deadline apart from the current time, so
+//! inserting a timer takes constant time however many there are, and
+//! removing one takes time proportional to the number sharing its slot. As
+//! time reaches a slot on a higher level, its timers are moved down
+//! to finer slots, until they land on level 0 and fire.
+
+use std::collections::HashMap;
+use std::task::Waker;
+
+const LEVELS: usize = 6;
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+
+/// The furthest ahead a timer can be placed directly. Timers further out go
+/// as far as they can, and are moved along when their slot comes up.
+const MAX_TICKS: u64 = (1 << (SLOT_BITS * LEVELS as u32)) - 1;
+
+/// Identifies a timer in a `Wheel`.
+pub(crate) type TimerId = u64;
+
+struct Entry {
+    deadline: u64,
+    waker: Option<Waker>,
+
+    /// The level and slot the timer's id is in.
+    level: usize,
+    slot: usize,
+}
+
+pub(crate) struct Wheel {
+    /// The current time, in ticks. Every timer's deadline is later.
+    elapsed: u64,
+
+    /// The ids of the timers in each slot of each level.
+    slots: Vec<Vec<Vec<TimerId>>>,
+
+    /// For each level, a bitmap of the slots that are occupied.
+    occupied: [u64; LEVELS],
+
+    entries: HashMap<TimerId, Entry>,
+    next_id: TimerId,
+}
+
+impl Wheel {
+    pub(crate) fn new() -> Wheel {
+        Wheel {
+            elapsed: 0,
+            slots: (0..LEVELS).map(|_| vec![vec![]; SLOTS]).collect(),
+            occupied: [0; LEVELS],
+            entries: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Return the current time, in ticks.
+    #[cfg(test)]
+    pub(crate) fn elapsed(&self) -> u64 {
+        self.elapsed
+    }
+
+    /// Return the number of timers in the wheel.
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Add a timer that wakes `waker` at `deadline`. If that's not in the
+    /// future, the timer fires at the next tick.
+    pub(crate) fn insert(&mut self, deadline: u64, waker: Waker) -> TimerId {
+        let id = self.next_id;
+        self.next_id += 1;
+        let deadline = deadline.max(self.elapsed + 1);
+        self.entries.insert(id, Entry { deadline, waker: Some(waker), level: 0, slot: 0 });
+        self.place(id, deadline);
+        id
+    }
+
+    /// Replace the waker for timer `id`. Return false if the timer has
+    /// already fired or been removed.
+    pub(crate) fn set_waker(&mut self, id: TimerId, waker: &Waker) -> bool {
+        match self.entries.get_mut(&id) {
+            Some(entry) => {
+                match &entry.waker {
+                    Some(existing) if existing.will_wake(waker) => {}
+                    _ => entry.waker = Some(waker.clone()),
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Cancel timer `id`, if it hasn't already fired or been removed.
+    pub(crate) fn remove(&mut self, id: TimerId) {
+        let Entry { level, slot, .. } = match self.entries.remove(&id) {
+            Some(entry) => entry,
+            None => return,
+        };
+        let ids = &mut self.slots[level][slot];
+        if let Some(i) = ids.iter().position(|&other| other == id) {
+            // The order within a slot doesn't matter; `advance` sorts.
+            ids.swap_remove(i);
+        }
+        if ids.is_empty() {
+            self.occupied[level] &= !(1 << slot);
+        }
+    }
+
+    fn place(&mut self, id: TimerId, deadline: u64) {
+        // Keep far-off timers within the top level's current rotation, so
+        // that their slot is unambiguous.
+        let mut when = deadline.min(self.elapsed | MAX_TICKS);
+        if when == self.elapsed {
+            when += 1;
+        }
+        let level = level_for(self.elapsed, when);
+        let slot = slot_for(when, level);
+        self.slots[level][slot].push(id);
+        self.occupied[level] |= 1 << slot;
+        let entry = self.entries.get_mut(&id).unwrap();
+        entry.level = level;
+        entry.slot = slot;
+    }
+
+    /// Return the time of the next slot with timers in it. A timer may be
+    /// later than this, if it's in a higher level's slot.
+    pub(crate) fn next_expiration(&self) -> Option<u64> {
+        self.next_slot().map(|(_, _, time)| time)
+    }
+
+    /// Return the level, slot and start time of the next occupied slot.
+    fn next_slot(&self) -> Option<(usize, usize, u64)> {
+        // Timers on lower levels are always due before those on higher
+        // levels, so the first occupied level is the one we want.
+        let level = (0..LEVELS).find(|&level| self.occupied[level] != 0)?;
+        let slot_range = 1u64 << (SLOT_BITS * level as u32);
+        let level_range = slot_range << SLOT_BITS;
+
+        let now_slot = slot_for(self.elapsed, level);
+        let distance = self.occupied[level].rotate_right(now_slot as u32).trailing_zeros();
+        let slot = (now_slot + distance as usize) % SLOTS;
+
+        let level_start = self.elapsed & !(level_range - 1);
+        let mut time = level_start + slot as u64 * slot_range;
+        if time <= self.elapsed && level == 0 || time + slot_range <= self.elapsed {
+            // The slot is in the wheel's next rotation.
+            time += level_range;
+        }
+        Some((level, slot, time.max(self.elapsed)))
+    }
+
+    /// Advance the time to `now`, and return the wakers of the timers due by
+    /// then, in order of deadline, and of insertion for equal deadlines.
+    pub(crate) fn advance(&mut self, now: u64) -> Vec<Waker> {
+        let mut fired = vec![];
+        while let Some((level, slot, time)) = self.next_slot() {
+            if time > now {
+                break;
+            }
+            self.elapsed = time;
+            self.occupied[level] &= !(1 << slot);
+            for id in std::mem::take(&mut self.slots[level][slot]) {
+                let deadline = self.entries[&id].deadline;
+                if deadline <= now {
+                    let entry = self.entries.remove(&id).unwrap();
+                    fired.push((entry.deadline, id, entry.waker));
+                } else {
+                    self.place(id, deadline);
+                }
+            }
+        }
+        self.elapsed = self.elapsed.max(now);
+
+        fired.sort_by_key(|&(deadline, id, _)| (deadline, id));
+        fired.into_iter().filter_map(|(_, _, waker)| waker).collect()
+    }
+}
+
+/// Return the level a timer due at `when` belongs on, given the current
+/// time `elapsed`: the level at which their slots first agree.
+fn level_for(elapsed: u64, when: u64) -> usize {
+    let masked = (elapsed ^ when) | (SLOTS as u64 - 1);
+    let significant = 63 - masked.leading_zeros();
+    ((significant / SLOT_BITS) as usize).min(LEVELS - 1)
+}
+
+fn slot_for(when: u64, level: usize) -> usize {
+    ((when >> (SLOT_BITS * level as u32)) as usize) % SLOTS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Wheel, LEVELS, MAX_TICKS};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::task::Waker;
+
+    /// Return a waker that records `label` in `log` when woken.
+    fn logger(log: &Arc<Mutex<Vec<u64>>>, label: u64) -> Waker {
+        let log = log.clone();
+        waker_fn::waker_fn(move || log.lock().unwrap().push(label))
+    }
+
+    fn fire(wheel: &mut Wheel, now: u64) -> usize {
+        let wakers = wheel.advance(now);
+        let count = wakers.len();
+        wakers.into_iter().for_each(Waker::wake);
+        count
+    }
+
+    #[test]
+    fn timers_fire_in_order() {
+        let log = Arc::new(Mutex::new(vec![]));
+        let mut wheel = Wheel::new();
+        // Deadlines on every level, and some sharing slots.
+        let deadlines = [1, 5, 63, 64, 65, 100, 4095, 4096, 4097, 70_000, 300_000,
+                         16_777_216, 1_000_000_000, 100, 5];
+        for (i, &deadline) in deadlines.iter().enumerate() {
+            wheel.insert(deadline, logger(&log, deadline * 100 + i as u64));
+        }
+        assert_eq!(wheel.len(), deadlines.len());
+
+        // Step through time irregularly, checking that each timer fires at
+        // its deadline, not before, and that next_expiration never skips
+        // past one.
+        let mut now = 0;
+        while wheel.len() > 0 {
+            let next = wheel.next_expiration().unwrap();
+            assert!(next >= now);
+            let pending: Vec<u64> = deadlines.iter().cloned().filter(|&d| d > now).collect();
+            assert!(next <= *pending.iter().min().unwrap());
+            now = next;
+            let before = log.lock().unwrap().len();
+            fire(&mut wheel, now);
+            for &label in &log.lock().unwrap()[before..] {
+                assert_eq!(label / 100, now, "timer fired at the wrong time");
+            }
+        }
+
+        let mut expected: Vec<u64> = deadlines.iter().enumerate()
+            .map(|(i, &d)| d * 100 + i as u64)
+            .collect();
+        expected.sort();
+        assert_eq!(*log.lock().unwrap(), expected);
+    }
+
+    #[test]
+    fn big_jumps_and_removal() {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counter = || {
+            let count = count.clone();
+            waker_fn::waker_fn(move || { count.fetch_add(1, Ordering::SeqCst); })
+        };
+
+        let mut wheel = Wheel::new();
+        let ids: Vec<_> = (0..1000u64).map(|i| wheel.insert(i * 7919 % 100_000, counter())).collect();
+        for id in ids.iter().step_by(2) {
+            wheel.remove(*id);
+        }
+        assert_eq!(fire(&mut wheel, 50_000), 250);
+        assert_eq!(wheel.elapsed(), 50_000);
+        assert_eq!(fire(&mut wheel, 50_000), 0);
+
+        // Timers in the past fire on the next tick.
+        wheel.insert(10, counter());
+        assert_eq!(wheel.next_expiration(), Some(50_001));
+        assert_eq!(fire(&mut wheel, 200_000), 251);
+        assert_eq!(wheel.len(), 0);
+        assert_eq!(wheel.next_expiration(), None);
+
+        // Timers beyond the wheel's range still fire on time.
+        let far = 50 * MAX_TICKS;
+        wheel.insert(far, counter());
+        let mut now = wheel.elapsed();
+        while let Some(next) = wheel.next_expiration() {
+            assert!(next > now && next <= far);
+            now = next;
+            fire(&mut wheel, now);
+        }
+        assert_eq!(now, far);
+        assert_eq!(count.load(Ordering::SeqCst), 502);
+    }
+
+    #[test]
+    fn removal_empties_slots() {
+        let mut wheel = Wheel::new();
+        let noop = || waker_fn::waker_fn(|| {});
+        let soon = wheel.insert(10, noop());
+        let also_soon = wheel.insert(10, noop());
+        let later = wheel.insert(5000, noop());
+
+        wheel.remove(soon);
+        assert_eq!(wheel.next_expiration(), Some(10));
+        wheel.remove(also_soon);
+        assert!(wheel.next_expiration().unwrap() > 10);
+        wheel.remove(later);
+        wheel.remove(later);
+        assert_eq!(wheel.next_expiration(), None);
+        assert_eq!(wheel.occupied, [0; LEVELS]);
+        assert!(wheel.slots.iter().flatten().all(Vec::is_empty));
+    }
+}
@@ -0,0 +1,135 @@
+//! Compare element-wise arithmetic on `ComplexVec`, which keeps real and
+//! imaginary parts in separate buffers, against the same loops over a
+//! `Vec` of the book's `derive_everything::Complex`, which interleaves them.
+//!
+//! Each iteration starts by copying the inputs into the output buffer, so
+//! that repeated in-place operations don't drift towards overflow; both
+//! layouts pay for the same copy.
+//!
+//! Run with `cargo bench`.
+
+use complex::derive_everything::Complex as AosComplex;
+use complex::{Complex, ComplexVec};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const SIZES: [usize; 3] = [64, 1024, 16384];
+
+fn aos_sample(n: usize) -> Vec<AosComplex<f64>> {
+    (0..n).map(|i| AosComplex { re: i as f64 * 0.5, im: 1.0 - i as f64 * 0.25 }).collect()
+}
+
+fn soa_sample(n: usize) -> ComplexVec<f64> {
+    aos_sample(n).iter().map(|z| Complex::new(z.re, z.im)).collect()
+}
+
+fn add(c: &mut Criterion) {
+    let mut group = c.benchmark_group("add");
+    for &n in &SIZES {
+        let (a, b) = (aos_sample(n), aos_sample(n));
+        group.bench_with_input(BenchmarkId::new("aos", n), &n, |bench, _| {
+            let mut out = a.clone();
+            bench.iter(|| {
+                out.copy_from_slice(&a);
+                for (x, y) in out.iter_mut().zip(&b) {
+                    x.re += y.re;
+                    x.im += y.im;
+                }
+                black_box(&out);
+            })
+        });
+
+        let (a, b) = (soa_sample(n), soa_sample(n));
+        group.bench_with_input(BenchmarkId::new("soa", n), &n, |bench, _| {
+            let mut out = a.clone();
+            bench.iter(|| {
+                out.clone_from(&a);
+                out += &b;
+                black_box(&out);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn mul(c: &mut Criterion) {
+    let mut group = c.benchmark_group("mul");
+    for &n in &SIZES {
+        let (a, b) = (aos_sample(n), aos_sample(n));
+        group.bench_with_input(BenchmarkId::new("aos", n), &n, |bench, _| {
+            let mut out = a.clone();
+            bench.iter(|| {
+                out.copy_from_slice(&a);
+                for (x, y) in out.iter_mut().zip(&b) {
+                    *x = AosComplex {
+                        re: x.re * y.re - x.im * y.im,
+                        im: x.re * y.im + x.im * y.re,
+                    };
+                }
+                black_box(&out);
+            })
+        });
+
+        let (a, b) = (soa_sample(n), soa_sample(n));
+        group.bench_with_input(BenchmarkId::new("soa", n), &n, |bench, _| {
+            let mut out = a.clone();
+            bench.iter(|| {
+                out.clone_from(&a);
+                out *= &b;
+                black_box(&out);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn conj(c: &mut Criterion) {
+    let mut group = c.benchmark_group("conj");
+    for &n in &SIZES {
+        let a = aos_sample(n);
+        group.bench_with_input(BenchmarkId::new("aos", n), &n, |bench, _| {
+            let mut out = a.clone();
+            bench.iter(|| {
+                out.copy_from_slice(&a);
+                for x in out.iter_mut() {
+                    x.im = -x.im;
+                }
+                black_box(&out);
+            })
+        });
+
+        let a = soa_sample(n);
+        group.bench_with_input(BenchmarkId::new("soa", n), &n, |bench, _| {
+            let mut out = a.clone();
+            bench.iter(|| {
+                out.clone_from(&a);
+                out.conj_in_place();
+                black_box(&out);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn magnitude(c: &mut Criterion) {
+    let mut group = c.benchmark_group("magnitude");
+    for &n in &SIZES {
+        let a = aos_sample(n);
+        group.bench_with_input(BenchmarkId::new("aos", n), &n, |bench, _| {
+            bench.iter(|| {
+                let norms: Vec<f64> = a.iter()
+                    .map(|z| (z.re * z.re + z.im * z.im).sqrt())
+                    .collect();
+                black_box(norms)
+            })
+        });
+
+        let a = soa_sample(n);
+        group.bench_with_input(BenchmarkId::new("soa", n), &n, |bench, _| {
+            bench.iter(|| black_box(a.norms()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, add, mul, conj, magnitude);
+criterion_main!(benches);
@@ -0,0 +1,718 @@
+//! A single, complete `Complex` type.
+//!
+//! The other modules in this crate each show one step of the chapter's
+//! development in isolation. This one puts the pieces together: every
+//! arithmetic operator and its compound-assignment form, mixed arithmetic
+//! with scalars, `Sum` and `Product`, and the usual polar-form and
+//! transcendental functions for floating-point components. It also has the
+//! `Display` impl from the `formatting` module, and a `FromStr` impl that
+//! reads that output back.
+
+use std::iter::{Product, Sum};
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Complex<T> {
+    /// Real portion of the complex number
+    pub re: T,
+
+    /// Imaginary portion of the complex number
+    pub im: T,
+}
+
+/// Numeric types with additive and multiplicative identities.
+///
+/// `Sum` and `Product` need these to handle empty iterators, and the
+/// floating-point functions need them for constants like `1 + 0i`.
+pub trait Identities {
+    fn zero() -> Self;
+    fn one() -> Self;
+}
+
+/// Floating-point types, providing the functions `Complex`'s polar-form and
+/// transcendental methods are built from.
+pub trait Float:
+    Copy + PartialOrd + Identities
+    + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self>
+    + Div<Output = Self> + Neg<Output = Self>
+{
+    fn sqrt(self) -> Self;
+    fn exp(self) -> Self;
+    fn ln(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn atan2(self, other: Self) -> Self;
+    fn hypot(self, other: Self) -> Self;
+    fn to_degrees(self) -> Self;
+    fn to_radians(self) -> Self;
+    fn abs(self) -> Self;
+    fn is_sign_negative(self) -> bool;
+}
+
+macro_rules! impl_identities {
+    ( $( $t:ident )* ) => {
+        $(
+            impl Identities for $t {
+                fn zero() -> $t { 0 as $t }
+                fn one() -> $t { 1 as $t }
+            }
+        )*
+    };
+}
+
+impl_identities!(u8 i8 u16 i16 u32 i32 u64 i64 u128 i128 usize isize f32 f64);
+
+macro_rules! impl_float {
+    ( $( $t:ident )* ) => {
+        $(
+            impl Float for $t {
+                fn sqrt(self) -> $t { $t::sqrt(self) }
+                fn exp(self) -> $t { $t::exp(self) }
+                fn ln(self) -> $t { $t::ln(self) }
+                fn sin(self) -> $t { $t::sin(self) }
+                fn cos(self) -> $t { $t::cos(self) }
+                fn atan2(self, other: $t) -> $t { $t::atan2(self, other) }
+                fn hypot(self, other: $t) -> $t { $t::hypot(self, other) }
+                fn to_degrees(self) -> $t { $t::to_degrees(self) }
+                fn to_radians(self) -> $t { $t::to_radians(self) }
+                fn abs(self) -> $t { $t::abs(self) }
+                fn is_sign_negative(self) -> bool { $t::is_sign_negative(self) }
+            }
+        )*
+    };
+}
+
+impl_float!(f32 f64);
+
+impl<T> Complex<T> {
+    pub fn new(re: T, im: T) -> Complex<T> {
+        Complex { re, im }
+    }
+}
+
+impl<T: Identities> Complex<T> {
+    /// The imaginary unit, `0 + 1i`.
+    pub fn i() -> Complex<T> {
+        Complex { re: T::zero(), im: T::one() }
+    }
+}
+
+impl<T: Clone + Neg<Output = T>> Complex<T> {
+    /// Return the complex conjugate, `re - im i`.
+    pub fn conj(&self) -> Complex<T> {
+        Complex { re: self.re.clone(), im: -self.im.clone() }
+    }
+}
+
+impl<T: Clone + Add<Output = T> + Mul<Output = T>> Complex<T> {
+    /// Return the square of the magnitude, `re² + im²`. This is cheaper than
+    /// `norm`, and doesn't need a square root.
+    pub fn norm_sqr(&self) -> T {
+        self.re.clone() * self.re.clone() + self.im.clone() * self.im.clone()
+    }
+}
+
+impl<T: Float> Complex<T> {
+    /// Return the magnitude (also known as the absolute value or modulus).
+    pub fn norm(&self) -> T {
+        self.re.hypot(self.im)
+    }
+
+    /// Return the angle from the positive real axis, in radians, in the
+    /// range `[-π, π]`.
+    pub fn arg(&self) -> T {
+        self.im.atan2(self.re)
+    }
+
+    /// Build a complex number from its magnitude and angle in radians.
+    pub fn from_polar(r: T, theta: T) -> Complex<T> {
+        Complex { re: r * theta.cos(), im: r * theta.sin() }
+    }
+
+    /// Return the magnitude and angle in radians.
+    pub fn to_polar(&self) -> (T, T) {
+        (self.norm(), self.arg())
+    }
+
+    /// Return `e` raised to this power.
+    pub fn exp(&self) -> Complex<T> {
+        Complex::from_polar(self.re.exp(), self.im)
+    }
+
+    /// Return the principal natural logarithm, whose imaginary part lies in
+    /// `[-π, π]`.
+    pub fn ln(&self) -> Complex<T> {
+        let (r, theta) = self.to_polar();
+        Complex { re: r.ln(), im: theta }
+    }
+
+    /// Return the principal square root, whose real part is non-negative.
+    pub fn sqrt(&self) -> Complex<T> {
+        let two = T::one() + T::one();
+        let r = self.norm();
+        let re = ((r + self.re) / two).sqrt();
+        let im = ((r - self.re) / two).sqrt();
+        Complex { re, im: if self.im < T::zero() { -im } else { im } }
+    }
+
+    /// Return this number raised to the complex power `exp`, using the
+    /// principal logarithm. Zero raised to any power is zero, except that
+    /// zero to the zeroth power is one.
+    pub fn powc(&self, exp: Complex<T>) -> Complex<T> {
+        let zero = Complex { re: T::zero(), im: T::zero() };
+        if *self == zero {
+            return if exp == zero { Complex { re: T::one(), im: T::zero() } } else { zero };
+        }
+        (exp * self.ln()).exp()
+    }
+}
+
+// Complex-by-complex arithmetic.
+
+impl<T: Add<Output = T>> Add for Complex<T> {
+    type Output = Self;
+    fn add(self, rhs: Self) -> Self {
+        Complex { re: self.re + rhs.re, im: self.im + rhs.im }
+    }
+}
+
+impl<T: Sub<Output = T>> Sub for Complex<T> {
+    type Output = Self;
+    fn sub(self, rhs: Self) -> Self {
+        Complex { re: self.re - rhs.re, im: self.im - rhs.im }
+    }
+}
+
+impl<T> Mul for Complex<T>
+where
+    T: Clone + Add<Output = T> + Sub<Output = T> + Mul<Output = T>,
+{
+    type Output = Self;
+    fn mul(self, rhs: Self) -> Self {
+        Complex {
+            re: self.re.clone() * rhs.re.clone() - self.im.clone() * rhs.im.clone(),
+            im: self.im * rhs.re + self.re * rhs.im,
+        }
+    }
+}
+
+impl<T> Div for Complex<T>
+where
+    T: Clone + Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Div<Output = T>,
+{
+    type Output = Self;
+    fn div(self, rhs: Self) -> Self {
+        // Multiply top and bottom by the conjugate of `rhs`, leaving a real
+        // denominator.
+        let denom = rhs.norm_sqr();
+        Complex {
+            re: (self.re.clone() * rhs.re.clone() + self.im.clone() * rhs.im.clone())
+                / denom.clone(),
+            im: (self.im * rhs.re - self.re * rhs.im) / denom,
+        }
+    }
+}
+
+impl<T: Neg<Output = T>> Neg for Complex<T> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Complex { re: -self.re, im: -self.im }
+    }
+}
+
+// Complex-by-scalar arithmetic. Addition and subtraction affect only the
+// real part; multiplication and division scale both parts.
+
+impl<T: Add<Output = T>> Add<T> for Complex<T> {
+    type Output = Self;
+    fn add(self, rhs: T) -> Self {
+        Complex { re: self.re + rhs, im: self.im }
+    }
+}
+
+impl<T: Sub<Output = T>> Sub<T> for Complex<T> {
+    type Output = Self;
+    fn sub(self, rhs: T) -> Self {
+        Complex { re: self.re - rhs, im: self.im }
+    }
+}
+
+impl<T: Clone + Mul<Output = T>> Mul<T> for Complex<T> {
+    type Output = Self;
+    fn mul(self, rhs: T) -> Self {
+        Complex { re: self.re * rhs.clone(), im: self.im * rhs }
+    }
+}
+
+impl<T: Clone + Div<Output = T>> Div<T> for Complex<T> {
+    type Output = Self;
+    fn div(self, rhs: T) -> Self {
+        Complex { re: self.re / rhs.clone(), im: self.im / rhs }
+    }
+}
+
+// Scalar-by-complex arithmetic. The orphan rules forbid a blanket
+// `impl<T> Mul<Complex<T>> for T`, so we spell these out for each
+// primitive type.
+
+macro_rules! impl_scalar_lhs {
+    ( $( $t:ident )* ) => {
+        $(
+            impl Add<Complex<$t>> for $t {
+                type Output = Complex<$t>;
+                fn add(self, rhs: Complex<$t>) -> Complex<$t> {
+                    Complex { re: self + rhs.re, im: rhs.im }
+                }
+            }
+
+            impl Sub<Complex<$t>> for $t {
+                type Output = Complex<$t>;
+                fn sub(self, rhs: Complex<$t>) -> Complex<$t> {
+                    Complex { re: self - rhs.re, im: -rhs.im }
+                }
+            }
+
+            impl Mul<Complex<$t>> for $t {
+                type Output = Complex<$t>;
+                fn mul(self, rhs: Complex<$t>) -> Complex<$t> {
+                    Complex { re: self * rhs.re, im: self * rhs.im }
+                }
+            }
+
+            impl Div<Complex<$t>> for $t {
+                type Output = Complex<$t>;
+                fn div(self, rhs: Complex<$t>) -> Complex<$t> {
+                    Complex { re: self, im: 0 as $t } / rhs
+                }
+            }
+        )*
+    };
+}
+
+impl_scalar_lhs!(i8 i16 i32 i64 i128 isize f32 f64);
+
+// Compound assignment, in terms of the operators above.
+
+macro_rules! impl_op_assign {
+    ( $( $assign_trait:ident $assign_fn:ident => $op_trait:ident $op_fn:ident ),* ) => {
+        $(
+            impl<T: Clone> $assign_trait for Complex<T>
+            where Complex<T>: $op_trait<Output = Complex<T>>
+            {
+                fn $assign_fn(&mut self, rhs: Complex<T>) {
+                    *self = $op_trait::$op_fn(self.clone(), rhs);
+                }
+            }
+
+            impl<T: Clone> $assign_trait<T> for Complex<T>
+            where Complex<T>: $op_trait<T, Output = Complex<T>>
+            {
+                fn $assign_fn(&mut self, rhs: T) {
+                    *self = $op_trait::$op_fn(self.clone(), rhs);
+                }
+            }
+        )*
+    };
+}
+
+impl_op_assign!(AddAssign add_assign => Add add,
+                SubAssign sub_assign => Sub sub,
+                MulAssign mul_assign => Mul mul,
+                DivAssign div_assign => Div div);
+
+impl<T: Identities + Add<Output = T>> Sum for Complex<T> {
+    fn sum<I: Iterator<Item = Complex<T>>>(iter: I) -> Complex<T> {
+        iter.fold(Complex { re: T::zero(), im: T::zero() }, |a, b| a + b)
+    }
+}
+
+impl<'a, T: 'a + Clone + Identities + Add<Output = T>> Sum<&'a Complex<T>> for Complex<T> {
+    fn sum<I: Iterator<Item = &'a Complex<T>>>(iter: I) -> Complex<T> {
+        iter.cloned().sum()
+    }
+}
+
+impl<T> Product for Complex<T>
+where
+    T: Clone + Identities + Add<Output = T> + Sub<Output = T> + Mul<Output = T>,
+{
+    fn product<I: Iterator<Item = Complex<T>>>(iter: I) -> Complex<T> {
+        iter.fold(Complex { re: T::one(), im: T::zero() }, |a, b| a * b)
+    }
+}
+
+impl<'a, T> Product<&'a Complex<T>> for Complex<T>
+where
+    T: 'a + Clone + Identities + Add<Output = T> + Sub<Output = T> + Mul<Output = T>,
+{
+    fn product<I: Iterator<Item = &'a Complex<T>>>(iter: I) -> Complex<T> {
+        iter.cloned().product()
+    }
+}
+
+use std::fmt;
+
+/// Print as `re + im i`, or with the `{:#}` flag, in polar form as
+/// `magnitude ∠ angle°`, just as the `formatting` module's type does.
+impl<T: Float + fmt::Display> fmt::Display for Complex<T> {
+    fn fmt(&self, dest: &mut fmt::Formatter) -> fmt::Result {
+        let (re, im) = (self.re, self.im);
+        if dest.alternate() {
+            write!(dest, "{} ∠ {}°", self.norm(), self.arg().to_degrees())
+        } else {
+            // Go by the sign bit, so that -0 prints as `- 0i`.
+            let im_sign = if im.is_sign_negative() { '-' } else { '+' };
+            write!(dest, "{} {} {}i", re, im_sign, im.abs())
+        }
+    }
+}
+
+/// An error parsing a `Complex` from text.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseComplexError {
+    /// There was nothing to parse.
+    Empty,
+
+    /// This text should have been a number, but wasn't.
+    InvalidNumber(String),
+
+    /// This text follows a `+` or `-`, so it should have been the
+    /// imaginary part, but it lacks the trailing `i`.
+    MissingImaginaryUnit(String),
+}
+
+impl fmt::Display for ParseComplexError {
+    fn fmt(&self, dest: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseComplexError::Empty =>
+                write!(dest, "cannot parse complex number from empty string"),
+            ParseComplexError::InvalidNumber(text) =>
+                write!(dest, "invalid number in complex number: {:?}", text),
+            ParseComplexError::MissingImaginaryUnit(text) =>
+                write!(dest, "imaginary part {:?} should end with 'i'", text),
+        }
+    }
+}
+
+impl std::error::Error for ParseComplexError {}
+
+fn parse_number<T: FromStr>(text: &str) -> Result<T, ParseComplexError> {
+    let text = text.trim();
+    text.parse().map_err(|_| ParseComplexError::InvalidNumber(text.to_string()))
+}
+
+/// Parse the coefficient of an imaginary part, with its `i` already
+/// removed. A bare sign, or nothing at all, means one.
+fn parse_coefficient<T: Float + FromStr>(text: &str) -> Result<T, ParseComplexError> {
+    match text.trim() {
+        "" | "+" => Ok(T::one()),
+        "-" => Ok(-T::one()),
+        text => parse_number(text),
+    }
+}
+
+/// Return the byte offset of the `+` or `-` separating the real and
+/// imaginary parts of `text`, if there is one. Signs at the very start, or
+/// in a float's exponent like `1e-5`, don't count.
+fn find_separator(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    (1..bytes.len())
+        .rev()
+        .find(|&pos| matches!(bytes[pos], b'+' | b'-')
+                     && !matches!(bytes[pos - 1], b'e' | b'E'))
+}
+
+use std::str::FromStr;
+
+/// Parse a complex number written in any of these forms:
+///
+/// - rectangular, like `3.5 - 2i` or `-0.5+0.866i`;
+/// - purely real or purely imaginary, like `7`, `2.5i`, or `-i`;
+/// - polar, like `5∠0.927` with the angle in radians, or `2 ∠ 90°` with
+///   the angle in degrees, as the `{:#}` format prints it.
+///
+/// Whatever `Display` prints, this parses back to the same value, exactly
+/// for the rectangular form, and up to rounding error for the polar form.
+impl<T: Float + FromStr> FromStr for Complex<T> {
+    type Err = ParseComplexError;
+
+    fn from_str(s: &str) -> Result<Complex<T>, ParseComplexError> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(ParseComplexError::Empty);
+        }
+
+        if let Some((magnitude, angle)) = s.split_once('∠') {
+            let magnitude = parse_number(magnitude)?;
+            let angle = angle.trim();
+            let radians = match angle.strip_suffix('°') {
+                Some(degrees) => parse_number::<T>(degrees)?.to_radians(),
+                None => parse_number(angle)?,
+            };
+            return Ok(Complex::from_polar(magnitude, radians));
+        }
+
+        match find_separator(s) {
+            Some(pos) => {
+                let (re, im) = (&s[..pos], &s[pos..]);
+                let coefficient = match im.strip_suffix('i') {
+                    Some(coefficient) => coefficient,
+                    None => return Err(ParseComplexError::MissingImaginaryUnit(im.to_string())),
+                };
+                // The coefficient still carries the separating sign.
+                let sign = &coefficient[..1];
+                let im = parse_coefficient::<T>(&coefficient[1..])?;
+                Ok(Complex {
+                    re: parse_number(re)?,
+                    im: if sign == "-" { -im } else { im },
+                })
+            }
+            None => match s.strip_suffix('i') {
+                Some(coefficient) => Ok(Complex { re: T::zero(), im: parse_coefficient(coefficient)? }),
+                None => Ok(Complex { re: parse_number(s)?, im: T::zero() }),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+fn assert_close(actual: Complex<f64>, expected: Complex<f64>) {
+    assert!((actual - expected).norm() < 1e-10,
+            "{:?} is not close to {:?}", actual, expected);
+}
+
+#[test]
+fn arithmetic() {
+    let a = Complex::new(3, 4);
+    let b = Complex::new(1, -2);
+
+    assert_eq!(a + b, Complex::new(4, 2));
+    assert_eq!(a - b, Complex::new(2, 6));
+    assert_eq!(a * b, Complex::new(11, -2));
+    assert_eq!(-a, Complex::new(-3, -4));
+
+    // Division of Gaussian integers truncates, like integer division.
+    assert_eq!(Complex::new(11, -2) / b, a);
+    assert_eq!(Complex::new(1.0, 0.0) / Complex::new(0.0, 1.0), Complex::new(0.0, -1.0));
+    assert_close(Complex::new(3.0, 4.0) / Complex::new(1.0, -2.0), Complex::new(-1.0, 2.0));
+}
+
+#[test]
+fn compound_assignment() {
+    let mut z = Complex::new(1, 1);
+    z += Complex::new(2, 3);
+    assert_eq!(z, Complex::new(3, 4));
+    z -= Complex::new(1, 1);
+    assert_eq!(z, Complex::new(2, 3));
+    z *= Complex::new(0, 1);
+    assert_eq!(z, Complex::new(-3, 2));
+    z /= Complex::new(0, 1);
+    assert_eq!(z, Complex::new(2, 3));
+
+    z += 1;
+    assert_eq!(z, Complex::new(3, 3));
+    z -= 3;
+    assert_eq!(z, Complex::new(0, 3));
+    z *= 4;
+    assert_eq!(z, Complex::new(0, 12));
+    z /= 3;
+    assert_eq!(z, Complex::new(0, 4));
+}
+
+#[test]
+fn scalars() {
+    let z = Complex::new(2.0, -6.0);
+    assert_eq!(z + 1.0, Complex::new(3.0, -6.0));
+    assert_eq!(z - 1.0, Complex::new(1.0, -6.0));
+    assert_eq!(z * 0.5, Complex::new(1.0, -3.0));
+    assert_eq!(z / 2.0, Complex::new(1.0, -3.0));
+
+    assert_eq!(1.0 + z, Complex::new(3.0, -6.0));
+    assert_eq!(1.0 - z, Complex::new(-1.0, 6.0));
+    assert_eq!(0.5 * z, Complex::new(1.0, -3.0));
+    assert_close(1.0 / Complex::new(0.0, 2.0), Complex::new(0.0, -0.5));
+    assert_eq!(3 * Complex::new(1, 2), Complex::new(3, 6));
+}
+
+#[test]
+fn sum_and_product() {
+    let zs = vec![Complex::new(1, 2), Complex::new(3, 4), Complex::new(-1, 0)];
+    assert_eq!(zs.iter().sum::<Complex<i32>>(), Complex::new(3, 6));
+    assert_eq!(zs.iter().product::<Complex<i32>>(), Complex::new(5, -10));
+    assert_eq!(zs.into_iter().sum::<Complex<i32>>(), Complex::new(3, 6));
+
+    let empty: Vec<Complex<f64>> = vec![];
+    assert_eq!(empty.iter().sum::<Complex<f64>>(), Complex::new(0.0, 0.0));
+    assert_eq!(empty.iter().product::<Complex<f64>>(), Complex::new(1.0, 0.0));
+}
+
+#[test]
+fn conj_and_norm() {
+    let z = Complex::new(3.0, 4.0);
+    assert_eq!(z.conj(), Complex::new(3.0, -4.0));
+    assert_eq!(z.norm_sqr(), 25.0);
+    assert_eq!(z.norm(), 5.0);
+    assert_eq!(z * z.conj(), Complex::new(25.0, 0.0));
+    assert_eq!(Complex::new(3, 4).norm_sqr(), 25);
+    assert_eq!(Complex::<i32>::i() * Complex::i(), Complex::new(-1, 0));
+}
+
+#[test]
+fn polar() {
+    use std::f64::consts::{FRAC_PI_2, PI};
+
+    assert_eq!(Complex::new(0.0, 2.0).arg(), FRAC_PI_2);
+    assert_eq!(Complex::new(-1.0, 0.0).arg(), PI);
+    assert_eq!(Complex::new(0.0, 2.0).to_polar(), (2.0, FRAC_PI_2));
+    assert_close(Complex::from_polar(2.0, FRAC_PI_2), Complex::new(0.0, 2.0));
+
+    let z = Complex::new(-0.5, 0.866);
+    let (r, theta) = z.to_polar();
+    assert_close(Complex::from_polar(r, theta), z);
+}
+
+#[test]
+fn transcendental() {
+    use std::f64::consts::{E, FRAC_PI_2, PI};
+
+    let i = Complex::<f64>::i();
+
+    // Euler's identity.
+    assert_close((i * PI).exp(), Complex::new(-1.0, 0.0));
+    assert_close(Complex::new(1.0, 0.0).exp(), Complex::new(E, 0.0));
+
+    assert_close(Complex::new(-1.0, 0.0).ln(), Complex::new(0.0, PI));
+    assert_close(i.ln(), Complex::new(0.0, FRAC_PI_2));
+    let z = Complex::new(1.5, -2.5);
+    assert_close(z.ln().exp(), z);
+
+    assert_close(Complex::new(-4.0, 0.0).sqrt(), Complex::new(0.0, 2.0));
+    assert_close(Complex::new(3.0, 4.0).sqrt(), Complex::new(2.0, 1.0));
+    assert_close(Complex::new(3.0, -4.0).sqrt(), Complex::new(2.0, -1.0));
+    assert_close(z.sqrt() * z.sqrt(), z);
+
+    // i^i is real.
+    assert_close(i.powc(i), Complex::new((-FRAC_PI_2).exp(), 0.0));
+    assert_close(z.powc(Complex::new(2.0, 0.0)), z * z);
+    assert_close(z.powc(Complex::new(0.5, 0.0)), z.sqrt());
+    let zero = Complex::new(0.0, 0.0);
+    assert_eq!(zero.powc(zero), Complex::new(1.0, 0.0));
+    assert_eq!(zero.powc(z), zero);
+}
+
+#[test]
+fn single_precision() {
+    let z = Complex::new(3.0_f32, 4.0);
+    assert_eq!(z.norm(), 5.0);
+    assert!((z.sqrt() - Complex::new(2.0, 1.0)).norm() < 1e-6);
+}
+
+#[test]
+fn display() {
+    assert_eq!(format!("{}", Complex::new(-0.5, 0.866)), "-0.5 + 0.866i");
+    assert_eq!(format!("{}", Complex::new(-0.5, -0.866)), "-0.5 - 0.866i");
+    assert_eq!(format!("{}", Complex::new(0.0, 2.0)), "0 + 2i");
+    assert_eq!(format!("{}", Complex::new(3.0, 0.0).conj()), "3 - 0i");
+    assert_eq!(format!("{:#}", Complex::new(0.0, 2.0)), "2 ∠ 90°");
+}
+
+#[test]
+fn parse() {
+    let parse = |s: &str| s.parse::<Complex<f64>>();
+
+    assert_eq!(parse("3.5 - 2i"), Ok(Complex::new(3.5, -2.0)));
+    assert_eq!(parse("-0.5+0.866i"), Ok(Complex::new(-0.5, 0.866)));
+    assert_eq!(parse("  1e-3 + 2E+2i "), Ok(Complex::new(0.001, 200.0)));
+    assert_eq!(parse("1 + i"), Ok(Complex::new(1.0, 1.0)));
+    assert_eq!(parse("1 - i"), Ok(Complex::new(1.0, -1.0)));
+    assert_eq!(parse("7"), Ok(Complex::new(7.0, 0.0)));
+    assert_eq!(parse("-7"), Ok(Complex::new(-7.0, 0.0)));
+    assert_eq!(parse("2.5i"), Ok(Complex::new(0.0, 2.5)));
+    assert_eq!(parse("-2.5i"), Ok(Complex::new(0.0, -2.5)));
+    assert_eq!(parse("i"), Ok(Complex::new(0.0, 1.0)));
+    assert_eq!(parse("-i"), Ok(Complex::new(0.0, -1.0)));
+    assert_eq!(parse("-inf - infi"), Ok(Complex::new(f64::NEG_INFINITY, f64::NEG_INFINITY)));
+
+    assert_close(parse("5∠0.9272952180016122").unwrap(), Complex::new(3.0, 4.0));
+    assert_close(parse("2 ∠ 90°").unwrap(), Complex::new(0.0, 2.0));
+    assert_close(parse("1∠-180°").unwrap(), Complex::new(-1.0, 0.0));
+
+    assert_eq!("1.5 - 0.25i".parse::<Complex<f32>>(), Ok(Complex::new(1.5, -0.25)));
+}
+
+#[test]
+fn parse_errors() {
+    let parse = |s: &str| s.parse::<Complex<f64>>();
+
+    assert_eq!(parse("   "), Err(ParseComplexError::Empty));
+    assert_eq!(parse("three"), Err(ParseComplexError::InvalidNumber("three".to_string())));
+    assert_eq!(parse("3 + 4"), Err(ParseComplexError::MissingImaginaryUnit("+ 4".to_string())));
+    assert_eq!(parse("3 + fouri"), Err(ParseComplexError::InvalidNumber("four".to_string())));
+    assert_eq!(parse("3 + 4j"), Err(ParseComplexError::MissingImaginaryUnit("+ 4j".to_string())));
+    assert_eq!(parse("2i + 3"), Err(ParseComplexError::MissingImaginaryUnit("+ 3".to_string())));
+    assert_eq!(parse("5∠"), Err(ParseComplexError::InvalidNumber("".to_string())));
+    assert_eq!(parse("∠ 90°"), Err(ParseComplexError::InvalidNumber("".to_string())));
+    assert_eq!(parse("5∠ninety°"), Err(ParseComplexError::InvalidNumber("ninety".to_string())));
+
+    assert_eq!(parse("3 + 4").unwrap_err().to_string(),
+               "imaginary part \"+ 4\" should end with 'i'");
+}
+
+/// A tiny xorshift generator, so the round-trip test can try lots of
+/// arbitrary values without pulling in a dependency.
+#[cfg(test)]
+struct XorShift(u64);
+
+#[cfg(test)]
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Return an arbitrary `f64`: half the time any bit pattern at all,
+    /// including infinities, NaNs and subnormals; sometimes a zero of
+    /// either sign; otherwise something of everyday size.
+    fn next_f64(&mut self) -> f64 {
+        let bits = self.next();
+        if bits & 1 == 0 {
+            f64::from_bits(self.next())
+        } else if bits & 0b110 == 0 {
+            if bits & 0b1000 == 0 { 0.0 } else { -0.0 }
+        } else {
+            (bits >> 11) as f64 / (1_u64 << 40) as f64 - 4096.0
+        }
+    }
+}
+
+#[test]
+fn display_parse_round_trip() {
+    // Zeros must come back with the same sign.
+    fn same(a: f64, b: f64) -> bool {
+        a.to_bits() == b.to_bits() || (a.is_nan() && b.is_nan())
+    }
+
+    let mut rng = XorShift(0x2545_f491_4f6c_dd1d);
+    for _ in 0..100_000 {
+        let z = Complex::new(rng.next_f64(), rng.next_f64());
+
+        for z in [z, z.conj()] {
+            let text = z.to_string();
+            let parsed: Complex<f64> = text.parse()
+                .unwrap_or_else(|e| panic!("couldn't parse {:?}: {}", text, e));
+            assert!(same(parsed.re, z.re) && same(parsed.im, z.im),
+                    "{:?} printed as {:?}, which parsed as {:?}", z, text, parsed);
+        }
+
+        // The polar form goes through trigonometry and a conversion to
+        // degrees, so it only round-trips approximately.
+        if z.norm() < 1e100 && z.norm() > 1e-100 {
+            let text = format!("{:#}", z);
+            let parsed: Complex<f64> = text.parse().unwrap();
+            assert!((parsed - z).norm() <= z.norm() * 1e-12,
+                    "{:?} printed as {:?}, which parsed as {:?}", z, text, parsed);
+        }
+    }
+}
@@ -0,0 +1,305 @@
+//! Fast Fourier transforms over slices of `Complex<f64>`.
+//!
+//! Lengths that are powers of two use the classic iterative radix-2
+//! Cooley-Tukey algorithm, entirely in place. Other lengths are factored
+//! into primes and handled by a recursive mixed-radix transform, which needs
+//! a scratch buffer the same size as the input; small prime factors are
+//! transformed directly. A length whose smallest prime factor is large,
+//! including a large prime, is transformed with Bluestein's algorithm
+//! instead, which rewrites it as a convolution of power-of-two length. So
+//! every length takes O(n log n) time, though lengths with only small prime
+//! factors are several times faster than the rest.
+//!
+//! The forward transform is unnormalized, and the inverse divides by the
+//! length, so `ifft` undoes `fft`.
+
+use crate::Complex;
+use std::f64::consts::PI;
+
+/// Replace `data` with its discrete Fourier transform:
+///
+/// ```text
+/// X[k] = Σ x[j] · e^(-2πi·jk/n)
+/// ```
+///
+/// This takes O(n log n) time for any length. Lengths other than powers of
+/// two allocate working space; see the module documentation.
+pub fn fft(data: &mut [Complex<f64>]) {
+    transform(data, Direction::Forward);
+}
+
+/// Replace `data` with its inverse discrete Fourier transform:
+///
+/// ```text
+/// x[j] = (1/n) Σ X[k] · e^(2πi·jk/n)
+/// ```
+///
+/// This costs the same as `fft`.
+pub fn ifft(data: &mut [Complex<f64>]) {
+    transform(data, Direction::Inverse);
+    let scale = 1.0 / data.len() as f64;
+    for x in data {
+        *x *= scale;
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Forward,
+    Inverse,
+}
+
+impl Direction {
+    /// Return `e^(∓2πi·k/n)`, the twiddle factor for this direction.
+    fn twiddle(self, k: usize, n: usize) -> Complex<f64> {
+        let sign = match self {
+            Direction::Forward => -1.0,
+            Direction::Inverse => 1.0,
+        };
+        // Reduce `k` first, to keep the angle small and accurate.
+        let angle = sign * 2.0 * PI * (k % n) as f64 / n as f64;
+        Complex::from_polar(1.0, angle)
+    }
+
+    /// Return `e^(∓πi·k²/n)`, the chirp Bluestein's algorithm multiplies
+    /// by. This is `twiddle(k², 2n)`, but computed without overflow.
+    fn chirp(self, k: usize, n: usize) -> Complex<f64> {
+        let k_squared = (k as u128 * k as u128 % (2 * n as u128)) as usize;
+        self.twiddle(k_squared, 2 * n)
+    }
+}
+
+/// Prime factors up to this size are transformed directly, in time
+/// proportional to their square; for bigger ones, Bluestein's algorithm is
+/// faster.
+const DIRECT_LIMIT: usize = 32;
+
+fn transform(data: &mut [Complex<f64>], direction: Direction) {
+    if data.len().is_power_of_two() {
+        radix2(data, direction);
+    } else {
+        let mut scratch = vec![Complex::default(); data.len()];
+        mixed_radix(data, &mut scratch, direction);
+    }
+}
+
+/// Transform `data`, whose length must be a power of two, in place.
+fn radix2(data: &mut [Complex<f64>], direction: Direction) {
+    let n = data.len();
+    if n <= 1 {
+        return;
+    }
+
+    // Put the elements in bit-reversed index order...
+    let shift = usize::BITS - n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> shift;
+        if i < j {
+            data.swap(i, j);
+        }
+    }
+
+    // ...and then combine pairs of transforms of length `half` into
+    // transforms of length `2 * half`.
+    let mut half = 1;
+    while half < n {
+        for start in (0..n).step_by(2 * half) {
+            for k in 0..half {
+                let w = direction.twiddle(k, 2 * half);
+                let even = data[start + k];
+                let odd = data[start + k + half] * w;
+                data[start + k] = even + odd;
+                data[start + k + half] = even - odd;
+            }
+        }
+        half *= 2;
+    }
+}
+
+fn smallest_prime_factor(n: usize) -> usize {
+    (2..).take_while(|p| p * p <= n).find(|&p| n % p == 0).unwrap_or(n)
+}
+
+/// Transform `data` of any length, using `scratch`, which must be the same
+/// length, as working space.
+fn mixed_radix(data: &mut [Complex<f64>], scratch: &mut [Complex<f64>], direction: Direction) {
+    let n = data.len();
+    if n.is_power_of_two() {
+        radix2(data, direction);
+        return;
+    }
+
+    let p = smallest_prime_factor(n);
+    if p > DIRECT_LIMIT {
+        // Splitting would need at least one pass of work proportional to
+        // `n * p`, so treat the whole length as if it were prime.
+        bluestein(data, direction);
+        return;
+    }
+    if p == n {
+        // A prime length: compute the transform directly.
+        for (k, out) in scratch.iter_mut().enumerate() {
+            *out = data.iter()
+                .enumerate()
+                .map(|(j, &x)| x * direction.twiddle(j * k, n))
+                .sum();
+        }
+        data.copy_from_slice(scratch);
+        return;
+    }
+
+    // Split `data` into `p` interleaved subsequences of length `m`, laid
+    // out one after another in `scratch`, and transform each of them, using
+    // the corresponding stretch of `data` as their scratch space.
+    let m = n / p;
+    for r in 0..p {
+        for k in 0..m {
+            scratch[r * m + k] = data[k * p + r];
+        }
+    }
+    for (sub, sub_scratch) in scratch.chunks_mut(m).zip(data.chunks_mut(m)) {
+        mixed_radix(sub, sub_scratch, direction);
+    }
+
+    // Combine: X[k + q·m] = Σ_r w^(r·(k + q·m)) · Y_r[k].
+    for q in 0..p {
+        for k in 0..m {
+            let index = k + q * m;
+            data[index] = (0..p)
+                .map(|r| scratch[r * m + k] * direction.twiddle(r * index, n))
+                .sum();
+        }
+    }
+}
+
+/// Transform `data` of any length with Bluestein's algorithm. Since
+/// `jk = (j² + k² - (k - j)²) / 2`, the transform is
+///
+/// ```text
+/// X[k] = c[k] · Σ (x[j] · c[j]) · conj(c[k - j])     where c[j] = e^(-πi·j²/n)
+/// ```
+///
+/// a convolution, which we compute with power-of-two transforms.
+fn bluestein(data: &mut [Complex<f64>], direction: Direction) {
+    let n = data.len();
+    let size = (2 * n - 1).next_power_of_two();
+    let chirp: Vec<_> = (0..n).map(|k| direction.chirp(k, n)).collect();
+
+    let mut a = vec![Complex::default(); size];
+    for (a, (&x, &c)) in a.iter_mut().zip(data.iter().zip(&chirp)) {
+        *a = x * c;
+    }
+
+    // The filter runs over negative offsets too, which wrap around to the
+    // end of the buffer.
+    let mut b = vec![Complex::default(); size];
+    b[0] = chirp[0].conj();
+    for k in 1..n {
+        b[k] = chirp[k].conj();
+        b[size - k] = chirp[k].conj();
+    }
+
+    radix2(&mut a, Direction::Forward);
+    radix2(&mut b, Direction::Forward);
+    for (a, b) in a.iter_mut().zip(&b) {
+        *a *= *b;
+    }
+    radix2(&mut a, Direction::Inverse);
+
+    let scale = 1.0 / size as f64;
+    for (out, (&conv, &c)) in data.iter_mut().zip(a.iter().zip(&chirp)) {
+        *out = conv * c * scale;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fft, ifft};
+    use crate::Complex;
+    use std::f64::consts::PI;
+
+    /// The discrete Fourier transform, straight from the definition.
+    fn naive_dft(input: &[Complex<f64>]) -> Vec<Complex<f64>> {
+        let n = input.len();
+        (0..n)
+            .map(|k| {
+                input.iter()
+                    .enumerate()
+                    .map(|(j, &x)| {
+                        let angle = -2.0 * PI * ((j * k) % n) as f64 / n as f64;
+                        x * Complex::from_polar(1.0, angle)
+                    })
+                    .sum()
+            })
+            .collect()
+    }
+
+    /// Some unremarkable test data.
+    fn signal(n: usize) -> Vec<Complex<f64>> {
+        (0..n)
+            .map(|i| {
+                let t = i as f64;
+                Complex::new((t * 0.7).sin() + t / 10.0, (t * 1.3).cos() - 0.5)
+            })
+            .collect()
+    }
+
+    fn assert_close(actual: &[Complex<f64>], expected: &[Complex<f64>]) {
+        assert_eq!(actual.len(), expected.len());
+        let tolerance = 1e-9 * (actual.len().max(1) as f64);
+        for (i, (a, e)) in actual.iter().zip(expected).enumerate() {
+            assert!((*a - *e).norm() < tolerance,
+                    "element {} of {}: {:?} != {:?}", i, actual.len(), a, e);
+        }
+    }
+
+    #[test]
+    fn matches_naive_dft() {
+        // Powers of two, primes, prime powers, and assorted composites.
+        // Lengths from 37 on include large prime factors.
+        let lengths = (0..=40).chain(vec![64, 97, 128, 210, 243, 360, 1000, 1024,
+                                          1009, 2 * 1009, 37 * 41, 3 * 5 * 47]);
+        for n in lengths {
+            let input = signal(n);
+            let mut output = input.clone();
+            fft(&mut output);
+            assert_close(&output, &naive_dft(&input));
+        }
+    }
+
+    #[test]
+    fn inverse_round_trip() {
+        for &n in &[1, 2, 8, 12, 17, 100, 256, 720, 10007, 2 * 10007] {
+            let input = signal(n);
+            let mut data = input.clone();
+            fft(&mut data);
+            ifft(&mut data);
+            assert_close(&data, &input);
+        }
+    }
+
+    #[test]
+    fn known_transforms() {
+        // An impulse transforms to a constant...
+        let mut data = vec![Complex::new(0.0, 0.0); 12];
+        data[0] = Complex::new(1.0, 0.0);
+        fft(&mut data);
+        assert_close(&data, &[Complex::new(1.0, 0.0); 12]);
+
+        // ...and a pure tone to an impulse in its frequency bin.
+        let n = 48;
+        let mut data: Vec<_> = (0..n)
+            .map(|j| Complex::from_polar(1.0, 2.0 * PI * 5.0 * j as f64 / n as f64))
+            .collect();
+        fft(&mut data);
+        let mut expected = vec![Complex::new(0.0, 0.0); n];
+        expected[5] = Complex::new(n as f64, 0.0);
+        assert_close(&data, &expected);
+
+        // Empty input is fine.
+        let mut empty: Vec<Complex<f64>> = vec![];
+        fft(&mut empty);
+        ifft(&mut empty);
+        assert!(empty.is_empty());
+    }
+}
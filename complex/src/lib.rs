@@ -5,10 +5,26 @@
 //! isolated modules, each of which defines its own `Complex` type in its own
 //! way. The `first_cut` module is the most well-developed.
 //!
+//! The `complex` module gathers everything together into a single public
+//! `Complex` type, re-exported here, with the full set of arithmetic
+//! operators and the usual floating-point functions.
+//!
+//! The `fft` and `matrix` modules build on that type with fast Fourier
+//! transforms and a small dense complex matrix type, and `soa` provides
+//! `ComplexVec`, a structure-of-arrays vector of complex numbers for
+//! SIMD-friendly bulk arithmetic.
+//!
 //! If you actually need a `Complex` type for real use, consider the
 //! `num_complex` crate, whose `Complex` type is incorporated into the `num`
 //! crate.
 
+pub mod complex;
+pub mod fft;
+pub mod matrix;
+pub mod soa;
+pub use complex::Complex;
+pub use soa::ComplexVec;
+
 macro_rules! define_complex {
     () => {
         #[derive(Clone, Copy, Debug)]
@@ -22,6 +38,7 @@ macro_rules! define_complex {
     };
 }
 
+#[allow(dead_code)]
 mod first_cut {
     #[derive(Clone, Copy, Debug)]
     struct Complex<T> {
@@ -104,6 +121,7 @@ mod first_cut {
     impl<T: Eq> Eq for Complex<T> {}
 }
 
+#[allow(dead_code)]
 mod non_generic_add {
     define_complex!();
 
@@ -120,6 +138,7 @@ mod non_generic_add {
     }
 }
 
+#[allow(dead_code)]
 mod somewhat_generic {
     define_complex!();
 
@@ -154,6 +173,7 @@ mod somewhat_generic {
     }
 }
 
+#[allow(dead_code)]
 mod very_generic {
     define_complex!();
 
@@ -173,6 +193,7 @@ mod very_generic {
     }
 }
 
+#[allow(dead_code)]
 mod impl_compound {
     define_complex!();
 
@@ -189,6 +210,7 @@ mod impl_compound {
     }
 }
 
+#[allow(dead_code)]
 mod derive_partialeq {
     #[derive(Clone, Copy, Debug, PartialEq)]
     struct Complex<T> {
@@ -197,14 +219,16 @@ mod derive_partialeq {
     }
 }
 
-mod derive_everything {
+// This one is public so that the `soa` benchmarks can compare against a
+// plain array-of-structs layout.
+pub mod derive_everything {
     #[derive(Clone, Copy, Debug, Eq, PartialEq)]
-    struct Complex<T> {
+    pub struct Complex<T> {
         /// Real portion of the complex number
-        re: T,
+        pub re: T,
 
         /// Imaginary portion of the complex number
-        im: T,
+        pub im: T,
     }
 }
 
@@ -0,0 +1,349 @@
+//! A small dense matrix of `Complex<f64>` values.
+//!
+//! This is meant for the modest systems that turn up in signal processing,
+//! not for serious numerical linear algebra; there's no blocking, and
+//! nothing is parallelized.
+
+use crate::Complex;
+use std::fmt;
+use std::ops::{Index, IndexMut, Mul};
+
+/// A matrix of complex numbers, stored in row-major order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    data: Vec<Complex<f64>>,
+}
+
+impl Matrix {
+    /// Return a `rows` by `cols` matrix of zeros.
+    pub fn zeros(rows: usize, cols: usize) -> Matrix {
+        Matrix { rows, cols, data: vec![Complex::default(); rows * cols] }
+    }
+
+    /// Return the `n` by `n` identity matrix.
+    pub fn identity(n: usize) -> Matrix {
+        let mut m = Matrix::zeros(n, n);
+        for i in 0..n {
+            m[(i, i)] = Complex::new(1.0, 0.0);
+        }
+        m
+    }
+
+    /// Build a matrix from its elements in row-major order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `data` doesn't hold exactly `rows * cols` elements.
+    pub fn from_vec(rows: usize, cols: usize, data: Vec<Complex<f64>>) -> Matrix {
+        assert_eq!(data.len(), rows * cols,
+                   "a {}x{} matrix needs {} elements", rows, cols, rows * cols);
+        Matrix { rows, cols, data }
+    }
+
+    /// Build a matrix from a function of each element's row and column.
+    pub fn from_fn<F>(rows: usize, cols: usize, mut f: F) -> Matrix
+    where F: FnMut(usize, usize) -> Complex<f64>
+    {
+        let data = (0..rows * cols).map(|i| f(i / cols, i % cols)).collect();
+        Matrix { rows, cols, data }
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn transpose(&self) -> Matrix {
+        Matrix::from_fn(self.cols, self.rows, |i, j| self[(j, i)])
+    }
+
+    /// Return the conjugate transpose, also called the Hermitian adjoint.
+    pub fn conj_transpose(&self) -> Matrix {
+        Matrix::from_fn(self.cols, self.rows, |i, j| self[(j, i)].conj())
+    }
+
+    /// Multiply this matrix by the column vector `v`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `v`'s length isn't the number of columns.
+    pub fn mul_vec(&self, v: &[Complex<f64>]) -> Vec<Complex<f64>> {
+        assert_eq!(v.len(), self.cols, "vector length doesn't match matrix columns");
+        if self.cols == 0 {
+            // There are no rows to chunk `data` into, but each entry of the
+            // result is still an empty sum.
+            return vec![Complex::default(); self.rows];
+        }
+        self.data
+            .chunks(self.cols)
+            .map(|row| row.iter().zip(v).map(|(&a, &b)| a * b).sum())
+            .collect()
+    }
+
+    /// Factor this matrix into lower and upper triangular matrices, with
+    /// partial pivoting, for use in solving linear systems.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the matrix isn't square.
+    pub fn lu(&self) -> Result<Lu, SingularMatrix> {
+        assert_eq!(self.rows, self.cols, "LU decomposition needs a square matrix");
+        let n = self.rows;
+        let mut lu = self.clone();
+        let mut permutation: Vec<usize> = (0..n).collect();
+
+        for col in 0..n {
+            // Choose the largest remaining entry in this column as the
+            // pivot, to keep rounding error under control.
+            let pivot = (col..n)
+                .max_by(|&a, &b| lu[(a, col)].norm().total_cmp(&lu[(b, col)].norm()))
+                .unwrap();
+            if lu[(pivot, col)].norm() == 0.0 {
+                return Err(SingularMatrix);
+            }
+            if pivot != col {
+                for j in 0..n {
+                    lu.data.swap(pivot * n + j, col * n + j);
+                }
+                permutation.swap(pivot, col);
+            }
+
+            for row in col + 1..n {
+                let factor = lu[(row, col)] / lu[(col, col)];
+                lu[(row, col)] = factor;
+                for j in col + 1..n {
+                    let above = lu[(col, j)];
+                    lu[(row, j)] -= factor * above;
+                }
+            }
+        }
+
+        Ok(Lu { lu, permutation })
+    }
+
+    /// Solve `self · x = b` for `x`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the matrix isn't square, or `b`'s length doesn't match.
+    pub fn solve(&self, b: &[Complex<f64>]) -> Result<Vec<Complex<f64>>, SingularMatrix> {
+        Ok(self.lu()?.solve(b))
+    }
+}
+
+impl Index<(usize, usize)> for Matrix {
+    type Output = Complex<f64>;
+
+    fn index(&self, (row, col): (usize, usize)) -> &Complex<f64> {
+        assert!(row < self.rows && col < self.cols, "matrix index out of bounds");
+        &self.data[row * self.cols + col]
+    }
+}
+
+impl IndexMut<(usize, usize)> for Matrix {
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut Complex<f64> {
+        assert!(row < self.rows && col < self.cols, "matrix index out of bounds");
+        &mut self.data[row * self.cols + col]
+    }
+}
+
+impl<'a> Mul for &'a Matrix {
+    type Output = Matrix;
+
+    fn mul(self, rhs: &'a Matrix) -> Matrix {
+        assert_eq!(self.cols, rhs.rows, "matrix dimensions don't match for multiplication");
+        let mut product = Matrix::zeros(self.rows, rhs.cols);
+        for i in 0..self.rows {
+            for k in 0..self.cols {
+                let a = self[(i, k)];
+                for j in 0..rhs.cols {
+                    product[(i, j)] += a * rhs[(k, j)];
+                }
+            }
+        }
+        product
+    }
+}
+
+impl Mul for Matrix {
+    type Output = Matrix;
+
+    fn mul(self, rhs: Matrix) -> Matrix {
+        &self * &rhs
+    }
+}
+
+/// The LU decomposition of a square matrix, as returned by `Matrix::lu`.
+///
+/// Decomposing once and then calling `solve` for each right-hand side is
+/// cheaper than calling `Matrix::solve` repeatedly.
+#[derive(Clone, Debug)]
+pub struct Lu {
+    /// `L` below the diagonal (its unit diagonal is implied), and `U` on
+    /// and above it.
+    lu: Matrix,
+
+    /// Row `i` of `L·U` is row `permutation[i]` of the original matrix.
+    permutation: Vec<usize>,
+}
+
+impl Lu {
+    /// Solve the original system for the right-hand side `b`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `b`'s length doesn't match the matrix size.
+    pub fn solve(&self, b: &[Complex<f64>]) -> Vec<Complex<f64>> {
+        let n = self.lu.rows;
+        assert_eq!(b.len(), n, "right-hand side length doesn't match matrix size");
+
+        // Forward substitution: L·y = P·b.
+        let mut x: Vec<Complex<f64>> = self.permutation.iter().map(|&i| b[i]).collect();
+        for i in 0..n {
+            for j in 0..i {
+                let xj = x[j];
+                x[i] -= self.lu[(i, j)] * xj;
+            }
+        }
+
+        // Back substitution: U·x = y.
+        for i in (0..n).rev() {
+            for j in i + 1..n {
+                let xj = x[j];
+                x[i] -= self.lu[(i, j)] * xj;
+            }
+            x[i] /= self.lu[(i, i)];
+        }
+        x
+    }
+}
+
+/// The error returned when asked to decompose or solve with a singular
+/// matrix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SingularMatrix;
+
+impl fmt::Display for SingularMatrix {
+    fn fmt(&self, dest: &mut fmt::Formatter) -> fmt::Result {
+        write!(dest, "matrix is singular")
+    }
+}
+
+impl std::error::Error for SingularMatrix {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Matrix, SingularMatrix};
+    use crate::Complex;
+
+    fn c(re: f64, im: f64) -> Complex<f64> {
+        Complex::new(re, im)
+    }
+
+    fn assert_close(actual: &[Complex<f64>], expected: &[Complex<f64>]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((*a - *e).norm() < 1e-10, "{:?} != {:?}", actual, expected);
+        }
+    }
+
+    #[test]
+    fn multiply() {
+        let a = Matrix::from_vec(2, 3, vec![c(1.0, 0.0), c(0.0, 1.0), c(2.0, 0.0),
+                                            c(0.0, 0.0), c(1.0, 1.0), c(0.0, -1.0)]);
+        let b = Matrix::from_vec(3, 2, vec![c(1.0, 0.0), c(0.0, 0.0),
+                                            c(0.0, 1.0), c(1.0, 0.0),
+                                            c(1.0, 0.0), c(0.0, 2.0)]);
+        let product = &a * &b;
+        assert_eq!((product.rows(), product.cols()), (2, 2));
+        assert_eq!(product, Matrix::from_vec(2, 2, vec![c(2.0, 0.0), c(0.0, 5.0),
+                                                        c(-1.0, 0.0), c(3.0, 1.0)]));
+
+        assert_eq!(&Matrix::identity(2) * &a, a);
+        assert_eq!(a.clone() * Matrix::identity(3), a);
+        assert_eq!(a.mul_vec(&[c(1.0, 0.0), c(0.0, 0.0), c(0.0, 0.0)]),
+                   vec![c(1.0, 0.0), c(0.0, 0.0)]);
+    }
+
+    #[test]
+    fn multiply_empty() {
+        assert_eq!(Matrix::zeros(3, 0).mul_vec(&[]), vec![c(0.0, 0.0); 3]);
+        assert_eq!(Matrix::zeros(0, 2).mul_vec(&[c(1.0, 0.0), c(2.0, 0.0)]), vec![]);
+        assert_eq!(&Matrix::zeros(2, 0) * &Matrix::zeros(0, 3), Matrix::zeros(2, 3));
+    }
+
+    #[test]
+    #[should_panic(expected = "matrix dimensions don't match")]
+    fn multiply_mismatched() {
+        let _ = &Matrix::zeros(2, 3) * &Matrix::zeros(2, 3);
+    }
+
+    #[test]
+    fn transposes() {
+        let a = Matrix::from_vec(2, 3, vec![c(1.0, 1.0), c(2.0, 0.0), c(3.0, -1.0),
+                                            c(4.0, 0.0), c(5.0, 2.0), c(6.0, 0.0)]);
+        let t = a.transpose();
+        assert_eq!((t.rows(), t.cols()), (3, 2));
+        assert_eq!(t[(2, 0)], c(3.0, -1.0));
+        assert_eq!(t.transpose(), a);
+
+        let h = a.conj_transpose();
+        assert_eq!(h[(2, 0)], c(3.0, 1.0));
+        assert_eq!(h[(1, 1)], c(5.0, -2.0));
+        assert_eq!(h.conj_transpose(), a);
+
+        // A·Aᴴ is Hermitian.
+        let aah = &a * &h;
+        assert_eq!(aah.conj_transpose(), aah);
+    }
+
+    #[test]
+    fn solve() {
+        let a = Matrix::from_vec(3, 3, vec![c(0.0, 0.0), c(2.0, 1.0), c(1.0, 0.0),
+                                            c(1.0, -1.0), c(0.0, 0.0), c(3.0, 0.0),
+                                            c(2.0, 0.0), c(1.0, 0.0), c(0.0, 1.0)]);
+        let x = vec![c(1.0, 2.0), c(-1.0, 0.5), c(0.0, -3.0)];
+        let b = a.mul_vec(&x);
+        assert_close(&a.solve(&b).unwrap(), &x);
+
+        // One decomposition can solve for several right-hand sides.
+        let lu = a.lu().unwrap();
+        for i in 0..3 {
+            let mut e = vec![c(0.0, 0.0); 3];
+            e[i] = c(1.0, 0.0);
+            let column = lu.solve(&e);
+            assert_close(&a.mul_vec(&column), &e);
+        }
+    }
+
+    #[test]
+    fn singular() {
+        let a = Matrix::from_vec(2, 2, vec![c(1.0, 1.0), c(2.0, 2.0),
+                                            c(2.0, 2.0), c(4.0, 4.0)]);
+        assert_eq!(a.lu().unwrap_err(), SingularMatrix);
+        assert_eq!(Matrix::zeros(3, 3).solve(&[c(1.0, 0.0); 3]), Err(SingularMatrix));
+    }
+
+    #[test]
+    fn dft_matrix_matches_fft() {
+        use std::f64::consts::PI;
+
+        let n = 12;
+        let dft = Matrix::from_fn(n, n, |j, k| {
+            Complex::from_polar(1.0, -2.0 * PI * ((j * k) % n) as f64 / n as f64)
+        });
+        let input: Vec<_> = (0..n).map(|i| c(i as f64, (i * i % 7) as f64)).collect();
+        let mut transformed = input.clone();
+        crate::fft::fft(&mut transformed);
+        assert_close(&dft.mul_vec(&input), &transformed);
+
+        // The DFT matrix over √n is unitary, so solving with it is an
+        // inverse transform.
+        assert_close(&dft.solve(&transformed).unwrap(), &input);
+    }
+}
@@ -0,0 +1,333 @@
+//! A structure-of-arrays vector of complex numbers.
+//!
+//! A `Vec<Complex<T>>` interleaves real and imaginary parts in memory,
+//! which makes it awkward for the compiler to use SIMD instructions on
+//! them: a vector register ends up holding a mix of both. `ComplexVec`
+//! instead keeps all the real parts in one buffer and all the imaginary
+//! parts in another, each aligned and padded to a whole number of SIMD
+//! lanes, so that element-wise loops compile down to straightforward
+//! vector arithmetic.
+
+use crate::complex::{Complex, Float};
+use std::fmt;
+use std::iter::FromIterator;
+use std::mem::size_of;
+use std::ops::{Add, AddAssign, Mul, MulAssign};
+
+/// The number of elements in each aligned block of a `ComplexVec` buffer.
+const LANES: usize = 8;
+
+/// A block of `LANES` values, aligned for the widest vector instructions
+/// we expect to use.
+#[derive(Clone, Copy)]
+#[repr(C, align(32))]
+struct Lanes<T>([T; LANES]);
+
+/// A vector of complex numbers, stored as separate buffers of real and
+/// imaginary parts.
+pub struct ComplexVec<T: Float> {
+    re: Vec<Lanes<T>>,
+    im: Vec<Lanes<T>>,
+    len: usize,
+}
+
+impl<T: Float> ComplexVec<T> {
+    /// Evaluating this fails to compile if `Lanes<T>` would have padding
+    /// between its elements, which would keep us from treating a buffer
+    /// as a plain slice of `T`.
+    const NO_PADDING: () = assert!(size_of::<Lanes<T>>() == LANES * size_of::<T>(),
+                                   "ComplexVec element type leaves padding between lanes");
+
+    pub fn new() -> ComplexVec<T> {
+        let () = Self::NO_PADDING;
+        ComplexVec { re: Vec::new(), im: Vec::new(), len: 0 }
+    }
+
+    /// Return a vector of `len` zeros.
+    pub fn zeros(len: usize) -> ComplexVec<T> {
+        let () = Self::NO_PADDING;
+        let blocks = (len + LANES - 1) / LANES;
+        let zero = Lanes([T::zero(); LANES]);
+        ComplexVec { re: vec![zero; blocks], im: vec![zero; blocks], len }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn push(&mut self, z: Complex<T>) {
+        if self.len % LANES == 0 {
+            self.re.push(Lanes([T::zero(); LANES]));
+            self.im.push(Lanes([T::zero(); LANES]));
+        }
+        let len = self.len;
+        self.len += 1;
+        self.re_mut()[len] = z.re;
+        self.im_mut()[len] = z.im;
+    }
+
+    pub fn get(&self, index: usize) -> Option<Complex<T>> {
+        if index < self.len {
+            Some(Complex { re: self.re()[index], im: self.im()[index] })
+        } else {
+            None
+        }
+    }
+
+    pub fn set(&mut self, index: usize, z: Complex<T>) {
+        self.re_mut()[index] = z.re;
+        self.im_mut()[index] = z.im;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Complex<T>> + '_ {
+        self.re().iter().zip(self.im()).map(|(&re, &im)| Complex { re, im })
+    }
+
+    /// Return the real parts, as a slice.
+    pub fn re(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.re.as_ptr() as *const T, self.len) }
+    }
+
+    /// Return the imaginary parts, as a slice.
+    pub fn im(&self) -> &[T] {
+        unsafe { std::slice::from_raw_parts(self.im.as_ptr() as *const T, self.len) }
+    }
+
+    pub fn re_mut(&mut self) -> &mut [T] {
+        unsafe { std::slice::from_raw_parts_mut(self.re.as_mut_ptr() as *mut T, self.len) }
+    }
+
+    pub fn im_mut(&mut self) -> &mut [T] {
+        unsafe { std::slice::from_raw_parts_mut(self.im.as_mut_ptr() as *mut T, self.len) }
+    }
+
+    /// Replace every element with its complex conjugate.
+    pub fn conj_in_place(&mut self) {
+        for block in &mut self.im {
+            for x in &mut block.0 {
+                *x = -*x;
+            }
+        }
+    }
+
+    /// Return the squared magnitude of each element.
+    pub fn norm_sqrs(&self) -> Vec<T> {
+        self.map_blocks(|re, im| re * re + im * im)
+    }
+
+    /// Return the magnitude of each element.
+    ///
+    /// Unlike `Complex::norm`, this doesn't use `hypot`, which doesn't
+    /// vectorize; so for elements whose squared magnitude overflows, the
+    /// result is infinite.
+    pub fn norms(&self) -> Vec<T> {
+        self.map_blocks(|re, im| (re * re + im * im).sqrt())
+    }
+
+    /// Apply `f` to the real and imaginary parts of each element, a whole
+    /// block at a time, and return the results.
+    fn map_blocks<F: Fn(T, T) -> T>(&self, f: F) -> Vec<T> {
+        let mut out = vec![T::zero(); self.re.len() * LANES];
+        let blocks = out.chunks_exact_mut(LANES).zip(self.re.iter().zip(&self.im));
+        for (out, (re, im)) in blocks {
+            for (i, x) in out.iter_mut().enumerate() {
+                *x = f(re.0[i], im.0[i]);
+            }
+        }
+        out.truncate(self.len);
+        out
+    }
+
+    fn assert_same_len(&self, other: &ComplexVec<T>) {
+        assert_eq!(self.len, other.len, "ComplexVec lengths differ");
+    }
+}
+
+// The padding elements past `len` are always zero, and stay zero under all
+// the element-wise operations below, so the kernels can simply process
+// whole blocks.
+
+impl<'a, T: Float> AddAssign<&'a ComplexVec<T>> for ComplexVec<T> {
+    fn add_assign(&mut self, rhs: &'a ComplexVec<T>) {
+        self.assert_same_len(rhs);
+        for (a, b) in self.re.iter_mut().zip(&rhs.re) {
+            for i in 0..LANES {
+                a.0[i] = a.0[i] + b.0[i];
+            }
+        }
+        for (a, b) in self.im.iter_mut().zip(&rhs.im) {
+            for i in 0..LANES {
+                a.0[i] = a.0[i] + b.0[i];
+            }
+        }
+    }
+}
+
+impl<'a, T: Float> MulAssign<&'a ComplexVec<T>> for ComplexVec<T> {
+    fn mul_assign(&mut self, rhs: &'a ComplexVec<T>) {
+        self.assert_same_len(rhs);
+        let blocks = self.re.iter_mut().zip(self.im.iter_mut()).zip(rhs.re.iter().zip(&rhs.im));
+        for ((a_re, a_im), (b_re, b_im)) in blocks {
+            for i in 0..LANES {
+                let re = a_re.0[i] * b_re.0[i] - a_im.0[i] * b_im.0[i];
+                let im = a_re.0[i] * b_im.0[i] + a_im.0[i] * b_re.0[i];
+                a_re.0[i] = re;
+                a_im.0[i] = im;
+            }
+        }
+    }
+}
+
+impl<'a, T: Float> Add for &'a ComplexVec<T> {
+    type Output = ComplexVec<T>;
+    fn add(self, rhs: &'a ComplexVec<T>) -> ComplexVec<T> {
+        let mut sum = self.clone();
+        sum += rhs;
+        sum
+    }
+}
+
+impl<'a, T: Float> Mul for &'a ComplexVec<T> {
+    type Output = ComplexVec<T>;
+    fn mul(self, rhs: &'a ComplexVec<T>) -> ComplexVec<T> {
+        let mut product = self.clone();
+        product *= rhs;
+        product
+    }
+}
+
+impl<T: Float> Clone for ComplexVec<T> {
+    fn clone(&self) -> ComplexVec<T> {
+        ComplexVec { re: self.re.clone(), im: self.im.clone(), len: self.len }
+    }
+
+    // The derived `clone_from` would allocate fresh buffers; reuse ours.
+    fn clone_from(&mut self, source: &ComplexVec<T>) {
+        self.re.clear();
+        self.re.extend_from_slice(&source.re);
+        self.im.clear();
+        self.im.extend_from_slice(&source.im);
+        self.len = source.len;
+    }
+}
+
+impl<T: Float> Default for ComplexVec<T> {
+    fn default() -> ComplexVec<T> {
+        ComplexVec::new()
+    }
+}
+
+impl<T: Float> PartialEq for ComplexVec<T> {
+    fn eq(&self, other: &ComplexVec<T>) -> bool {
+        self.re() == other.re() && self.im() == other.im()
+    }
+}
+
+impl<T: Float + fmt::Debug> fmt::Debug for ComplexVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Float> FromIterator<Complex<T>> for ComplexVec<T> {
+    fn from_iter<I: IntoIterator<Item = Complex<T>>>(iter: I) -> ComplexVec<T> {
+        let mut v = ComplexVec::new();
+        for z in iter {
+            v.push(z);
+        }
+        v
+    }
+}
+
+impl<'a, T: Float> From<&'a [Complex<T>]> for ComplexVec<T> {
+    fn from(slice: &'a [Complex<T>]) -> ComplexVec<T> {
+        let mut v = ComplexVec::zeros(slice.len());
+        for (i, z) in slice.iter().enumerate() {
+            v.set(i, *z);
+        }
+        v
+    }
+}
+
+impl<T: Float> From<Vec<Complex<T>>> for ComplexVec<T> {
+    fn from(vec: Vec<Complex<T>>) -> ComplexVec<T> {
+        ComplexVec::from(&vec[..])
+    }
+}
+
+impl<T: Float> From<ComplexVec<T>> for Vec<Complex<T>> {
+    fn from(v: ComplexVec<T>) -> Vec<Complex<T>> {
+        v.iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ComplexVec;
+    use crate::Complex;
+
+    fn sample(n: usize) -> Vec<Complex<f64>> {
+        (0..n).map(|i| Complex::new(i as f64, 1.0 - i as f64 / 2.0)).collect()
+    }
+
+    #[test]
+    fn conversions() {
+        for n in [0, 1, 7, 8, 9, 100] {
+            let aos = sample(n);
+            let soa = ComplexVec::from(aos.clone());
+            assert_eq!(soa.len(), n);
+            assert_eq!(soa.re(), aos.iter().map(|z| z.re).collect::<Vec<_>>());
+            assert_eq!(soa.im(), aos.iter().map(|z| z.im).collect::<Vec<_>>());
+            assert_eq!(soa, aos.iter().cloned().collect());
+            assert_eq!(Vec::from(soa), aos);
+        }
+
+        let mut v = ComplexVec::new();
+        assert!(v.is_empty());
+        v.push(Complex::new(1.0_f32, 2.0));
+        assert_eq!(v.get(0), Some(Complex::new(1.0, 2.0)));
+        assert_eq!(v.get(1), None);
+        assert_eq!(format!("{:?}", v), "[Complex { re: 1.0, im: 2.0 }]");
+    }
+
+    #[test]
+    fn buffers_are_aligned() {
+        let v = ComplexVec::from(sample(20));
+        assert_eq!(v.re().as_ptr() as usize % 32, 0);
+        assert_eq!(v.im().as_ptr() as usize % 32, 0);
+    }
+
+    #[test]
+    fn kernels_match_scalar_arithmetic() {
+        for n in [0, 3, 8, 13, 64] {
+            let a = sample(n);
+            let b: Vec<_> = a.iter().map(|z| Complex::new(z.im * 3.0, z.re - 2.0)).collect();
+            let (sa, sb) = (ComplexVec::from(a.clone()), ComplexVec::from(b.clone()));
+
+            let sums: Vec<_> = a.iter().zip(&b).map(|(x, y)| *x + *y).collect();
+            assert_eq!(Vec::from(&sa + &sb), sums);
+
+            let products: Vec<_> = a.iter().zip(&b).map(|(x, y)| *x * *y).collect();
+            assert_eq!(Vec::from(&sa * &sb), products);
+
+            let mut conj = sa.clone();
+            conj.conj_in_place();
+            assert_eq!(Vec::from(conj), a.iter().map(|z| z.conj()).collect::<Vec<_>>());
+
+            assert_eq!(sa.norm_sqrs(), a.iter().map(|z| z.norm_sqr()).collect::<Vec<_>>());
+            for (norm, z) in sa.norms().iter().zip(&a) {
+                assert!((norm - z.norm()).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "ComplexVec lengths differ")]
+    fn mismatched_lengths() {
+        let _ = &ComplexVec::<f64>::zeros(3) + &ComplexVec::zeros(4);
+    }
+}
@@ -1,10 +1,167 @@
 #![warn(rust_2018_idioms)]
 #![allow(elided_lifetimes_in_paths)]
 
-#[derive(Debug, PartialEq)]
-struct Interval<T> {
-    lower: T, // inclusive
-    upper: T, // exclusive
+use std::ops::Bound::{self, Excluded, Included, Unbounded};
+
+mod time;
+pub use time::TimePoint;
+
+/// A range of values of type `T`, described by a pair of endpoints.
+///
+/// Each endpoint may be closed (`Included`), open (`Excluded`), or absent
+/// altogether (`Unbounded`), exactly as with `std::ops::Bound`. The book's
+/// original inclusive-lower, exclusive-upper interval is what `Interval::new`
+/// builds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Interval<T> {
+    pub lower: Bound<T>,
+    pub upper: Bound<T>,
+}
+
+impl<T> Interval<T> {
+    /// The half-open interval `[lower, upper)`.
+    pub fn new(lower: T, upper: T) -> Interval<T> {
+        Interval { lower: Included(lower), upper: Excluded(upper) }
+    }
+
+    /// The closed interval `[lower, upper]`.
+    pub fn closed(lower: T, upper: T) -> Interval<T> {
+        Interval { lower: Included(lower), upper: Included(upper) }
+    }
+
+    /// The open interval `(lower, upper)`.
+    pub fn open(lower: T, upper: T) -> Interval<T> {
+        Interval { lower: Excluded(lower), upper: Excluded(upper) }
+    }
+
+    /// The interval containing every value of `T`.
+    pub fn unbounded() -> Interval<T> {
+        Interval { lower: Unbounded, upper: Unbounded }
+    }
+}
+
+impl<T: PartialOrd> Interval<T> {
+    /// Return true if `value` lies within this interval.
+    pub fn contains(&self, value: &T) -> bool {
+        let above_lower = match &self.lower {
+            Included(lower) => lower <= value,
+            Excluded(lower) => lower < value,
+            Unbounded => true,
+        };
+        let below_upper = match &self.upper {
+            Included(upper) => value <= upper,
+            Excluded(upper) => value < upper,
+            Unbounded => true,
+        };
+        above_lower && below_upper
+    }
+
+    /// Return true if no value of `T` could lie within this interval.
+    ///
+    /// This only looks at the endpoints, so an interval like `(1, 2)` over
+    /// integers is not considered empty, even though no integer lies in it.
+    pub fn is_empty(&self) -> bool {
+        match (&self.lower, &self.upper) {
+            (Included(lower), Included(upper)) => lower > upper,
+            (Included(lower), Excluded(upper)) |
+            (Excluded(lower), Included(upper)) |
+            (Excluded(lower), Excluded(upper)) => lower >= upper,
+            _ => false,
+        }
+    }
+}
+
+impl<T: PartialOrd + Clone> Interval<T> {
+    /// Return the parts of this interval not covered by any of `busy`, in
+    /// increasing order. The intervals in `busy` may overlap one another,
+    /// extend past `self`, and appear in any order.
+    ///
+    /// Endpoints are complemented exactly: if a busy interval ends at an
+    /// excluded bound, the following gap starts at an included one, and
+    /// vice versa.
+    pub fn gaps_within(&self, busy: &[Interval<T>]) -> Vec<Interval<T>> {
+        let mut busy: Vec<&Interval<T>> = busy.iter()
+            .filter(|b| !b.is_empty())
+            .collect();
+        busy.sort_by(|a, b| cmp_lower(&a.lower, &b.lower));
+
+        let mut gaps = vec![];
+        let mut free_from = self.lower.clone();
+        for b in busy {
+            // The gap before `b` ends just where `b` begins.
+            let gap_upper = match &b.lower {
+                Included(x) => Some(Excluded(x.clone())),
+                Excluded(x) => Some(Included(x.clone())),
+                Unbounded => None,
+            };
+            if let Some(gap_upper) = gap_upper {
+                let gap = Interval {
+                    lower: free_from.clone(),
+                    upper: earlier_upper(gap_upper, self.upper.clone()),
+                };
+                if !gap.is_empty() {
+                    gaps.push(gap);
+                }
+            }
+
+            free_from = match &b.upper {
+                Included(x) => later_lower(free_from, Excluded(x.clone())),
+                Excluded(x) => later_lower(free_from, Included(x.clone())),
+                Unbounded => return gaps,
+            };
+        }
+
+        let last = Interval { lower: free_from, upper: self.upper.clone() };
+        if !last.is_empty() {
+            gaps.push(last);
+        }
+        gaps
+    }
+}
+
+/// Compare two lower bounds by where the intervals they begin start.
+fn cmp_lower<T: PartialOrd>(a: &Bound<T>, b: &Bound<T>) -> Ordering {
+    match (a, b) {
+        (Unbounded, Unbounded) => Ordering::Equal,
+        (Unbounded, _) => Ordering::Less,
+        (_, Unbounded) => Ordering::Greater,
+        (Included(x), Included(y)) | (Excluded(x), Excluded(y)) =>
+            x.partial_cmp(y).unwrap_or(Ordering::Equal),
+        (Included(x), Excluded(y)) =>
+            if x <= y { Ordering::Less } else { Ordering::Greater },
+        (Excluded(x), Included(y)) =>
+            if x < y { Ordering::Less } else { Ordering::Greater },
+    }
+}
+
+/// Of two lower bounds, return the one that starts later.
+fn later_lower<T: PartialOrd>(a: Bound<T>, b: Bound<T>) -> Bound<T> {
+    if cmp_lower(&a, &b) == Ordering::Less { b } else { a }
+}
+
+/// Of two upper bounds, return the one that ends earlier.
+fn earlier_upper<T: PartialOrd>(a: Bound<T>, b: Bound<T>) -> Bound<T> {
+    let a_first = match (&a, &b) {
+        (_, Unbounded) => true,
+        (Unbounded, _) => false,
+        (Included(x), Included(y)) | (Excluded(x), Excluded(y)) => x <= y,
+        (Included(x), Excluded(y)) => x < y,
+        (Excluded(x), Included(y)) => x <= y,
+    };
+    if a_first { a } else { b }
+}
+
+/// Return true if every value below `upper` is less than every value above
+/// `lower`: that is, if an interval ending at `upper` lies entirely before
+/// an interval starting at `lower`.
+fn ends_before<T: PartialOrd>(upper: &Bound<T>, lower: &Bound<T>) -> bool {
+    match (upper, lower) {
+        (Included(upper), Included(lower)) => upper < lower,
+        (Included(upper), Excluded(lower)) |
+        (Excluded(upper), Included(lower)) |
+        (Excluded(upper), Excluded(lower)) => upper <= lower,
+        _ => false,
+    }
 }
 
 use std::cmp::{Ordering, PartialOrd};
@@ -13,9 +170,9 @@ impl<T: PartialOrd> PartialOrd<Interval<T>> for Interval<T> {
     fn partial_cmp(&self, other: &Interval<T>) -> Option<Ordering> {
         if self == other {
             Some(Ordering::Equal)
-        } else if self.lower >= other.upper {
+        } else if ends_before(&other.upper, &self.lower) {
             Some(Ordering::Greater)
-        } else if self.upper <= other.lower {
+        } else if ends_before(&self.upper, &other.lower) {
             Some(Ordering::Less)
         } else {
             None
@@ -23,16 +180,261 @@ impl<T: PartialOrd> PartialOrd<Interval<T>> for Interval<T> {
     }
 }
 
+use std::ops::RangeBounds;
+
+impl<T> RangeBounds<T> for Interval<T> {
+    fn start_bound(&self) -> Bound<&T> {
+        self.lower.as_ref()
+    }
+
+    fn end_bound(&self) -> Bound<&T> {
+        self.upper.as_ref()
+    }
+}
+
+use std::ops::{Range, RangeFrom, RangeFull, RangeInclusive, RangeTo, RangeToInclusive};
+
+impl<T> From<Range<T>> for Interval<T> {
+    fn from(range: Range<T>) -> Interval<T> {
+        Interval::new(range.start, range.end)
+    }
+}
+
+impl<T> From<RangeInclusive<T>> for Interval<T> {
+    fn from(range: RangeInclusive<T>) -> Interval<T> {
+        let (lower, upper) = range.into_inner();
+        Interval::closed(lower, upper)
+    }
+}
+
+impl<T> From<RangeFrom<T>> for Interval<T> {
+    fn from(range: RangeFrom<T>) -> Interval<T> {
+        Interval { lower: Included(range.start), upper: Unbounded }
+    }
+}
+
+impl<T> From<RangeTo<T>> for Interval<T> {
+    fn from(range: RangeTo<T>) -> Interval<T> {
+        Interval { lower: Unbounded, upper: Excluded(range.end) }
+    }
+}
+
+impl<T> From<RangeToInclusive<T>> for Interval<T> {
+    fn from(range: RangeToInclusive<T>) -> Interval<T> {
+        Interval { lower: Unbounded, upper: Included(range.end) }
+    }
+}
+
+impl<T> From<RangeFull> for Interval<T> {
+    fn from(_: RangeFull) -> Interval<T> {
+        Interval::unbounded()
+    }
+}
+
+use std::fmt;
+
+/// Intervals print in the usual mathematical notation: `[10, 20)`,
+/// `(-inf, 5]`, and so on.
+///
+/// An endpoint that is present but infinite, like `f64::INFINITY`, prints
+/// as `infinity` or `-infinity`, so that it doesn't read back as unbounded.
+impl<T: fmt::Display> fmt::Display for Interval<T> {
+    fn fmt(&self, dest: &mut fmt::Formatter) -> fmt::Result {
+        match &self.lower {
+            Included(lower) => write!(dest, "[{}", Endpoint(lower))?,
+            Excluded(lower) => write!(dest, "({}", Endpoint(lower))?,
+            Unbounded => write!(dest, "(-inf")?,
+        }
+        match &self.upper {
+            Included(upper) => write!(dest, ", {}]", Endpoint(upper)),
+            Excluded(upper) => write!(dest, ", {})", Endpoint(upper)),
+            Unbounded => write!(dest, ", +inf)"),
+        }
+    }
+}
+
+/// An endpoint's value, displayed so that it can't be mistaken for one of
+/// the spellings `FromStr` takes to mean unbounded.
+struct Endpoint<'a, T>(&'a T);
+
+impl<'a, T: fmt::Display> fmt::Display for Endpoint<'a, T> {
+    fn fmt(&self, dest: &mut fmt::Formatter) -> fmt::Result {
+        let text = self.0.to_string();
+        match text.as_str() {
+            "inf" | "+inf" | "-inf" => write!(dest, "{}inity", text),
+            _ => dest.write_str(&text),
+        }
+    }
+}
+
+/// An error parsing an `Interval` from text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseIntervalError<E> {
+    /// The text isn't of the form `[a, b)`; the string says what's wrong.
+    Syntax(&'static str),
+
+    /// One of the endpoints couldn't be parsed as a `T`.
+    Endpoint(E),
+}
+
+impl<E: fmt::Display> fmt::Display for ParseIntervalError<E> {
+    fn fmt(&self, dest: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseIntervalError::Syntax(msg) => write!(dest, "malformed interval: {}", msg),
+            ParseIntervalError::Endpoint(err) => write!(dest, "bad interval endpoint: {}", err),
+        }
+    }
+}
+
+impl<E: std::error::Error + 'static> std::error::Error for ParseIntervalError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseIntervalError::Syntax(_) => None,
+            ParseIntervalError::Endpoint(err) => Some(err),
+        }
+    }
+}
+
+/// Parse one endpoint of an interval. `infinities` are the spellings that
+/// mean this end is unbounded.
+fn parse_bound<T: FromStr>(text: &str, closed: bool, infinities: &[&str])
+                           -> Result<Bound<T>, ParseIntervalError<T::Err>>
+{
+    if infinities.contains(&text) {
+        if closed {
+            return Err(ParseIntervalError::Syntax("an infinite endpoint must be open"));
+        }
+        return Ok(Unbounded);
+    }
+
+    let value = text.parse().map_err(ParseIntervalError::Endpoint)?;
+    Ok(if closed { Included(value) } else { Excluded(value) })
+}
+
+use std::str::FromStr;
+
+/// Parse an interval written in mathematical notation, like `[10, 20)` or
+/// `(-inf, 5]`. Square brackets mark closed endpoints, parentheses open ones.
+///
+/// Endpoints are split at the first comma, so `T`'s own syntax must not
+/// contain commas.
+impl<T: FromStr> FromStr for Interval<T> {
+    type Err = ParseIntervalError<T::Err>;
+
+    fn from_str(s: &str) -> Result<Interval<T>, Self::Err> {
+        let s = s.trim();
+
+        let (lower_closed, rest) = if let Some(rest) = s.strip_prefix('[') {
+            (true, rest)
+        } else if let Some(rest) = s.strip_prefix('(') {
+            (false, rest)
+        } else {
+            return Err(ParseIntervalError::Syntax("expected '[' or '(' at start"));
+        };
+
+        let (upper_closed, rest) = if let Some(rest) = rest.strip_suffix(']') {
+            (true, rest)
+        } else if let Some(rest) = rest.strip_suffix(')') {
+            (false, rest)
+        } else {
+            return Err(ParseIntervalError::Syntax("expected ']' or ')' at end"));
+        };
+
+        let (lower, upper) = match rest.find(',') {
+            Some(comma) => (rest[..comma].trim(), rest[comma + 1..].trim()),
+            None => return Err(ParseIntervalError::Syntax("expected ',' between endpoints")),
+        };
+
+        Ok(Interval {
+            lower: parse_bound(lower, lower_closed, &["-inf"])?,
+            upper: parse_bound(upper, upper_closed, &["+inf", "inf"])?,
+        })
+    }
+}
+
 #[test]
+#[allow(clippy::neg_cmp_op_on_partial_ord)]
 fn test() {
-    assert!(Interval { lower: 10, upper: 20 } <  Interval { lower: 20, upper: 40 });
-    assert!(Interval { lower: 7,  upper: 8  } >= Interval { lower: 0,  upper: 1  });
-    assert!(Interval { lower: 7,  upper: 8  } <= Interval { lower: 7,  upper: 8  });
-    assert!(Interval { lower: 7,  upper: 8  }.le(&Interval { lower: 7,  upper: 8  }));
+    assert!(Interval::new(10, 20) <  Interval::new(20, 40));
+    assert!(Interval::new(7,  8 ) >= Interval::new(0,  1 ));
+    assert!(Interval::new(7,  8 ) <= Interval::new(7,  8 ));
+    assert!(Interval::new(7,  8 ).le(&Interval::new(7,  8 )));
 
     // Overlapping intervals aren't ordered with respect to each other.
-    let left  = Interval { lower: 10, upper: 30 };
-    let right = Interval { lower: 20, upper: 40 };
+    let left  = Interval::new(10, 30);
+    let right = Interval::new(20, 40);
     assert!(!(left < right));
     assert!(!(left >= right));
 }
+
+#[test]
+fn test_bounds() {
+    // Closed intervals sharing an endpoint overlap; open ones don't.
+    assert_eq!(Interval::closed(1, 5).partial_cmp(&Interval::closed(5, 9)), None);
+    assert!(Interval::closed(1, 5) < Interval::open(5, 9));
+    assert!(Interval::open(5, 9) > Interval::closed(1, 5));
+    assert!(Interval::from(..5) < Interval::from(5..));
+    assert_eq!(Interval::from(..=5).partial_cmp(&Interval::from(5..)), None);
+    assert_eq!(Interval::<i32>::unbounded().partial_cmp(&Interval::new(0, 1)), None);
+
+    let half_open = Interval::from(10..20);
+    assert!(half_open.contains(&10));
+    assert!(half_open.contains(&19));
+    assert!(!half_open.contains(&20));
+
+    let open = Interval::open(1.0, 2.0);
+    assert!(!open.contains(&1.0));
+    assert!(open.contains(&1.5));
+    assert!(!open.contains(&2.0));
+
+    assert!(Interval::from(..).contains(&i64::MIN));
+    assert!(Interval::from(3..=3).contains(&3));
+    assert!(!Interval::from(3..=3).is_empty());
+    assert!(Interval::from(3..3).is_empty());
+    assert!(Interval::open(3, 3).is_empty());
+    assert!(!Interval::from(3..).is_empty());
+
+    // `Interval` can be used anywhere the standard library wants a range.
+    let mut v: Vec<i32> = (0..10).collect();
+    assert_eq!(v.drain(Interval::open(2, 5)).collect::<Vec<_>>(), vec![3, 4]);
+}
+
+#[test]
+fn test_display_and_parse() {
+    let cases = [
+        ("[10, 20)", Interval::new(10, 20)),
+        ("[10, 20]", Interval::closed(10, 20)),
+        ("(10, 20)", Interval::open(10, 20)),
+        ("(-inf, 5]", Interval::from(..=5)),
+        ("[5, +inf)", Interval::from(5..)),
+        ("(-inf, +inf)", Interval::unbounded()),
+    ];
+    for (text, interval) in &cases {
+        assert_eq!(&interval.to_string(), text);
+        assert_eq!(&text.parse::<Interval<i32>>().unwrap(), interval);
+    }
+
+    assert_eq!("  ( -3 ,inf )".parse(), Ok(Interval { lower: Excluded(-3), upper: Unbounded }));
+    assert_eq!("[0.5, 1e3)".parse(), Ok(Interval::new(0.5, 1000.0)));
+
+    // Infinite endpoints are written differently from missing ones.
+    let infinite = [
+        (Interval::open(0.0, f64::INFINITY), "(0, infinity)"),
+        (Interval::closed(f64::NEG_INFINITY, 0.5), "[-infinity, 0.5]"),
+        (Interval::from(..f64::INFINITY), "(-inf, infinity)"),
+    ];
+    for (interval, text) in &infinite {
+        assert_eq!(&interval.to_string(), text);
+        assert_eq!(&text.parse::<Interval<f64>>().unwrap(), interval);
+    }
+
+    use ParseIntervalError::*;
+    assert!(matches!("10, 20)".parse::<Interval<i32>>(), Err(Syntax(_))));
+    assert!(matches!("[10, 20".parse::<Interval<i32>>(), Err(Syntax(_))));
+    assert!(matches!("[10 20)".parse::<Interval<i32>>(), Err(Syntax(_))));
+    assert!(matches!("[-inf, 20)".parse::<Interval<i32>>(), Err(Syntax(_))));
+    assert!(matches!("(inf, 20)".parse::<Interval<i32>>(), Err(Endpoint(_))));
+    assert!(matches!("[10, twenty)".parse::<Interval<i32>>(), Err(Endpoint(_))));
+    assert_eq!("[10, twenty)".parse::<Interval<i32>>().unwrap_err().to_string(),
+               "bad interval endpoint: invalid digit found in string");
+}
@@ -0,0 +1,302 @@
+//! Helpers for intervals of time: booking windows of `SystemTime`, offsets
+//! measured as `Duration`s, and so on.
+
+use crate::Interval;
+use std::ops::Bound::{Excluded, Included};
+use std::time::{Duration, SystemTime};
+
+/// A point in time that can be moved by a `Duration`.
+///
+/// This is implemented for `SystemTime`, and for `Duration` itself, treating
+/// it as an offset from some agreed-upon starting point.
+pub trait TimePoint: Copy + PartialOrd {
+    /// Return the point `d` later than `self`, or `None` on overflow.
+    fn forward(self, d: Duration) -> Option<Self>;
+
+    /// Return the point `d` earlier than `self`, or `None` on overflow.
+    fn backward(self, d: Duration) -> Option<Self>;
+
+    /// Return the time elapsed from `earlier` to `self`, or zero if
+    /// `earlier` is actually later.
+    fn elapsed_since(self, earlier: Self) -> Duration;
+}
+
+impl TimePoint for SystemTime {
+    fn forward(self, d: Duration) -> Option<SystemTime> {
+        self.checked_add(d)
+    }
+
+    fn backward(self, d: Duration) -> Option<SystemTime> {
+        self.checked_sub(d)
+    }
+
+    fn elapsed_since(self, earlier: SystemTime) -> Duration {
+        self.duration_since(earlier).unwrap_or(Duration::ZERO)
+    }
+}
+
+impl TimePoint for Duration {
+    fn forward(self, d: Duration) -> Option<Duration> {
+        self.checked_add(d)
+    }
+
+    fn backward(self, d: Duration) -> Option<Duration> {
+        self.checked_sub(d)
+    }
+
+    fn elapsed_since(self, earlier: Duration) -> Duration {
+        self.saturating_sub(earlier)
+    }
+}
+
+impl<T: TimePoint> Interval<T> {
+    /// Return the length of this interval, or `None` if either end is
+    /// unbounded. Whether the endpoints are open or closed makes no
+    /// difference; an interval whose upper end precedes its lower end has
+    /// length zero.
+    pub fn duration(&self) -> Option<Duration> {
+        match (self.lower, self.upper) {
+            (Included(lower), Included(upper)) |
+            (Included(lower), Excluded(upper)) |
+            (Excluded(lower), Included(upper)) |
+            (Excluded(lower), Excluded(upper)) => Some(upper.elapsed_since(lower)),
+            _ => None,
+        }
+    }
+
+    /// Return this interval moved `by` later, keeping the same kinds of
+    /// endpoints.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an endpoint moves past the range `T` can represent, just
+    /// as `SystemTime + Duration` does.
+    pub fn shift(&self, by: Duration) -> Interval<T> {
+        let forward = |t: T| t.forward(by).expect("overflow when shifting interval");
+        Interval { lower: self.lower.map(forward), upper: self.upper.map(forward) }
+    }
+
+    /// Return this interval moved `by` earlier, keeping the same kinds of
+    /// endpoints.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an endpoint moves past the range `T` can represent.
+    pub fn shift_back(&self, by: Duration) -> Interval<T> {
+        let backward = |t: T| t.backward(by).expect("overflow when shifting interval");
+        Interval { lower: self.lower.map(backward), upper: self.upper.map(backward) }
+    }
+
+    /// Cut this interval into consecutive pieces `step` long, in order. The
+    /// last piece may be shorter. The first and last pieces keep this
+    /// interval's own endpoints; the cuts between pieces belong to the
+    /// later piece, so interior pieces are half-open.
+    ///
+    /// An empty interval yields no pieces.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `step` is zero, or if either end of the interval is
+    /// unbounded.
+    pub fn split_every(&self, step: Duration) -> Vec<Interval<T>> {
+        assert!(step > Duration::ZERO, "split_every step must be nonzero");
+        let (start, end) = match (self.lower, self.upper) {
+            (Included(start) | Excluded(start), Included(end) | Excluded(end)) => (start, end),
+            _ => panic!("can't split an unbounded interval"),
+        };
+
+        let mut pieces = vec![];
+        if self.is_empty() {
+            return pieces;
+        }
+
+        let mut lower = self.lower;
+        let mut cut = start;
+        while let Some(next) = cut.forward(step) {
+            if next >= end {
+                break;
+            }
+            pieces.push(Interval { lower, upper: Excluded(next) });
+            lower = Included(next);
+            cut = next;
+        }
+        pieces.push(Interval { lower, upper: self.upper });
+        pieces
+    }
+}
+
+#[cfg(test)]
+fn secs(n: u64) -> Duration {
+    Duration::from_secs(n)
+}
+
+#[test]
+fn test_duration() {
+    assert_eq!(Interval::new(secs(10), secs(25)).duration(), Some(secs(15)));
+    assert_eq!(Interval::closed(secs(10), secs(25)).duration(), Some(secs(15)));
+    assert_eq!(Interval::open(secs(10), secs(25)).duration(), Some(secs(15)));
+    assert_eq!(Interval::closed(secs(10), secs(10)).duration(), Some(secs(0)));
+    assert_eq!(Interval::new(secs(25), secs(10)).duration(), Some(secs(0)));
+    assert_eq!(Interval::from(secs(10)..).duration(), None);
+    assert_eq!(Interval::from(..secs(10)).duration(), None);
+    assert_eq!(Interval::<Duration>::unbounded().duration(), None);
+
+    let noon = SystemTime::UNIX_EPOCH + secs(12 * 3600);
+    let window = Interval::new(noon, noon + secs(1800));
+    assert_eq!(window.duration(), Some(secs(1800)));
+    assert_eq!(Interval::new(noon + secs(1800), noon).duration(), Some(secs(0)));
+}
+
+#[test]
+fn test_shift() {
+    assert_eq!(Interval::new(secs(10), secs(20)).shift(secs(5)),
+               Interval::new(secs(15), secs(25)));
+    assert_eq!(Interval::open(secs(10), secs(20)).shift_back(secs(10)),
+               Interval::open(secs(0), secs(10)));
+    assert_eq!(Interval::from(..=secs(20)).shift(secs(1)),
+               Interval::from(..=secs(21)));
+    assert_eq!(Interval::from(secs(20)..).shift_back(secs(1)),
+               Interval::from(secs(19)..));
+    assert_eq!(Interval::<Duration>::unbounded().shift(secs(1)),
+               Interval::unbounded());
+
+    let noon = SystemTime::UNIX_EPOCH + secs(12 * 3600);
+    let window = Interval::closed(noon, noon + secs(60));
+    assert_eq!(window.shift(secs(3600)).shift_back(secs(3600)), window);
+    assert_eq!(window.shift(secs(3600)).duration(), window.duration());
+}
+
+#[test]
+#[should_panic(expected = "overflow when shifting interval")]
+fn test_shift_overflow() {
+    Interval::new(secs(0), secs(10)).shift_back(secs(1));
+}
+
+#[test]
+fn test_split_every() {
+    // Evenly divisible, half-open.
+    assert_eq!(Interval::new(secs(0), secs(30)).split_every(secs(10)),
+               vec![Interval::new(secs(0), secs(10)),
+                    Interval::new(secs(10), secs(20)),
+                    Interval::new(secs(20), secs(30))]);
+
+    // A shorter final piece.
+    assert_eq!(Interval::new(secs(0), secs(25)).split_every(secs(10)),
+               vec![Interval::new(secs(0), secs(10)),
+                    Interval::new(secs(10), secs(20)),
+                    Interval::new(secs(20), secs(25))]);
+
+    // The outer endpoints are preserved; interior cuts are half-open.
+    assert_eq!(Interval::open(secs(0), secs(20)).split_every(secs(10)),
+               vec![Interval { lower: Excluded(secs(0)), upper: Excluded(secs(10)) },
+                    Interval::new(secs(10), secs(20))]);
+    assert_eq!(Interval::closed(secs(0), secs(20)).split_every(secs(10)),
+               vec![Interval::new(secs(0), secs(10)),
+                    Interval::closed(secs(10), secs(20))]);
+
+    // Step longer than the interval.
+    assert_eq!(Interval::closed(secs(0), secs(5)).split_every(secs(10)),
+               vec![Interval::closed(secs(0), secs(5))]);
+
+    // A single point is one piece; an empty interval is none.
+    assert_eq!(Interval::closed(secs(5), secs(5)).split_every(secs(10)),
+               vec![Interval::closed(secs(5), secs(5))]);
+    assert_eq!(Interval::new(secs(5), secs(5)).split_every(secs(10)), vec![]);
+    assert_eq!(Interval::new(secs(9), secs(5)).split_every(secs(1)), vec![]);
+
+    // Stepping past the end of the representable range just stops.
+    assert_eq!(Interval::closed(Duration::MAX - secs(1), Duration::MAX).split_every(secs(10)),
+               vec![Interval::closed(Duration::MAX - secs(1), Duration::MAX)]);
+
+    let noon = SystemTime::UNIX_EPOCH + secs(12 * 3600);
+    let slots = Interval::new(noon, noon + secs(3600)).split_every(secs(900));
+    assert_eq!(slots.len(), 4);
+    assert_eq!(slots[3], Interval::new(noon + secs(2700), noon + secs(3600)));
+    assert!(slots.iter().all(|slot| slot.duration() == Some(secs(900))));
+}
+
+#[test]
+#[should_panic(expected = "step must be nonzero")]
+fn test_split_every_zero() {
+    Interval::new(secs(0), secs(10)).split_every(secs(0));
+}
+
+#[test]
+#[should_panic(expected = "can't split an unbounded interval")]
+fn test_split_every_unbounded() {
+    Interval::from(secs(0)..).split_every(secs(1));
+}
+
+#[test]
+fn test_gaps_within() {
+    let day = Interval::new(secs(0), secs(100));
+
+    // Nothing booked: the whole day is free.
+    assert_eq!(day.gaps_within(&[]), vec![day]);
+
+    // Bookings in any order, overlapping, and spilling past the day.
+    let busy = [
+        Interval::new(secs(60), secs(70)),
+        Interval::new(secs(10), secs(30)),
+        Interval::new(secs(20), secs(40)),
+        Interval::new(secs(90), secs(150)),
+    ];
+    assert_eq!(day.gaps_within(&busy),
+               vec![Interval::new(secs(0), secs(10)),
+                    Interval::new(secs(40), secs(60)),
+                    Interval::new(secs(70), secs(90))]);
+
+    // Back-to-back half-open bookings leave no gap between them.
+    let busy = [Interval::new(secs(0), secs(50)), Interval::new(secs(50), secs(100))];
+    assert_eq!(day.gaps_within(&busy), vec![]);
+
+    // Endpoints are complemented exactly.
+    let busy = [Interval::closed(secs(10), secs(20)), Interval::open(secs(20), secs(30))];
+    assert_eq!(day.gaps_within(&busy),
+               vec![Interval::new(secs(0), secs(10)),
+                    Interval::new(secs(30), secs(100))]);
+    let busy = [Interval::new(secs(10), secs(20)), Interval::open(secs(20), secs(30))];
+    assert_eq!(day.gaps_within(&busy),
+               vec![Interval::new(secs(0), secs(10)),
+                    Interval::closed(secs(20), secs(20)),
+                    Interval::new(secs(30), secs(100))]);
+    assert_eq!(Interval::closed(secs(0), secs(100))
+                   .gaps_within(&[Interval::open(secs(0), secs(100))]),
+               vec![Interval::closed(secs(0), secs(0)),
+                    Interval::closed(secs(100), secs(100))]);
+
+    // Bookings outside the day, and empty bookings, change nothing.
+    let busy = [Interval::new(secs(200), secs(300)), Interval::new(secs(50), secs(50))];
+    assert_eq!(day.gaps_within(&busy), vec![day]);
+    assert_eq!(Interval::new(secs(10), secs(20))
+                   .gaps_within(&[Interval::new(secs(0), secs(10))]),
+               vec![Interval::new(secs(10), secs(20))]);
+
+    // Unbounded bookings swallow everything on their side.
+    assert_eq!(day.gaps_within(&[Interval::from(secs(40)..)]),
+               vec![Interval::new(secs(0), secs(40))]);
+    assert_eq!(day.gaps_within(&[Interval::from(..=secs(40))]),
+               vec![Interval { lower: Excluded(secs(40)), upper: Excluded(secs(100)) }]);
+    assert_eq!(day.gaps_within(&[Interval::unbounded()]), vec![]);
+
+    // An unbounded search window.
+    assert_eq!(Interval::<Duration>::unbounded()
+                   .gaps_within(&[Interval::new(secs(10), secs(20))]),
+               vec![Interval::from(..secs(10)), Interval::from(secs(20)..)]);
+
+    // Free slots in a real booking calendar.
+    let nine = SystemTime::UNIX_EPOCH + secs(9 * 3600);
+    let hour = secs(3600);
+    let workday = Interval::new(nine, nine + hour * 8);
+    let meetings = [
+        Interval::new(nine + hour, nine + hour * 2),
+        Interval::new(nine + hour * 3, nine + hour * 5),
+    ];
+    let free = workday.gaps_within(&meetings);
+    assert_eq!(free,
+               vec![Interval::new(nine, nine + hour),
+                    Interval::new(nine + hour * 2, nine + hour * 3),
+                    Interval::new(nine + hour * 5, nine + hour * 8)]);
+    let free_time: Duration = free.iter().map(|slot| slot.duration().unwrap()).sum();
+    assert_eq!(free_time, hour * 5);
+}
@@ -1,18 +1,25 @@
 #![warn(rust_2018_idioms)]
 #![allow(elided_lifetimes_in_paths)]
 
-use std::collections::HashMap;
+#[macro_use] #[doc(hidden)] pub mod macros;
+mod map;
+mod number;
+mod parse;
+mod print;
 
-#[macro_use] mod macros;
+pub use map::Map;
+pub use number::Number;
+pub use parse::{ParseError, DEFAULT_MAX_DEPTH};
+pub use print::Pretty;
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum Json {
     Null,
     Boolean(bool),
-    Number(f64),
+    Number(Number),
     String(String),
     Array(Vec<Json>),
-    Object(Box<HashMap<String, Json>>)
+    Object(Box<Map>)
 }
 
 impl From<bool> for Json {
@@ -38,7 +45,7 @@ macro_rules! impl_from_num_for_json {
         $(
             impl From<$t> for Json {
                 fn from(n: $t) -> Json {
-                    Json::Number(n as f64)
+                    Json::Number(Number::from(n))
                 }
             }
         )*
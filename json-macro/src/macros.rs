@@ -1,5 +1,4 @@
 // macros.rs
-pub use std::collections::HashMap;
 pub use std::boxed::Box;
 pub use std::string::ToString;
 
@@ -13,8 +12,9 @@ macro_rules! json {
     };
     ({ $( $key:tt : $value:tt ),* }) => {
         {
+            #[allow(unused_mut)]
             let mut fields = $crate::macros::Box::new(
-                $crate::macros::HashMap::new());
+                $crate::Map::new());
             $(
                 fields.insert($crate::macros::ToString::to_string($key),
                               json!($value));
@@ -57,12 +57,12 @@ mod tests {
             let students = Json::Array(vec![
                 Json::Object(Box::new(vec![
                     ("name".to_string(), Json::String("Jim Blandy".to_string())),
-                    ("class_of".to_string(), Json::Number(1926.0)),
+                    ("class_of".to_string(), Json::Number(1926.into())),
                     ("major".to_string(), Json::String("Tibetan throat singing".to_string()))
                 ].into_iter().collect())),
                 Json::Object(Box::new(vec![
                     ("name".to_string(), Json::String("Jason Orendorff".to_string())),
-                    ("class_of".to_string(), Json::Number(1702.0)),
+                    ("class_of".to_string(), Json::Number(1702.into())),
                     ("major".to_string(), Json::String("Knots".to_string()))
                 ].into_iter().collect()))
             ]);
@@ -101,7 +101,7 @@ mod tests {
         let hand_coded_value =
             Json::Array(vec![
                 Json::Object(Box::new(vec![
-                    ("pitch".to_string(), Json::Number(440.0))
+                    ("pitch".to_string(), Json::Number(440.0.into()))
                 ].into_iter().collect()))
             ]);
         assert_eq!(macro_generated_value, hand_coded_value);
@@ -118,8 +118,8 @@ mod tests {
 
         let hand_coded_value =
             Json::Object(Box::new(vec![
-                ("width".to_string(), Json::Number(width)),
-                ("height".to_string(), Json::Number(width * 9.0 / 4.0))
+                ("width".to_string(), Json::Number(width.into())),
+                ("height".to_string(), Json::Number((width * 9.0 / 4.0).into()))
             ].into_iter().collect()));
         assert_eq!(desc, hand_coded_value);
     }
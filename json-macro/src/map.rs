@@ -0,0 +1,190 @@
+// map.rs
+//
+// The map type for JSON objects.
+
+use crate::Json;
+use std::collections::HashMap;
+use std::fmt;
+use std::iter::FromIterator;
+
+/// The fields of a JSON object, in the order they were inserted.
+///
+/// Looking up a key takes constant time; removing one takes time linear in
+/// the number of fields. Two maps are equal if they have the same fields,
+/// whatever their order, as JSON objects are.
+#[derive(Clone, Default)]
+pub struct Map {
+    entries: Vec<(String, Json)>,
+
+    /// The position of each key in `entries`.
+    index: HashMap<String, usize>
+}
+
+impl Map {
+    pub fn new() -> Map {
+        Map::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        self.index.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Json> {
+        let i = *self.index.get(key)?;
+        Some(&mut self.entries[i].1)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.index.contains_key(key)
+    }
+
+    /// Set the field `key` to `value`, and return its old value, if it had
+    /// one. A new key goes at the end; an existing key keeps its place.
+    pub fn insert(&mut self, key: String, value: Json) -> Option<Json> {
+        match self.index.get(&key) {
+            Some(&i) => Some(std::mem::replace(&mut self.entries[i].1, value)),
+            None => {
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+                None
+            }
+        }
+    }
+
+    /// Remove the field `key`, and return its value, if it had one. The
+    /// fields after it keep their order.
+    pub fn remove(&mut self, key: &str) -> Option<Json> {
+        let i = self.index.remove(key)?;
+        let (_, value) = self.entries.remove(i);
+        for (key, _) in &self.entries[i..] {
+            *self.index.get_mut(key).unwrap() -= 1;
+        }
+        Some(value)
+    }
+
+    /// Iterate over the fields in order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.entries.iter())
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Json> {
+        self.iter().map(|(_, value)| value)
+    }
+}
+
+/// An iterator over a `Map`'s fields. See `Map::iter`.
+pub struct Iter<'m>(std::slice::Iter<'m, (String, Json)>);
+
+impl<'m> Iterator for Iter<'m> {
+    type Item = (&'m str, &'m Json);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(key, value)| (key.as_str(), value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'m> IntoIterator for &'m Map {
+    type Item = (&'m str, &'m Json);
+    type IntoIter = Iter<'m>;
+
+    fn into_iter(self) -> Iter<'m> {
+        self.iter()
+    }
+}
+
+impl IntoIterator for Map {
+    type Item = (String, Json);
+    type IntoIter = std::vec::IntoIter<(String, Json)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl Extend<(String, Json)> for Map {
+    fn extend<I: IntoIterator<Item = (String, Json)>>(&mut self, fields: I) {
+        for (key, value) in fields {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl FromIterator<(String, Json)> for Map {
+    fn from_iter<I: IntoIterator<Item = (String, Json)>>(fields: I) -> Map {
+        let mut map = Map::new();
+        map.extend(fields);
+        map
+    }
+}
+
+impl PartialEq for Map {
+    fn eq(&self, other: &Map) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl fmt::Debug for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Map;
+    use crate::Json;
+
+    fn map(keys: &[&str]) -> Map {
+        keys.iter().enumerate().map(|(i, key)| (key.to_string(), Json::from(i as u32))).collect()
+    }
+
+    #[test]
+    fn insertion_order() {
+        let mut fields = map(&["z", "a", "m"]);
+        assert_eq!(fields.keys().collect::<Vec<_>>(), ["z", "a", "m"]);
+
+        assert_eq!(fields.insert("a".to_string(), json!("new")), Some(json!(1)));
+        assert_eq!(fields.insert("b".to_string(), json!(null)), None);
+        assert_eq!(fields.keys().collect::<Vec<_>>(), ["z", "a", "m", "b"]);
+        assert_eq!(fields.get("a"), Some(&json!("new")));
+
+        assert_eq!(fields.remove("z"), Some(json!(0)));
+        assert_eq!(fields.remove("z"), None);
+        assert_eq!(fields.keys().collect::<Vec<_>>(), ["a", "m", "b"]);
+        assert_eq!(fields.get("m"), Some(&json!(2)));
+        *fields.get_mut("b").unwrap() = json!(true);
+        assert_eq!(fields.values().collect::<Vec<_>>(), [&json!("new"), &json!(2), &json!(true)]);
+
+        let owned: Vec<_> = fields.clone().into_iter().map(|(key, _)| key).collect();
+        assert_eq!(owned, ["a", "m", "b"]);
+        assert_eq!(format!("{:?}", fields), r#"{"a": String("new"), "m": Number(2), "b": Boolean(true)}"#);
+    }
+
+    #[test]
+    fn equality_ignores_order() {
+        let mut reordered = map(&["a", "b"]);
+        reordered.remove("a");
+        reordered.insert("a".to_string(), json!(0));
+        assert_eq!(reordered, map(&["a", "b"]));
+        assert_ne!(map(&["a", "b"]), map(&["b", "a"]));
+        assert_ne!(map(&["a"]), map(&["a", "b"]));
+        assert!(Map::new().is_empty());
+    }
+}
@@ -0,0 +1,260 @@
+// number.rs
+//
+// The number type for JSON values.
+
+use std::convert::TryFrom;
+use std::fmt;
+
+/// A JSON number. Integers are stored exactly, however large they are, up
+/// to the range of `u128` and `i128`; anything else is stored as an `f64`.
+///
+/// Numbers compare equal if they have the same value, however they're
+/// stored: `Number::from(2)` equals `Number::from(2.0)`.
+#[derive(Clone, Copy)]
+pub struct Number(N);
+
+#[derive(Clone, Copy)]
+enum N {
+    UInt(u128),
+
+    /// Always negative.
+    NegInt(i128),
+    Float(f64)
+}
+
+/// 2^127 and 2^128, the limits of `i128` and `u128`. Each is exactly
+/// representable as an `f64`, unlike `u128::MAX`, which rounds up.
+const TWO_127: f64 = 170141183460469231731687303715884105728.0;
+const TWO_128: f64 = 340282366920938463463374607431768211456.0;
+
+/// Convert `f` to a `u128`, if that can be done exactly.
+fn exact_u128(f: f64) -> Option<u128> {
+    if f.fract() == 0.0 && (0.0..TWO_128).contains(&f) {
+        Some(f as u128)
+    } else {
+        None
+    }
+}
+
+/// Convert `f` to an `i128`, if that can be done exactly.
+fn exact_i128(f: f64) -> Option<i128> {
+    if f.fract() == 0.0 && (-TWO_127..TWO_127).contains(&f) {
+        Some(f as i128)
+    } else {
+        None
+    }
+}
+
+impl Number {
+    /// Return this number as a `u64`, or `None` if that would lose
+    /// information: that is, if it's negative, fractional, or too large.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.0 {
+            N::UInt(n) => u64::try_from(n).ok(),
+            N::NegInt(_) => None,
+            N::Float(f) => exact_u128(f).and_then(|n| u64::try_from(n).ok())
+        }
+    }
+
+    /// Return this number as an `i64`, or `None` if that would lose
+    /// information: that is, if it's fractional or out of range.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.0 {
+            N::UInt(n) => i64::try_from(n).ok(),
+            N::NegInt(n) => i64::try_from(n).ok(),
+            N::Float(f) => exact_i128(f).and_then(|n| i64::try_from(n).ok())
+        }
+    }
+
+    /// Return this number as an `f64`, or `None` if that would lose
+    /// information: that is, if it's an integer with more significant bits
+    /// than an `f64` can hold. Use `to_f64` for the nearest `f64` instead.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self.0 {
+            N::UInt(n) => Some(n as f64).filter(|&f| exact_u128(f) == Some(n)),
+            N::NegInt(n) => Some(n as f64).filter(|&f| exact_i128(f) == Some(n)),
+            N::Float(f) => Some(f)
+        }
+    }
+
+    /// Return the `f64` nearest to this number.
+    pub fn to_f64(&self) -> f64 {
+        match self.0 {
+            N::UInt(n) => n as f64,
+            N::NegInt(n) => n as f64,
+            N::Float(f) => f
+        }
+    }
+
+    /// Return true if this number is an integer stored exactly, rather than
+    /// as an `f64`.
+    pub fn is_integer(&self) -> bool {
+        !matches!(self.0, N::Float(_))
+    }
+
+    /// Return false if this number is infinite or NaN, which JSON text
+    /// can't represent.
+    pub fn is_finite(&self) -> bool {
+        match self.0 {
+            N::Float(f) => f.is_finite(),
+            _ => true
+        }
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        match (self.0, other.0) {
+            (N::UInt(a), N::UInt(b)) => a == b,
+            (N::NegInt(a), N::NegInt(b)) => a == b,
+            (N::Float(a), N::Float(b)) => a == b,
+            (N::UInt(n), N::Float(f)) | (N::Float(f), N::UInt(n)) => exact_u128(f) == Some(n),
+            (N::NegInt(n), N::Float(f)) | (N::Float(f), N::NegInt(n)) => exact_i128(f) == Some(n),
+            (N::UInt(_), N::NegInt(_)) | (N::NegInt(_), N::UInt(_)) => false
+        }
+    }
+}
+
+/// Writes the number as JSON text would, except that infinities and NaN
+/// are written as Rust writes them.
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            N::UInt(n) => write!(f, "{}", n),
+            N::NegInt(n) => write!(f, "{}", n),
+            N::Float(n) => {
+                // Rust never uses an exponent when displaying floats, so
+                // 1e300 would come out as 301 digits. Both forms give the
+                // shortest digits that parse back to the same value.
+                let magnitude = n.abs();
+                if magnitude != 0.0 && magnitude.is_finite() && !(1e-6..1e21).contains(&magnitude) {
+                    write!(f, "{:e}", n)
+                } else {
+                    write!(f, "{}", n)
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            N::Float(n) => write!(f, "{:?}", n),
+            _ => write!(f, "{}", self)
+        }
+    }
+}
+
+macro_rules! impl_from_unsigned_for_number {
+    ( $( $t:ident )* ) => {
+        $(
+            impl From<$t> for Number {
+                fn from(n: $t) -> Number {
+                    Number(N::UInt(n as u128))
+                }
+            }
+        )*
+    };
+}
+
+macro_rules! impl_from_signed_for_number {
+    ( $( $t:ident )* ) => {
+        $(
+            impl From<$t> for Number {
+                fn from(n: $t) -> Number {
+                    if n < 0 {
+                        Number(N::NegInt(n as i128))
+                    } else {
+                        Number(N::UInt(n as u128))
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_from_unsigned_for_number!(u8 u16 u32 u64 u128 usize);
+impl_from_signed_for_number!(i8 i16 i32 i64 i128 isize);
+
+impl From<f32> for Number {
+    fn from(n: f32) -> Number {
+        Number(N::Float(n as f64))
+    }
+}
+
+impl From<f64> for Number {
+    fn from(n: f64) -> Number {
+        Number(N::Float(n))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Number, TWO_127, TWO_128};
+
+    #[test]
+    fn exact_integers() {
+        let big = Number::from(u64::MAX);
+        assert_eq!(big.as_u64(), Some(u64::MAX));
+        assert_eq!(big.as_i64(), None);
+        assert_eq!(big.as_f64(), None);
+        assert_eq!(big.to_f64(), 18446744073709551615.0);
+        assert_eq!(big.to_string(), "18446744073709551615");
+        assert_ne!(big, Number::from(u64::MAX - 1));
+
+        let small = Number::from(i64::MIN);
+        assert_eq!(small.as_i64(), Some(i64::MIN));
+        assert_eq!(small.as_u64(), None);
+        assert_eq!(small.as_f64(), Some(-9223372036854775808.0));
+        assert_eq!(small.to_string(), "-9223372036854775808");
+
+        assert_eq!(Number::from(u128::MAX).to_string(), "340282366920938463463374607431768211455");
+        assert_eq!(Number::from(i128::MIN).to_string(), "-170141183460469231731687303715884105728");
+        assert_eq!(Number::from(u128::MAX).as_f64(), None);
+        assert_eq!(Number::from(1u128 << 127).as_f64(), Some(TWO_127));
+        assert_eq!(Number::from(i128::MIN).as_f64(), Some(-TWO_127));
+
+        assert_eq!(Number::from(2_u64.pow(53)).as_f64(), Some(9007199254740992.0));
+        assert_eq!(Number::from(2_u64.pow(53) + 1).as_f64(), None);
+        assert_eq!(Number::from(-5i8).as_u64(), None);
+        assert_eq!(Number::from(-5i8).as_i64(), Some(-5));
+        assert!(Number::from(7usize).is_integer());
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(Number::from(1.5).as_i64(), None);
+        assert_eq!(Number::from(-3.0).as_i64(), Some(-3));
+        assert_eq!(Number::from(-3.0).as_u64(), None);
+        assert_eq!(Number::from(3.0f32).as_u64(), Some(3));
+        assert_eq!(Number::from(1e19).as_u64(), Some(10_000_000_000_000_000_000));
+        assert_eq!(Number::from(1e19).as_i64(), None);
+        assert_eq!(Number::from(1e300).as_u64(), None);
+        assert_eq!(Number::from(f64::NAN).as_i64(), None);
+        assert_eq!(Number::from(f64::INFINITY).as_u64(), None);
+        assert_eq!(Number::from(0.1).as_f64(), Some(0.1));
+        assert!(!Number::from(2.0).is_integer());
+        assert!(!Number::from(f64::NAN).is_finite());
+    }
+
+    #[test]
+    fn equality() {
+        assert_eq!(Number::from(2), Number::from(2.0));
+        assert_eq!(Number::from(-2), Number::from(-2.0));
+        assert_eq!(Number::from(0u8), Number::from(-0.0));
+        assert_ne!(Number::from(2), Number::from(2.5));
+        assert_ne!(Number::from(-1), Number::from(1u8));
+        assert_ne!(Number::from(f64::NAN), Number::from(f64::NAN));
+
+        // u128::MAX rounds to 2^128 as an f64, but isn't equal to it.
+        assert_ne!(Number::from(u128::MAX), Number::from(TWO_128));
+        assert_ne!(Number::from(2_u64.pow(53) + 1), Number::from(2f64.powi(53)));
+    }
+
+    #[test]
+    fn debug() {
+        assert_eq!(format!("{:?}", Number::from(2)), "2");
+        assert_eq!(format!("{:?}", Number::from(2.0)), "2.0");
+    }
+}
@@ -0,0 +1,581 @@
+// parse.rs
+//
+// Parsing JSON text into `Json` values, as specified by RFC 8259.
+
+use crate::{Json, Map, Number};
+use std::fmt;
+use std::str::FromStr;
+
+/// How deeply arrays and objects may nest when parsing with `FromStr`.
+/// Each level of nesting costs a stack frame, so without a limit, a short
+/// run of `[` characters could overflow the stack.
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// An error parsing JSON text: what went wrong, and where in the input we
+/// noticed. `offset` is in bytes from the start of the text; `line` and
+/// `column` count from 1, and columns count characters, not bytes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at line {}, column {}", self.message, self.line, self.column)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl FromStr for Json {
+    type Err = ParseError;
+
+    fn from_str(text: &str) -> Result<Json, ParseError> {
+        Json::parse_with_max_depth(text, DEFAULT_MAX_DEPTH)
+    }
+}
+
+impl Json {
+    /// Parse `text`, allowing arrays and objects to nest at most
+    /// `max_depth` levels deep. `str::parse` uses `DEFAULT_MAX_DEPTH`.
+    pub fn parse_with_max_depth(text: &str, max_depth: usize) -> Result<Json, ParseError> {
+        let mut parser = Parser { text, pos: 0, depth: 0, max_depth };
+        let value = parser.value()?;
+        parser.skip_whitespace();
+        if parser.pos < text.len() {
+            return Err(parser.error("trailing characters after JSON value"));
+        }
+        Ok(value)
+    }
+}
+
+struct Parser<'t> {
+    text: &'t str,
+    pos: usize,
+
+    /// How many arrays and objects enclose the current position.
+    depth: usize,
+    max_depth: usize
+}
+
+impl<'t> Parser<'t> {
+    fn error(&self, message: &str) -> ParseError {
+        let before = &self.text[..self.pos];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        ParseError {
+            message: message.to_string(),
+            offset: self.pos,
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1
+        }
+    }
+
+    /// Note that we're entering an array or object, checking the nesting
+    /// limit. Every successful call must be matched by a call to `leave`.
+    fn enter(&mut self) -> Result<(), ParseError> {
+        if self.depth >= self.max_depth {
+            return Err(self.error("arrays and objects nested too deeply"));
+        }
+        self.depth += 1;
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn leave<T>(&mut self, value: T) -> Result<T, ParseError> {
+        self.depth -= 1;
+        self.pos += 1;
+        Ok(value)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8, message: &str) -> Result<(), ParseError> {
+        self.skip_whitespace();
+        if self.peek() != Some(byte) {
+            return Err(self.error(message));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn literal(&mut self, word: &str, value: Json) -> Result<Json, ParseError> {
+        if !self.text[self.pos..].starts_with(word) {
+            return Err(self.error("expected a JSON value"));
+        }
+        self.pos += word.len();
+        Ok(value)
+    }
+
+    fn value(&mut self) -> Result<Json, ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            None => Err(self.error("unexpected end of input")),
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b't') => self.literal("true", Json::Boolean(true)),
+            Some(b'f') => self.literal("false", Json::Boolean(false)),
+            Some(b'"') => Ok(Json::String(self.string()?)),
+            Some(b'[') => self.array(),
+            Some(b'{') => self.object(),
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("expected a JSON value"))
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, ParseError> {
+        self.enter()?;
+        let mut elements = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            return self.leave(Json::Array(elements));
+        }
+        loop {
+            elements.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => return self.leave(Json::Array(elements)),
+                _ => return Err(self.error("expected ',' or ']' in array"))
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, ParseError> {
+        self.enter()?;
+        let mut fields = Map::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            return self.leave(Json::Object(Box::new(fields)));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected a string key in object"));
+            }
+            let key = self.string()?;
+            self.expect(b':', "expected ':' after object key")?;
+            // RFC 8259 leaves duplicate keys up to us: the last one wins.
+            let value = self.value()?;
+            fields.insert(key, value);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => return self.leave(Json::Object(Box::new(fields))),
+                _ => return Err(self.error("expected ',' or '}' in object"))
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, ParseError> {
+        let start = self.pos;
+        let bytes = self.text.as_bytes();
+        let digits = |pos: &mut usize| {
+            let begin = *pos;
+            while *pos < bytes.len() && bytes[*pos].is_ascii_digit() {
+                *pos += 1;
+            }
+            *pos - begin
+        };
+
+        let mut pos = self.pos;
+        if bytes[pos] == b'-' {
+            pos += 1;
+        }
+        let int_digits = digits(&mut pos);
+        if int_digits == 0 || (int_digits > 1 && bytes[pos - int_digits] == b'0') {
+            self.pos = pos;
+            return Err(self.error("invalid number"));
+        }
+        let integer = pos == bytes.len() || !matches!(bytes[pos], b'.' | b'e' | b'E');
+        if pos < bytes.len() && bytes[pos] == b'.' {
+            pos += 1;
+            if digits(&mut pos) == 0 {
+                self.pos = pos;
+                return Err(self.error("expected digits after decimal point"));
+            }
+        }
+        if pos < bytes.len() && (bytes[pos] == b'e' || bytes[pos] == b'E') {
+            pos += 1;
+            if pos < bytes.len() && (bytes[pos] == b'+' || bytes[pos] == b'-') {
+                pos += 1;
+            }
+            if digits(&mut pos) == 0 {
+                self.pos = pos;
+                return Err(self.error("expected digits in exponent"));
+            }
+        }
+
+        // Keep integers exact, if they fit. Negative zero has to be a float.
+        let text = &self.text[start..pos];
+        if integer {
+            let exact = match text.parse::<i128>() {
+                Ok(0) if text.starts_with('-') => None,
+                Ok(n) => Some(Number::from(n)),
+                Err(_) => text.parse::<u128>().ok().map(Number::from)
+            };
+            if let Some(n) = exact {
+                self.pos = pos;
+                return Ok(Json::Number(n));
+            }
+        }
+
+        // The grammar above is a subset of what Rust's float parser accepts.
+        let n: f64 = text.parse().unwrap();
+        if n.is_infinite() {
+            self.pos = start;
+            return Err(self.error("number out of range"));
+        }
+        self.pos = pos;
+        Ok(Json::Number(Number::from(n)))
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            let rest = &self.text[self.pos..];
+            let c = match rest.chars().next() {
+                None => return Err(self.error("unterminated string")),
+                Some(c) => c
+            };
+            match c {
+                '"' => {
+                    self.pos += 1;
+                    return Ok(s);
+                }
+                '\\' => {
+                    self.pos += 1;
+                    s.push(self.escape()?);
+                }
+                '\u{0}'..='\u{1f}' => return Err(self.error("control character in string")),
+                c => {
+                    s.push(c);
+                    self.pos += c.len_utf8();
+                }
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, ParseError> {
+        let c = match self.peek() {
+            Some(b'"') => '"',
+            Some(b'\\') => '\\',
+            Some(b'/') => '/',
+            Some(b'b') => '\u{8}',
+            Some(b'f') => '\u{c}',
+            Some(b'n') => '\n',
+            Some(b'r') => '\r',
+            Some(b't') => '\t',
+            Some(b'u') => return self.unicode_escape(),
+            _ => return Err(self.error("invalid escape in string"))
+        };
+        self.pos += 1;
+        Ok(c)
+    }
+
+    /// Decode a `\uXXXX` escape, starting at the `u`. Characters outside
+    /// the Basic Multilingual Plane are written as a UTF-16 surrogate pair,
+    /// `\uD83D\uDE00`; each half alone is an error.
+    fn unicode_escape(&mut self) -> Result<char, ParseError> {
+        let high = self.hex4()?;
+        let code = match high {
+            0xD800..=0xDBFF => {
+                if !self.text[self.pos..].starts_with("\\u") {
+                    return Err(self.error("unpaired surrogate in \\u escape"));
+                }
+                self.pos += 1;
+                let low = self.hex4()?;
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return Err(self.error("unpaired surrogate in \\u escape"));
+                }
+                0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+            }
+            0xDC00..=0xDFFF => return Err(self.error("unpaired surrogate in \\u escape")),
+            code => code
+        };
+        Ok(char::from_u32(code).unwrap())
+    }
+
+    /// Read a `u` and four hex digits, and return their value.
+    fn hex4(&mut self) -> Result<u32, ParseError> {
+        let hex = self.text.get(self.pos + 1..self.pos + 5)
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .ok_or_else(|| self.error("expected four hex digits after \\u"))?;
+        let code = u32::from_str_radix(hex, 16).unwrap();
+        self.pos += 5;
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Json;
+
+    #[test]
+    fn parse_values() {
+        let parsed: Json = r#" {
+            "name": "Jim Blandy",
+            "class_of": 1926,
+            "tags": [true, false, null, -1.5e2, "tab\there é"],
+            "empty": {}
+        } "#.parse().unwrap();
+        assert_eq!(parsed, json!({
+            "name": "Jim Blandy",
+            "class_of": 1926,
+            "tags": [true, false, null, (-150.0), "tab\there \u{e9}"],
+            "empty": {}
+        }));
+        assert_eq!("[]".parse(), Ok(Json::Array(vec![])));
+    }
+
+    #[test]
+    fn parse_errors() {
+        let offset = |text: &str| text.parse::<Json>().unwrap_err().offset;
+        assert_eq!(offset(""), 0);
+        assert_eq!(offset("[1, 2"), 5);
+        assert_eq!(offset("[1, 2] 3"), 7);
+        assert_eq!(offset("{\"a\" 1}"), 5);
+        assert_eq!(offset("01"), 2);
+        assert_eq!(offset("\"abc"), 4);
+        assert_eq!(offset("tru"), 0);
+        assert_eq!("[1,]".parse::<Json>().unwrap_err().to_string(),
+                   "expected a JSON value at line 1, column 4");
+    }
+
+    #[test]
+    fn error_positions() {
+        let position = |text: &str| {
+            let error = text.parse::<Json>().unwrap_err();
+            (error.line, error.column)
+        };
+        assert_eq!(position(""), (1, 1));
+        assert_eq!(position("[\n  1,\n  2\n  3\n]"), (4, 3));
+        assert_eq!(position("{\r\n\"a\": x}"), (2, 6));
+        // Columns count characters, not bytes.
+        assert_eq!(position("[\"\u{e9}t\u{e9}\", @]"), (1, 9));
+        assert_eq!(position("\n\n"), (3, 1));
+    }
+
+    #[test]
+    fn nesting_limits() {
+        let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+        assert!(nested(crate::DEFAULT_MAX_DEPTH).parse::<Json>().is_ok());
+
+        let error = nested(crate::DEFAULT_MAX_DEPTH + 1).parse::<Json>().unwrap_err();
+        assert_eq!(error.message, "arrays and objects nested too deeply");
+        assert_eq!(error.offset, crate::DEFAULT_MAX_DEPTH);
+
+        // Far too deep to parse recursively, if we didn't stop early.
+        assert!("[".repeat(1_000_000).parse::<Json>().is_err());
+
+        assert!(Json::parse_with_max_depth(r#"{"a": [{}]}"#, 3).is_ok());
+        assert!(Json::parse_with_max_depth(r#"{"a": [{}]}"#, 2).is_err());
+        assert_eq!(Json::parse_with_max_depth("7", 0), Ok(json!(7)));
+        assert!(Json::parse_with_max_depth("[]", 0).is_err());
+    }
+
+    #[test]
+    fn string_escapes() {
+        let string = |text: &str| match text.parse::<Json>() {
+            Ok(Json::String(s)) => s,
+            other => panic!("{:?} parsed as {:?}", text, other)
+        };
+        assert_eq!(string(r#""\"\\\/\b\f\n\r\t""#), "\"\\/\u{8}\u{c}\n\r\t");
+        assert_eq!(string(r#""\u0041\u00e9\u20AC""#), "A\u{e9}\u{20ac}");
+        assert_eq!(string(r#""\u0000""#), "\u{0}");
+        assert_eq!(string(r#""\uD83D\uDE00""#), "\u{1f600}");
+        assert_eq!(string(r#""\udbff\udfff""#), "\u{10ffff}");
+        assert_eq!(string("\"\u{1f600}\""), "\u{1f600}");
+
+        let message = |text: &str| text.parse::<Json>().unwrap_err().message;
+        assert_eq!(message(r#""\uD83D""#), "unpaired surrogate in \\u escape");
+        assert_eq!(message(r#""\uD83Dx""#), "unpaired surrogate in \\u escape");
+        assert_eq!(message(r#""\uD83D\n""#), "unpaired surrogate in \\u escape");
+        assert_eq!(message(r#""\uD83D\uD83D""#), "unpaired surrogate in \\u escape");
+        assert_eq!(message(r#""\uDE00""#), "unpaired surrogate in \\u escape");
+        assert_eq!(message(r#""\uD83D\u12""#), "expected four hex digits after \\u");
+        assert_eq!(message(r#""\x41""#), "invalid escape in string");
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        assert_eq!(r#"{"a": 1, "a": 2}"#.parse(), Ok(json!({ "a": 2 })));
+    }
+
+    #[test]
+    fn exact_numbers() {
+        let number = |text: &str| match text.parse::<Json>() {
+            Ok(Json::Number(n)) => n,
+            other => panic!("{:?} parsed as {:?}", text, other)
+        };
+        assert_eq!(number("18446744073709551615").as_u64(), Some(u64::MAX));
+        assert_eq!(number("-9223372036854775808").as_i64(), Some(i64::MIN));
+        assert_eq!(number("9007199254740993").as_f64(), None);
+        assert_eq!(number("340282366920938463463374607431768211455"), u128::MAX.into());
+        assert_eq!(number("-170141183460469231731687303715884105728"), i128::MIN.into());
+        assert!(number("12").is_integer());
+        assert!(!number("12.0").is_integer());
+        assert!(!number("12e0").is_integer());
+        assert!(!number("-0").is_integer());
+        assert_eq!(number("-0").to_f64().to_bits(), (-0.0f64).to_bits());
+
+        // Integers too big to store exactly become floats.
+        let huge = number("340282366920938463463374607431768211456");
+        assert!(!huge.is_integer());
+        assert_eq!(huge.to_f64(), 2f64.powi(128));
+    }
+
+    #[test]
+    fn key_order() {
+        let value: Json = r#"{"z": 1, "a": 2, "m": {"y": 3, "b": 4}, "a": 5}"#.parse().unwrap();
+        match value {
+            Json::Object(fields) => {
+                assert_eq!(fields.keys().collect::<Vec<_>>(), ["z", "a", "m"]);
+                assert_eq!(fields.get("a"), Some(&json!(5)));
+            }
+            other => panic!("parsed as {:?}", other)
+        }
+    }
+
+    /// Documents every conforming parser must accept, in the style of the
+    /// `y_` cases from JSONTestSuite.
+    const ACCEPT: &[&str] = &[
+        "[]",
+        "{}",
+        "[[]   ]",
+        "[null, 1, \"1\", {}]",
+        "[1\n]",
+        " [1] ",
+        "\t\r\n[\t\r\n]\t\r\n",
+        "[\"\"]",
+        "[false]",
+        "[true]",
+        "null",
+        "2",
+        "-0",
+        "[-0.0]",
+        "[-1]",
+        "[0e+1]",
+        "[0e1]",
+        "[1E22]",
+        "[1E-2]",
+        "[1E+2]",
+        "[1e-2]",
+        "[123e45]",
+        "[123.456e78]",
+        "[-123]",
+        "[1.5e-300]",
+        "\"asd\"",
+        "{\"asd\":\"sdf\", \"dfg\":\"fgh\"}",
+        "{\"\":0}",
+        "{\"a\":[]}",
+        "{\"x\":[{\"id\": \"xxxxxx\"}], \"id\": \"xxxxxx\"}",
+        "[\"\\u0060\\u012a\\u12AB\"]",
+        "[\"\\uD801\\udc37\"]",
+        "[\"\\\"\\\\\\/\\b\\f\\n\\r\\t\"]",
+        "[\"a\u{7f}a\"]",
+        "[\"\u{2028}\u{2029}\"]",
+        "[\"\u{10ffff}\"]",
+        "[\"new\\u000Aline\"]",
+        "[\"\\uFFFF\"]",
+        "{\"a\":\"b\",\"a\":\"c\"}",
+    ];
+
+    /// Documents every conforming parser must reject, in the style of the
+    /// `n_` cases from JSONTestSuite.
+    const REJECT: &[&str] = &[
+        "",
+        " ",
+        "[",
+        "]",
+        "[1",
+        "[1,",
+        "[1,]",
+        "[,1]",
+        "[1,,2]",
+        "[1 true]",
+        "[\"a\",\n4\n,1,",
+        "{",
+        "{\"a\"",
+        "{\"a\":",
+        "{\"a\":1,}",
+        "{\"a\" 1}",
+        "{1:1}",
+        "{'a':1}",
+        "{a:1}",
+        "{\"a\":1 \"b\":2}",
+        "[1]]",
+        "[1] x",
+        "{}}",
+        "[]\u{0}",
+        "[nul]",
+        "[True]",
+        "[NaN]",
+        "[Infinity]",
+        "[-Infinity]",
+        "[+1]",
+        "[01]",
+        "[-01]",
+        "[.1]",
+        "[1.]",
+        "[1.e1]",
+        "[1e]",
+        "[1e+]",
+        "[0x1]",
+        "[- 1]",
+        "[-]",
+        "['single quote']",
+        "[\"unterminated]",
+        "[\"tab\tinside\"]",
+        "[\"new\nline\"]",
+        "[\"\\a\"]",
+        "[\"\\u00\"]",
+        "[\"\\u00G0\"]",
+        "[\"\\\"]",
+        "[\u{a0}1]",
+        "/* comment */ []",
+    ];
+
+    /// Documents RFC 8259 leaves to the parser, in the style of the `i_`
+    /// cases from JSONTestSuite, with whether this one accepts them.
+    const IMPLEMENTATION_DEFINED: &[(&str, bool)] = &[
+        // Integers too big for 64 bits, and floats that round to zero, are
+        // accepted; floats that overflow aren't.
+        ("[100000000000000000000]", true),
+        ("[123e-10000000]", true),
+        ("[1e999]", false),
+        ("[-1e999]", false),
+        // We don't skip a byte order mark, or accept unpaired surrogates.
+        ("\u{feff}{}", false),
+        ("[\"\\uD800\"]", false),
+        ("[\"\\uDFFF\"]", false),
+        ("[\"\\uD800\\u0041\"]", false),
+    ];
+
+    #[test]
+    fn test_suite() {
+        for text in ACCEPT {
+            if let Err(error) = text.parse::<Json>() {
+                panic!("rejected {:?}: {}", text, error);
+            }
+        }
+        for text in REJECT {
+            if let Ok(value) = text.parse::<Json>() {
+                panic!("accepted {:?} as {:?}", text, value);
+            }
+        }
+        for &(text, accepted) in IMPLEMENTATION_DEFINED {
+            assert_eq!(text.parse::<Json>().is_ok(), accepted, "{:?}", text);
+        }
+    }
+}
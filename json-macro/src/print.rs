@@ -0,0 +1,264 @@
+// print.rs
+//
+// Writing `Json` values as JSON text.
+//
+// `Display` writes compact text, with no whitespace at all; the alternate
+// form, `{:#}`, writes it pretty-printed with the default settings. Either
+// way, parsing the output gives back the value written, except that JSON
+// has no way to write infinities or NaN, so those are written as `null`.
+
+use crate::{Json, Number};
+use std::fmt::{self, Write};
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "{}", self.pretty())
+        } else {
+            Printer { out: f, indent: None, sort_keys: false, ascii_only: false }.value(self, 0)
+        }
+    }
+}
+
+impl Json {
+    /// Return a value that displays this one as indented JSON text, one
+    /// array element or object field per line. Call its methods to change
+    /// the settings.
+    ///
+    ///     # #[macro_use] extern crate json_macro;
+    ///     # fn main() {
+    ///     let value = json!({ "name": "caf\u{e9}" });
+    ///     assert_eq!(value.pretty().indent(4).ascii_only(true).to_string(),
+    ///                "{\n    \"name\": \"caf\\u00e9\"\n}");
+    ///     # }
+    pub fn pretty(&self) -> Pretty<'_> {
+        Pretty { json: self, indent: 2, sort_keys: false, ascii_only: false }
+    }
+}
+
+/// A `Json` value to be displayed pretty-printed. See `Json::pretty`.
+#[derive(Clone, Copy, Debug)]
+pub struct Pretty<'j> {
+    json: &'j Json,
+    indent: usize,
+    sort_keys: bool,
+    ascii_only: bool
+}
+
+impl<'j> Pretty<'j> {
+    /// Indent each level of nesting by `width` spaces. The default is 2.
+    pub fn indent(mut self, width: usize) -> Pretty<'j> {
+        self.indent = width;
+        self
+    }
+
+    /// Write object fields sorted by key, rather than in the object's own
+    /// order. Off by default.
+    pub fn sort_keys(mut self, sort_keys: bool) -> Pretty<'j> {
+        self.sort_keys = sort_keys;
+        self
+    }
+
+    /// Write every non-ASCII character in strings as a `\u` escape, so the
+    /// output is plain ASCII. Off by default.
+    pub fn ascii_only(mut self, ascii_only: bool) -> Pretty<'j> {
+        self.ascii_only = ascii_only;
+        self
+    }
+}
+
+impl<'j> fmt::Display for Pretty<'j> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Printer {
+            out: f,
+            indent: Some(self.indent),
+            sort_keys: self.sort_keys,
+            ascii_only: self.ascii_only
+        }.value(self.json, 0)
+    }
+}
+
+struct Printer<'p, 'f> {
+    out: &'p mut fmt::Formatter<'f>,
+
+    /// How many spaces to indent each level by, or `None` to write
+    /// everything on one line with no whitespace.
+    indent: Option<usize>,
+    sort_keys: bool,
+    ascii_only: bool
+}
+
+impl<'p, 'f> Printer<'p, 'f> {
+    fn value(&mut self, json: &Json, depth: usize) -> fmt::Result {
+        match json {
+            Json::Null => self.out.write_str("null"),
+            Json::Boolean(b) => write!(self.out, "{}", b),
+            Json::Number(n) => self.number(n),
+            Json::String(s) => self.string(s),
+            Json::Array(elements) => {
+                self.out.write_char('[')?;
+                for (i, element) in elements.iter().enumerate() {
+                    self.separator(i, depth + 1)?;
+                    self.value(element, depth + 1)?;
+                }
+                self.close(!elements.is_empty(), depth, ']')
+            }
+            Json::Object(fields) => {
+                let mut fields: Vec<_> = fields.iter().collect();
+                if self.sort_keys {
+                    fields.sort_by(|a, b| a.0.cmp(b.0));
+                }
+                self.out.write_char('{')?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    self.separator(i, depth + 1)?;
+                    self.string(key)?;
+                    self.out.write_str(if self.indent.is_some() { ": " } else { ":" })?;
+                    self.value(value, depth + 1)?;
+                }
+                self.close(!fields.is_empty(), depth, '}')
+            }
+        }
+    }
+
+    /// Write what goes before the `i`'th element of an array or object.
+    fn separator(&mut self, i: usize, depth: usize) -> fmt::Result {
+        if i > 0 {
+            self.out.write_char(',')?;
+        }
+        self.newline(depth)
+    }
+
+    fn close(&mut self, nonempty: bool, depth: usize, bracket: char) -> fmt::Result {
+        if nonempty {
+            self.newline(depth)?;
+        }
+        self.out.write_char(bracket)
+    }
+
+    fn newline(&mut self, depth: usize) -> fmt::Result {
+        if let Some(width) = self.indent {
+            write!(self.out, "\n{:1$}", "", width * depth)?;
+        }
+        Ok(())
+    }
+
+    fn number(&mut self, n: &Number) -> fmt::Result {
+        if n.is_finite() {
+            write!(self.out, "{}", n)
+        } else {
+            self.out.write_str("null")
+        }
+    }
+
+    fn string(&mut self, s: &str) -> fmt::Result {
+        self.out.write_char('"')?;
+        for c in s.chars() {
+            match c {
+                '"' => self.out.write_str("\\\"")?,
+                '\\' => self.out.write_str("\\\\")?,
+                '\n' => self.out.write_str("\\n")?,
+                '\r' => self.out.write_str("\\r")?,
+                '\t' => self.out.write_str("\\t")?,
+                '\u{8}' => self.out.write_str("\\b")?,
+                '\u{c}' => self.out.write_str("\\f")?,
+                '\u{0}'..='\u{1f}' => write!(self.out, "\\u{:04x}", c as u32)?,
+                c if self.ascii_only && !c.is_ascii() => {
+                    let mut units = [0; 2];
+                    for unit in c.encode_utf16(&mut units) {
+                        write!(self.out, "\\u{:04x}", unit)?;
+                    }
+                }
+                c => self.out.write_char(c)?
+            }
+        }
+        self.out.write_char('"')
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Json;
+
+    #[test]
+    fn compact() {
+        assert_eq!(json!(null).to_string(), "null");
+        assert_eq!(json!([true, false, 1, (-2.5), "x"]).to_string(),
+                   r#"[true,false,1,-2.5,"x"]"#);
+        assert_eq!(json!({ "a": [[], {}] }).to_string(), r#"{"a":[[],{}]}"#);
+        // Fields come out in the order they went in, and integers exactly.
+        let text = r#"{"z":18446744073709551615,"a":-170141183460469231731687303715884105728,"m":0.5}"#;
+        assert_eq!(text.parse::<Json>().unwrap().to_string(), text);
+        assert_eq!(json!("q\" b\\ t\t n\u{0} d\u{7f} \u{e9}").to_string(),
+                   "\"q\\\" b\\\\ t\\t n\\u0000 d\u{7f} \u{e9}\"");
+    }
+
+    #[test]
+    fn pretty() {
+        let value = json!({ "b": [1, { "c": null }, []], "a": {} });
+        assert_eq!(value.pretty().sort_keys(true).to_string(), "\
+{
+  \"a\": {},
+  \"b\": [
+    1,
+    {
+      \"c\": null
+    },
+    []
+  ]
+}");
+        assert_eq!(format!("{:#}", json!([1])), "[\n  1\n]");
+        assert_eq!(json!([[1]]).pretty().indent(4).to_string(), "[\n    [\n        1\n    ]\n]");
+        assert_eq!(json!([1, 2]).pretty().indent(0).to_string(), "[\n1,\n2\n]");
+        assert_eq!(json!(7).pretty().to_string(), "7");
+    }
+
+    #[test]
+    fn ascii_only() {
+        let value = json!(["caf\u{e9} \u{1f600} \u{7f}"]);
+        assert_eq!(value.pretty().ascii_only(true).to_string(),
+                   "[\n  \"caf\\u00e9 \\ud83d\\ude00 \u{7f}\"\n]");
+        assert_eq!(value.pretty().to_string(), "[\n  \"caf\u{e9} \u{1f600} \u{7f}\"\n]");
+    }
+
+    #[test]
+    fn numbers() {
+        let text = |n: f64| Json::from(n).to_string();
+        assert_eq!(text(0.0), "0");
+        assert_eq!(text(-0.0), "-0");
+        assert_eq!(text(1926.0), "1926");
+        assert_eq!(text(0.1), "0.1");
+        assert_eq!(text(1e20), "100000000000000000000");
+        assert_eq!(text(1e21), "1e21");
+        assert_eq!(text(-1.5e300), "-1.5e300");
+        assert_eq!(text(0.000001), "0.000001");
+        assert_eq!(text(1.25e-7), "1.25e-7");
+        assert_eq!(text(f64::MIN_POSITIVE), "2.2250738585072014e-308");
+
+        // JSON can't represent these.
+        assert_eq!(text(f64::NAN), "null");
+        assert_eq!(text(f64::INFINITY), "null");
+        assert_eq!(json!([(f64::NEG_INFINITY)]).pretty().to_string(), "[\n  null\n]");
+    }
+
+    #[test]
+    fn round_trips() {
+        let documents = [
+            "null",
+            "[true, false, 0, -0, 1.5, -1e-300, 1.7976931348623157e308, 5e-324, 123456789012]",
+            r#""\"\\\/\b\f\n\r\t\u0000\u001f\u007f 😀 é""#,
+            r#"{"a": {"b": [[{}], {"c": []}]}, "": "", "x y": [null]}"#,
+            "[0.1, 0.2, 0.30000000000000004, 3.141592653589793, 2.718281828459045e-10]",
+        ];
+        for text in &documents {
+            let value: Json = text.parse().unwrap();
+            let printed = [
+                value.to_string(),
+                format!("{:#}", value),
+                value.pretty().indent(3).sort_keys(true).ascii_only(true).to_string(),
+            ];
+            for printed in &printed {
+                assert_eq!(printed.parse::<Json>().as_ref(), Ok(&value), "{}", printed);
+            }
+        }
+    }
+}
@@ -1,6 +1,8 @@
 #![allow(dead_code)]
 
-mod ref_with_flag {
+pub mod tagged_ptr;
+
+pub mod ref_with_flag {
     use std::marker::PhantomData;
     use std::mem::align_of;
 
@@ -16,8 +18,13 @@ mod ref_with_flag {
     }
 
     impl<'a, T: 'a> RefWithFlag<'a, T> {
-        pub fn new(ptr: &'a T, flag: bool) -> RefWithFlag<T> {
-            assert!(align_of::<T>() % 2 == 0);
+        /// Evaluating this fails to compile if `T`'s alignment leaves no
+        /// room for the flag.
+        const FLAG_FITS: () = assert!(align_of::<T>() >= 2,
+                                      "RefWithFlag requires at least two-byte alignment");
+
+        pub fn new(ptr: &'a T, flag: bool) -> RefWithFlag<'a, T> {
+            let () = Self::FLAG_FITS;
             RefWithFlag {
                 ptr_and_bit: ptr as *const T as usize | flag as usize,
                 behaves_like: PhantomData
@@ -35,6 +42,113 @@ mod ref_with_flag {
             self.ptr_and_bit & 1 != 0
         }
     }
+
+    // A derived `Clone` would needlessly require `T: Clone`.
+    impl<'a, T> Clone for RefWithFlag<'a, T> {
+        fn clone(&self) -> Self {
+            *self
+        }
+    }
+
+    impl<'a, T> Copy for RefWithFlag<'a, T> {}
+
+    impl<'a, T: std::fmt::Debug> std::fmt::Debug for RefWithFlag<'a, T> {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            f.debug_struct("RefWithFlag")
+                .field("target", self.get_ref())
+                .field("flag", &self.get_flag())
+                .finish()
+        }
+    }
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A `RefWithFlag` that can be updated atomically, pointer and flag
+    /// together. This is just the thing for the "marked" next-pointers of a
+    /// lock-free linked list, where a node is logically deleted by setting
+    /// the flag on its `next` link, and a compare-and-swap must fail if the
+    /// link was marked in the meantime.
+    pub struct AtomicRefWithFlag<'a, T> {
+        ptr_and_bit: AtomicUsize,
+        behaves_like: PhantomData<&'a T>
+    }
+
+    impl<'a, T: 'a> AtomicRefWithFlag<'a, T> {
+        pub fn new(ptr: &'a T, flag: bool) -> AtomicRefWithFlag<'a, T> {
+            AtomicRefWithFlag::from(RefWithFlag::new(ptr, flag))
+        }
+
+        fn unpack(ptr_and_bit: usize) -> RefWithFlag<'a, T> {
+            RefWithFlag { ptr_and_bit, behaves_like: PhantomData }
+        }
+
+        pub fn load(&self, order: Ordering) -> RefWithFlag<'a, T> {
+            Self::unpack(self.ptr_and_bit.load(order))
+        }
+
+        pub fn store(&self, new: RefWithFlag<'a, T>, order: Ordering) {
+            self.ptr_and_bit.store(new.ptr_and_bit, order)
+        }
+
+        pub fn swap(&self, new: RefWithFlag<'a, T>, order: Ordering) -> RefWithFlag<'a, T> {
+            Self::unpack(self.ptr_and_bit.swap(new.ptr_and_bit, order))
+        }
+
+        /// Store `new` if the current pointer and flag are both exactly
+        /// those of `current`. Pointers are compared by address, not by
+        /// value. Returns the previous value, as `Ok` if the exchange took
+        /// place, or `Err` if it didn't.
+        pub fn compare_exchange(&self,
+                                current: RefWithFlag<'a, T>,
+                                new: RefWithFlag<'a, T>,
+                                success: Ordering,
+                                failure: Ordering)
+                                -> Result<RefWithFlag<'a, T>, RefWithFlag<'a, T>>
+        {
+            self.ptr_and_bit
+                .compare_exchange(current.ptr_and_bit, new.ptr_and_bit, success, failure)
+                .map(Self::unpack)
+                .map_err(Self::unpack)
+        }
+
+        /// Like `compare_exchange`, but allowed to fail spuriously, which
+        /// can be cheaper inside a retry loop.
+        pub fn compare_exchange_weak(&self,
+                                     current: RefWithFlag<'a, T>,
+                                     new: RefWithFlag<'a, T>,
+                                     success: Ordering,
+                                     failure: Ordering)
+                                     -> Result<RefWithFlag<'a, T>, RefWithFlag<'a, T>>
+        {
+            self.ptr_and_bit
+                .compare_exchange_weak(current.ptr_and_bit, new.ptr_and_bit, success, failure)
+                .map(Self::unpack)
+                .map_err(Self::unpack)
+        }
+
+        /// Set the flag to `flag`, leaving the pointer alone, and return the
+        /// previous value.
+        pub fn fetch_set_flag(&self, flag: bool, order: Ordering) -> RefWithFlag<'a, T> {
+            Self::unpack(if flag {
+                self.ptr_and_bit.fetch_or(1, order)
+            } else {
+                self.ptr_and_bit.fetch_and(!1, order)
+            })
+        }
+
+        pub fn into_inner(self) -> RefWithFlag<'a, T> {
+            Self::unpack(self.ptr_and_bit.into_inner())
+        }
+    }
+
+    impl<'a, T: 'a> From<RefWithFlag<'a, T>> for AtomicRefWithFlag<'a, T> {
+        fn from(r: RefWithFlag<'a, T>) -> AtomicRefWithFlag<'a, T> {
+            AtomicRefWithFlag {
+                ptr_and_bit: AtomicUsize::new(r.ptr_and_bit),
+                behaves_like: PhantomData
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -51,3 +165,130 @@ mod ref_with_flag_tests {
         assert_eq!(flagged.get_flag(), true);
     }
 }
+
+#[cfg(test)]
+mod atomic_ref_with_flag_tests {
+    use super::ref_with_flag::{AtomicRefWithFlag, RefWithFlag};
+    use std::sync::atomic::Ordering::{Acquire, Relaxed, Release, SeqCst};
+    use std::thread;
+
+    #[test]
+    fn single_threaded() {
+        let (a, b) = (String::from("a"), String::from("b"));
+        let atomic = AtomicRefWithFlag::new(&a, false);
+
+        let loaded = atomic.load(SeqCst);
+        assert!(std::ptr::eq(loaded.get_ref(), &a));
+        assert!(!loaded.get_flag());
+
+        atomic.store(RefWithFlag::new(&b, true), SeqCst);
+        assert_eq!(atomic.load(SeqCst).get_ref(), "b");
+        assert!(atomic.load(SeqCst).get_flag());
+
+        let old = atomic.fetch_set_flag(false, SeqCst);
+        assert!(old.get_flag());
+        assert!(!atomic.load(SeqCst).get_flag());
+        assert_eq!(atomic.load(SeqCst).get_ref(), "b");
+
+        let old = atomic.swap(RefWithFlag::new(&a, true), SeqCst);
+        assert_eq!((old.get_ref().as_str(), old.get_flag()), ("b", false));
+        assert_eq!(atomic.into_inner().get_ref(), "a");
+    }
+
+    #[test]
+    fn compare_exchange_checks_pointer_and_flag() {
+        let (a, b) = (5_u64, 5_u64);
+        let atomic = AtomicRefWithFlag::new(&a, false);
+
+        // Same value, different address: no exchange.
+        let result = atomic.compare_exchange(RefWithFlag::new(&b, false),
+                                             RefWithFlag::new(&b, true),
+                                             SeqCst, SeqCst);
+        assert!(std::ptr::eq(result.unwrap_err().get_ref(), &a));
+
+        // Same address, different flag: no exchange.
+        assert!(atomic.compare_exchange(RefWithFlag::new(&a, true),
+                                        RefWithFlag::new(&b, true),
+                                        SeqCst, SeqCst).is_err());
+
+        // Both match.
+        let previous = atomic.compare_exchange(RefWithFlag::new(&a, false),
+                                               RefWithFlag::new(&b, true),
+                                               SeqCst, SeqCst).unwrap();
+        assert!(std::ptr::eq(previous.get_ref(), &a));
+        assert!(std::ptr::eq(atomic.load(SeqCst).get_ref(), &b));
+        assert!(atomic.load(SeqCst).get_flag());
+    }
+
+    #[test]
+    fn only_one_thread_sets_the_flag() {
+        const THREADS: usize = 8;
+
+        for _ in 0..200 {
+            let node = 0_u32;
+            let link = AtomicRefWithFlag::new(&node, false);
+            let winners: usize = thread::scope(|s| {
+                let handles: Vec<_> = (0..THREADS)
+                    .map(|_| s.spawn(|| !link.fetch_set_flag(true, SeqCst).get_flag()))
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap() as usize).sum()
+            });
+            assert_eq!(winners, 1);
+            assert!(std::ptr::eq(link.load(SeqCst).get_ref(), &node));
+        }
+    }
+
+    /// Threads race to advance a link along an array of nodes with CAS
+    /// loops, while others mark and unmark it. An advance must never
+    /// succeed against a marked link, and no advance may be lost.
+    #[test]
+    fn marking_races_with_compare_exchange() {
+        const ADVANCERS: usize = 4;
+        const STEPS: usize = 500;
+
+        let nodes: Vec<u64> = (0..(ADVANCERS * STEPS + 1) as u64).collect();
+        let link = AtomicRefWithFlag::new(&nodes[0], false);
+
+        thread::scope(|s| {
+            for _ in 0..ADVANCERS {
+                s.spawn(|| {
+                    for _ in 0..STEPS {
+                        let mut current = link.load(Acquire);
+                        loop {
+                            if current.get_flag() {
+                                // Marked; wait for the marker to let go.
+                                current = link.load(Acquire);
+                                continue;
+                            }
+                            let next = &nodes[*current.get_ref() as usize + 1];
+                            match link.compare_exchange_weak(current,
+                                                             RefWithFlag::new(next, false),
+                                                             Release, Acquire) {
+                                Ok(previous) => {
+                                    assert!(!previous.get_flag());
+                                    break;
+                                }
+                                Err(actual) => current = actual,
+                            }
+                        }
+                    }
+                });
+            }
+
+            s.spawn(|| {
+                for _ in 0..STEPS {
+                    let previous = link.fetch_set_flag(true, SeqCst);
+                    assert!(!previous.get_flag());
+                    thread::yield_now();
+                    let marked = link.fetch_set_flag(false, SeqCst);
+                    assert!(marked.get_flag());
+                    assert!(std::ptr::eq(marked.get_ref(), previous.get_ref()));
+                }
+            });
+        });
+
+        let end = link.load(Relaxed);
+        assert!(!end.get_flag());
+        assert_eq!(*end.get_ref() as usize, ADVANCERS * STEPS);
+    }
+}
@@ -0,0 +1,272 @@
+//! A generalization of `RefWithFlag`: steal as many low bits as the
+//! pointee's alignment leaves free, from any kind of owning or borrowing
+//! pointer.
+
+use std::fmt;
+use std::marker::PhantomData;
+use std::mem::{align_of, ManuallyDrop};
+use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
+
+/// A pointer type that can be converted to a raw pointer and back.
+///
+/// # Safety
+///
+/// `into_raw` must return a non-null pointer, aligned for `Self::Target`,
+/// and `from_raw` must accept any pointer `into_raw` returned, giving back
+/// exactly the value that was converted. `TaggedPtr` relies on this to
+/// stash bits in the pointer's alignment padding.
+pub unsafe trait Pointer: Deref
+where Self::Target: Sized
+{
+    /// Consume `this`, returning the raw pointer it holds.
+    fn into_raw(this: Self) -> *const Self::Target;
+
+    /// Rebuild a pointer from a raw pointer produced by `into_raw`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have come from `into_raw` on this same pointer type, and
+    /// must be converted back at most once.
+    unsafe fn from_raw(ptr: *const Self::Target) -> Self;
+}
+
+unsafe impl<'a, T> Pointer for &'a T {
+    fn into_raw(this: &'a T) -> *const T {
+        this
+    }
+
+    unsafe fn from_raw(ptr: *const T) -> &'a T {
+        &*ptr
+    }
+}
+
+unsafe impl<'a, T> Pointer for &'a mut T {
+    fn into_raw(this: &'a mut T) -> *const T {
+        this
+    }
+
+    unsafe fn from_raw(ptr: *const T) -> &'a mut T {
+        &mut *(ptr as *mut T)
+    }
+}
+
+unsafe impl<T> Pointer for Box<T> {
+    fn into_raw(this: Box<T>) -> *const T {
+        Box::into_raw(this)
+    }
+
+    unsafe fn from_raw(ptr: *const T) -> Box<T> {
+        Box::from_raw(ptr as *mut T)
+    }
+}
+
+unsafe impl<T> Pointer for Arc<T> {
+    fn into_raw(this: Arc<T>) -> *const T {
+        Arc::into_raw(this)
+    }
+
+    unsafe fn from_raw(ptr: *const T) -> Arc<T> {
+        Arc::from_raw(ptr)
+    }
+}
+
+/// A pointer `P` and a `BITS`-bit tag, wrapped up in a single word.
+///
+/// The tag lives in the pointer's low bits, which are always zero because
+/// of the pointee's alignment. Asking for more bits than the alignment
+/// provides is a compile-time error:
+///
+/// ```compile_fail
+/// use ref_with_flag::tagged_ptr::TaggedPtr;
+///
+/// let byte = 0_u8;
+/// let tagged = TaggedPtr::<&u8, 1>::new(&byte, 1); // u8 has no spare bits
+/// ```
+pub struct TaggedPtr<P: Pointer, const BITS: usize>
+where P::Target: Sized
+{
+    ptr_and_tag: usize,
+    behaves_like: PhantomData<P>, // occupies no space
+}
+
+impl<P: Pointer, const BITS: usize> TaggedPtr<P, BITS>
+where P::Target: Sized
+{
+    /// The bits of `ptr_and_tag` that hold the tag.
+    const MASK: usize = (1 << BITS) - 1;
+
+    /// Evaluating this fails to compile if `P::Target`'s alignment doesn't
+    /// leave `BITS` low bits free.
+    const BITS_FIT: () = assert!(BITS < usize::BITS as usize
+                                 && 1 << BITS <= align_of::<P::Target>(),
+                                 "pointee alignment leaves too few bits for the tag");
+
+    /// Pack `ptr` and `tag` together.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tag` doesn't fit in `BITS` bits.
+    pub fn new(ptr: P, tag: usize) -> TaggedPtr<P, BITS> {
+        let () = Self::BITS_FIT;
+        assert!(tag <= Self::MASK, "tag {} doesn't fit in {} bits", tag, BITS);
+        TaggedPtr {
+            ptr_and_tag: P::into_raw(ptr) as usize | tag,
+            behaves_like: PhantomData,
+        }
+    }
+
+    fn raw(&self) -> *const P::Target {
+        (self.ptr_and_tag & !Self::MASK) as *const P::Target
+    }
+
+    pub fn tag(&self) -> usize {
+        self.ptr_and_tag & Self::MASK
+    }
+
+    /// Replace the tag, leaving the pointer unchanged.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tag` doesn't fit in `BITS` bits.
+    pub fn set_tag(&mut self, tag: usize) {
+        assert!(tag <= Self::MASK, "tag {} doesn't fit in {} bits", tag, BITS);
+        self.ptr_and_tag = self.ptr_and_tag & !Self::MASK | tag;
+    }
+
+    /// Take the pointer and tag apart again.
+    pub fn into_inner(self) -> (P, usize) {
+        let this = ManuallyDrop::new(self);
+        (unsafe { P::from_raw(this.raw()) }, this.tag())
+    }
+
+    /// Apply `f` to the pointer, keeping the tag. The new pointee's
+    /// alignment must leave room for the tag, too.
+    pub fn map<Q, F>(self, f: F) -> TaggedPtr<Q, BITS>
+    where F: FnOnce(P) -> Q,
+          Q: Pointer,
+          Q::Target: Sized
+    {
+        let (ptr, tag) = self.into_inner();
+        TaggedPtr::new(f(ptr), tag)
+    }
+}
+
+impl<P: Pointer, const BITS: usize> Deref for TaggedPtr<P, BITS>
+where P::Target: Sized
+{
+    type Target = P::Target;
+
+    fn deref(&self) -> &P::Target {
+        unsafe { &*self.raw() }
+    }
+}
+
+impl<P: Pointer + DerefMut, const BITS: usize> DerefMut for TaggedPtr<P, BITS>
+where P::Target: Sized
+{
+    fn deref_mut(&mut self) -> &mut P::Target {
+        unsafe { &mut *(self.raw() as *mut P::Target) }
+    }
+}
+
+impl<P: Pointer + Clone, const BITS: usize> Clone for TaggedPtr<P, BITS>
+where P::Target: Sized
+{
+    fn clone(&self) -> Self {
+        // Borrow the pointer back without taking ownership of it.
+        let ptr = ManuallyDrop::new(unsafe { P::from_raw(self.raw()) });
+        TaggedPtr::new(P::clone(&ptr), self.tag())
+    }
+}
+
+impl<P: Pointer, const BITS: usize> Drop for TaggedPtr<P, BITS>
+where P::Target: Sized
+{
+    fn drop(&mut self) {
+        drop(unsafe { P::from_raw(self.raw()) });
+    }
+}
+
+impl<P: Pointer, const BITS: usize> fmt::Debug for TaggedPtr<P, BITS>
+where P::Target: Sized + fmt::Debug
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaggedPtr")
+            .field("target", &**self)
+            .field("tag", &self.tag())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TaggedPtr;
+    use std::sync::Arc;
+
+    #[test]
+    fn shared_ref() {
+        let vec = vec![10, 20, 30];
+        let mut tagged = TaggedPtr::<_, 3>::new(&vec, 5);
+        assert_eq!(tagged[1], 20);
+        assert_eq!(tagged.tag(), 5);
+
+        tagged.set_tag(7);
+        assert_eq!(tagged.tag(), 7);
+        assert_eq!(tagged.len(), 3);
+
+        let copy = tagged.clone();
+        let (ptr, tag) = tagged.into_inner();
+        assert!(std::ptr::eq(ptr, &vec));
+        assert_eq!(tag, 7);
+        assert_eq!(copy.tag(), 7);
+    }
+
+    #[test]
+    fn mut_ref() {
+        let mut n = 1_u32;
+        {
+            let mut tagged = TaggedPtr::<_, 2>::new(&mut n, 3);
+            *tagged += 41;
+            tagged.set_tag(0);
+            assert_eq!(*tagged, 42);
+            assert_eq!(tagged.tag(), 0);
+        }
+        assert_eq!(n, 42);
+    }
+
+    #[test]
+    fn boxed() {
+        let tagged = TaggedPtr::<_, 3>::new(Box::new(17_u64), 6);
+        assert_eq!(*tagged, 17);
+
+        let mapped = tagged.map(|b| Box::new(*b as f64 * 2.0));
+        assert_eq!(*mapped, 34.0);
+        assert_eq!(mapped.tag(), 6);
+
+        let (b, tag) = mapped.into_inner();
+        assert_eq!((*b, tag), (34.0, 6));
+    }
+
+    #[test]
+    fn arc_reference_counts() {
+        let shared = Arc::new(String::from("tagged"));
+        let tagged = TaggedPtr::<_, 1>::new(shared.clone(), 1);
+        assert_eq!(Arc::strong_count(&shared), 2);
+
+        let again = tagged.clone();
+        assert_eq!(Arc::strong_count(&shared), 3);
+        assert_eq!(&**again, "tagged");
+
+        drop(tagged);
+        drop(again);
+        assert_eq!(Arc::strong_count(&shared), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "tag 4 doesn't fit in 2 bits")]
+    fn oversized_tag() {
+        let n = 0_u32;
+        TaggedPtr::<_, 2>::new(&n, 4);
+    }
+}
@@ -1,60 +1,154 @@
 #[cfg(test)]
 mod tests;
 
-use std::sync::{Arc, Mutex};
-use std::task::Waker;
+mod oneshot;
+mod pool;
+mod stream;
+pub use pool::{Builder, Pool};
+pub use stream::{spawn_blocking_stream, BlockingStream};
 
-pub struct SpawnBlocking<T>(Arc<Mutex<Shared<T>>>);
+use oneshot::Oneshot;
+use pool::{Job, Submission};
+use std::any::Any;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
 
-struct Shared<T> {
-    value: Option<T>,
-    waker: Option<Waker>,
+pub struct SpawnBlocking<T> {
+    shared: Arc<Oneshot<Result<T, JoinError>>>,
+    submission: Submission,
 }
 
+/// Run `closure` on the shared pool of blocking threads, and return a
+/// future for its value. See `Pool` for how the pool is managed; use your
+/// own `Pool` for different settings.
+///
+/// The closure is queued straight away if the pool's queue has room. If
+/// the queue is full, the future holds on to the closure, which is queued,
+/// and so runs, only when the future is polled after room is made: a
+/// future that's never awaited never runs its closure.
+///
+/// If `closure` panics, the future's value is a `JoinError` holding the
+/// panic's payload. Use `SpawnBlocking::resume_unwind` to panic in the
+/// awaiting task instead.
 pub fn spawn_blocking<T, F>(closure: F) -> SpawnBlocking<T>
 where F: FnOnce() -> T,
       F: Send + 'static,
       T: Send + 'static,
 {
-    let inner = Arc::new(Mutex::new(Shared {
-        value: None,
-        waker: None,
-    }));
-
-    std::thread::spawn({
-        let inner = inner.clone();
-        move || {
-            let value = closure();
-
-            let maybe_waker = {
-                let mut guard = inner.lock().unwrap();
-                guard.value = Some(value);
-                guard.waker.take()
-            };
-
-            if let Some(waker) = maybe_waker {
-                waker.wake();
+    pool::global().spawn_blocking(closure)
+}
+
+impl<T: Send + 'static> SpawnBlocking<T> {
+    fn new<F>(pool: Arc<pool::Inner>, closure: F) -> SpawnBlocking<T>
+    where F: FnOnce() -> T,
+          F: Send + 'static,
+    {
+        let inner = Arc::new(Oneshot::new());
+
+        let job: Job = Box::new({
+            let inner = inner.clone();
+            move || {
+                let value = panic::catch_unwind(AssertUnwindSafe(closure))
+                    .map_err(|payload| JoinError { payload });
+
+                // Safe: the job runs only once.
+                unsafe { inner.send(value) };
             }
-        }
-    });
+        });
 
-    SpawnBlocking(inner)
+        SpawnBlocking { shared: inner, submission: Submission::new(pool, job) }
+    }
 }
 
 use std::future::Future;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
+impl<T> SpawnBlocking<T> {
+    /// Return a future for the closure's value that, if the closure
+    /// panicked, continues the panic in the task awaiting it.
+    pub fn resume_unwind(self) -> ResumeUnwind<T> {
+        ResumeUnwind(self)
+    }
+}
+
 impl<T: Send> Future for SpawnBlocking<T> {
+    type Output = Result<T, JoinError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if this.submission.poll_queued(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        // Safe: we have `&mut self`, so nothing else is receiving.
+        let value = unsafe { this.shared.poll_recv(cx) };
+        if value.is_ready() {
+            this.submission.done();
+        }
+        value
+    }
+}
+
+/// A future for a `SpawnBlocking`'s value that resumes the closure's panic,
+/// if it had one. See `SpawnBlocking::resume_unwind`.
+pub struct ResumeUnwind<T>(SpawnBlocking<T>);
+
+impl<T: Send> Future for ResumeUnwind<T> {
     type Output = T;
 
-    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
-        let mut guard = self.0.lock().unwrap();
-        if let Some(value) = guard.value.take() {
-            return Poll::Ready(value);
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        match Pin::new(&mut self.0).poll(cx) {
+            Poll::Ready(Ok(value)) => Poll::Ready(value),
+            Poll::Ready(Err(error)) => error.resume(),
+            Poll::Pending => Poll::Pending,
         }
+    }
+}
+
+/// The error from a `SpawnBlocking` whose closure panicked.
+pub struct JoinError {
+    payload: Box<dyn Any + Send + 'static>,
+}
+
+impl JoinError {
+    /// Return the panic's message, if it had one: that is, if the payload
+    /// is a string, as it is for `panic!` with a message.
+    pub fn panic_message(&self) -> Option<&str> {
+        if let Some(message) = self.payload.downcast_ref::<&'static str>() {
+            Some(message)
+        } else {
+            self.payload.downcast_ref::<String>().map(String::as_str)
+        }
+    }
+
+    /// Return the value the closure panicked with.
+    pub fn into_panic(self) -> Box<dyn Any + Send + 'static> {
+        self.payload
+    }
 
-        guard.waker = Some(cx.waker().clone());
-        Poll::Pending
+    /// Continue the panic in the current thread, with the original payload.
+    pub fn resume(self) -> ! {
+        panic::resume_unwind(self.payload)
     }
 }
+
+impl fmt::Debug for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JoinError")
+            .field("panic_message", &self.panic_message())
+            .finish()
+    }
+}
+
+impl fmt::Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.panic_message() {
+            Some(message) => write!(f, "blocking task panicked: {}", message),
+            None => write!(f, "blocking task panicked"),
+        }
+    }
+}
+
+impl std::error::Error for JoinError {}
@@ -0,0 +1,145 @@
+//! A lock-free slot for passing one value from a worker thread to a future.
+//!
+//! The worker writes the value, then sets a flag; the future checks the
+//! flag before reading the value. Waking is handled by `AtomicWaker`, which
+//! lets the future swap in a new waker on each poll while the worker may be
+//! trying to wake the old one, without either side ever blocking.
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::task::{Context, Poll, Waker};
+
+pub(crate) struct Oneshot<T> {
+    /// Set once `value` has been written. After that, only the receiver
+    /// touches it.
+    complete: AtomicBool,
+    value: UnsafeCell<Option<T>>,
+    waker: AtomicWaker,
+}
+
+// The sender only writes `value` before setting `complete`, and the
+// receiver only reads it after seeing `complete` set, so access to it is
+// never shared.
+unsafe impl<T: Send> Send for Oneshot<T> {}
+unsafe impl<T: Send> Sync for Oneshot<T> {}
+
+impl<T> Oneshot<T> {
+    pub(crate) fn new() -> Oneshot<T> {
+        Oneshot {
+            complete: AtomicBool::new(false),
+            value: UnsafeCell::new(None),
+            waker: AtomicWaker::new(),
+        }
+    }
+
+    /// Store `value`, and wake the receiver.
+    ///
+    /// # Safety
+    ///
+    /// Must be called at most once.
+    pub(crate) unsafe fn send(&self, value: T) {
+        *self.value.get() = Some(value);
+        self.complete.store(true, Ordering::Release);
+        self.waker.wake();
+    }
+
+    /// Take the value, if it has been sent; otherwise, arrange for `cx` to
+    /// be woken when it is. Once the value has been taken, this is always
+    /// pending.
+    ///
+    /// # Safety
+    ///
+    /// Must not be called from two threads at once.
+    pub(crate) unsafe fn poll_recv(&self, cx: &mut Context<'_>) -> Poll<T> {
+        if !self.complete.load(Ordering::Acquire) {
+            self.waker.register(cx.waker());
+            // The value may have been sent before we registered, in which
+            // case the sender found no waker to wake.
+            if !self.complete.load(Ordering::Acquire) {
+                return Poll::Pending;
+            }
+        }
+        match (*self.value.get()).take() {
+            Some(value) => Poll::Ready(value),
+            None => Poll::Pending,
+        }
+    }
+}
+
+const WAITING: usize = 0;
+const REGISTERING: usize = 0b01;
+const WAKING: usize = 0b10;
+
+/// A waker slot that one thread can register in while another wakes it.
+///
+/// The state says who has access to `waker`: `register` holds it while
+/// `REGISTERING`, and `wake` while `WAKING`. If `wake` finds a registration
+/// in progress, it just sets `WAKING` and leaves; `register` notices that
+/// when it's done, and wakes the new waker itself. If `register` finds a
+/// wake in progress, the new waker might be missed, so it wakes it at once.
+pub(crate) struct AtomicWaker {
+    state: AtomicUsize,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+unsafe impl Send for AtomicWaker {}
+unsafe impl Sync for AtomicWaker {}
+
+impl AtomicWaker {
+    pub(crate) fn new() -> AtomicWaker {
+        AtomicWaker {
+            state: AtomicUsize::new(WAITING),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    /// Make `waker` the one woken by the next `wake`. Calls to `register`
+    /// must not overlap each other.
+    pub(crate) fn register(&self, waker: &Waker) {
+        match self.state.compare_exchange(WAITING, REGISTERING, Ordering::Acquire, Ordering::Acquire) {
+            Ok(_) => unsafe {
+                match &*self.waker.get() {
+                    Some(old) if old.will_wake(waker) => {}
+                    _ => *self.waker.get() = Some(waker.clone()),
+                }
+
+                if self.state.compare_exchange(REGISTERING, WAITING, Ordering::AcqRel, Ordering::Acquire).is_err() {
+                    // A `wake` came in while we were registering, and left
+                    // the waking to us. The state must be
+                    // `REGISTERING | WAKING`, so we still have the waker.
+                    let waker = (*self.waker.get()).take().unwrap();
+                    self.state.swap(WAITING, Ordering::AcqRel);
+                    waker.wake();
+                }
+            },
+            Err(WAKING) => {
+                // A `wake` is taking the old waker. It may already have
+                // done so, so wake the new one too.
+                waker.wake_by_ref();
+            }
+            Err(state) => {
+                debug_assert!(state == REGISTERING || state == REGISTERING | WAKING,
+                              "AtomicWaker in an impossible state");
+            }
+        }
+    }
+
+    /// Wake the registered waker, if there is one.
+    pub(crate) fn wake(&self) {
+        if let Some(waker) = self.take() {
+            waker.wake();
+        }
+    }
+
+    fn take(&self) -> Option<Waker> {
+        match self.state.fetch_or(WAKING, Ordering::AcqRel) {
+            WAITING => {
+                let waker = unsafe { (*self.waker.get()).take() };
+                self.state.fetch_and(!WAKING, Ordering::Release);
+                waker
+            }
+            // Someone else is registering or waking, and will see to it.
+            _ => None,
+        }
+    }
+}
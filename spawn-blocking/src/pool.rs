@@ -0,0 +1,376 @@
+//! The thread pool that runs `spawn_blocking` closures.
+//!
+//! A pool starts threads as jobs arrive, up to a limit, and lets them exit
+//! once they've been idle for a while. Jobs that arrive while every thread
+//! is busy wait in a queue. The queue has a limit too: once it's full, a
+//! `SpawnBlocking` holds on to its closure, and queues it when it's next
+//! polled after room is made. So a burst of calls costs memory, not threads,
+//! and a caller that awaits its jobs is slowed down to the pool's pace.
+
+use crate::{BlockingStream, SpawnBlocking};
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::Duration;
+
+pub(crate) type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Settings for a `Pool`.
+#[derive(Clone, Debug)]
+pub struct Builder {
+    max_threads: usize,
+    queue_limit: usize,
+    keep_alive: Duration,
+    thread_name: String,
+    stream_buffer: usize,
+}
+
+impl Builder {
+    /// Return a builder with the default settings: at most 64 threads, at
+    /// most 1024 queued jobs, threads exit after ten idle seconds, and
+    /// streams buffer 16 items.
+    pub fn new() -> Builder {
+        Builder {
+            max_threads: 64,
+            queue_limit: 1024,
+            keep_alive: Duration::from_secs(10),
+            thread_name: "spawn-blocking".to_string(),
+            stream_buffer: 16,
+        }
+    }
+
+    /// Set the maximum number of threads that run jobs at once.
+    pub fn max_threads(mut self, max_threads: usize) -> Builder {
+        self.max_threads = max_threads;
+        self
+    }
+
+    /// Set the maximum number of jobs waiting for a thread.
+    pub fn queue_limit(mut self, queue_limit: usize) -> Builder {
+        self.queue_limit = queue_limit;
+        self
+    }
+
+    /// Set how long a thread waits for a job before exiting.
+    pub fn keep_alive(mut self, keep_alive: Duration) -> Builder {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Set the name given to the pool's threads.
+    pub fn thread_name(mut self, name: &str) -> Builder {
+        self.thread_name = name.to_string();
+        self
+    }
+
+    /// Set how many items a `spawn_blocking_stream` stream buffers. Its
+    /// iterator can get one item further ahead of the consumer than this,
+    /// holding the item while it waits for room.
+    pub fn stream_buffer(mut self, stream_buffer: usize) -> Builder {
+        self.stream_buffer = stream_buffer;
+        self
+    }
+
+    /// Create the pool. No threads are started until there's work.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max_threads` or `stream_buffer` is zero.
+    pub fn build(self) -> Pool {
+        assert!(self.max_threads > 0, "a pool needs at least one thread");
+        assert!(self.stream_buffer > 0, "a stream buffer needs room for an item");
+        Pool {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State {
+                    queue: VecDeque::new(),
+                    next_id: 0,
+                    threads: 0,
+                    idle: 0,
+                    wakeups: 0,
+                    space_waiters: vec![],
+                    shutdown: false,
+                }),
+                work: Condvar::new(),
+                config: self,
+            }),
+        }
+    }
+}
+
+impl Default for Builder {
+    fn default() -> Builder {
+        Builder::new()
+    }
+}
+
+/// A pool of threads for running blocking closures.
+///
+/// Dropping the pool doesn't cancel its jobs: they all still run, and their
+/// `SpawnBlocking` futures still complete. Its threads exit as soon as
+/// they're idle.
+pub struct Pool {
+    inner: Arc<Inner>,
+}
+
+pub(crate) struct Inner {
+    state: Mutex<State>,
+
+    /// Signalled when a job is queued for an idle thread, or on shutdown.
+    work: Condvar,
+
+    config: Builder,
+}
+
+struct State {
+    queue: VecDeque<(u64, Job)>,
+    next_id: u64,
+
+    /// The number of threads running, and how many of those are waiting
+    /// for work.
+    threads: usize,
+    idle: usize,
+
+    /// The number of idle threads that have been handed a job, but haven't
+    /// woken up to take it yet. They no longer count as idle.
+    wakeups: usize,
+
+    /// The wakers of futures waiting for room in the queue.
+    space_waiters: Vec<Waker>,
+
+    shutdown: bool,
+}
+
+impl Pool {
+    /// Create a pool with the default settings. See `Builder::new`.
+    pub fn new() -> Pool {
+        Builder::new().build()
+    }
+
+    /// Run `closure` on one of the pool's threads, and return a future for
+    /// its value. As with the `spawn_blocking` function, if the queue is
+    /// full, the closure waits in the future until it's polled.
+    pub fn spawn_blocking<T, F>(&self, closure: F) -> SpawnBlocking<T>
+    where F: FnOnce() -> T,
+          F: Send + 'static,
+          T: Send + 'static,
+    {
+        SpawnBlocking::new(self.inner.clone(), closure)
+    }
+
+    /// Run `iter` on one of the pool's threads, and return a stream of its
+    /// items. See `spawn_blocking_stream`.
+    pub fn spawn_blocking_stream<I>(&self, iter: I) -> BlockingStream<I::Item>
+    where I: IntoIterator + Send + 'static,
+          I::Item: Send + 'static,
+    {
+        BlockingStream::new(self.inner.clone(), self.inner.config.stream_buffer, iter)
+    }
+
+    /// Return the number of jobs waiting for a thread.
+    pub fn queued_jobs(&self) -> usize {
+        self.inner.lock().queue.len()
+    }
+
+    /// Return the number of threads the pool has running.
+    pub fn threads(&self) -> usize {
+        self.inner.lock().threads
+    }
+
+    /// Return the number of wakers waiting for room in the queue.
+    #[cfg(test)]
+    pub(crate) fn space_waiters(&self) -> usize {
+        self.inner.lock().space_waiters.len()
+    }
+}
+
+impl Default for Pool {
+    fn default() -> Pool {
+        Pool::new()
+    }
+}
+
+impl Drop for Pool {
+    fn drop(&mut self) {
+        self.inner.lock().shutdown = true;
+        self.inner.work.notify_all();
+    }
+}
+
+/// A job handed to a pool, as seen by the future or stream waiting on it.
+/// Dropping it before the job starts takes the job out of the queue.
+pub(crate) struct Submission {
+    pool: Arc<Inner>,
+    start: Start,
+}
+
+/// How far a job has got towards running.
+enum Start {
+    /// The pool's queue was full. We'll try again when polled.
+    Waiting(Job),
+
+    /// In the pool's queue, with this id, unless a thread has taken it.
+    Queued(u64),
+
+    /// Finished, and its result taken.
+    Done,
+}
+
+impl Submission {
+    pub(crate) fn new(pool: Arc<Inner>, job: Job) -> Submission {
+        let start = match pool.push(job, None) {
+            Ok(id) => Start::Queued(id),
+            Err(job) => Start::Waiting(job),
+        };
+        Submission { pool, start }
+    }
+
+    /// If the job is still waiting for room in the queue, try again to
+    /// queue it. Return `Pending` if there's still no room.
+    pub(crate) fn poll_queued(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        self.start = match std::mem::replace(&mut self.start, Start::Done) {
+            Start::Waiting(job) => match self.pool.push(job, Some(cx.waker())) {
+                Ok(id) => Start::Queued(id),
+                Err(job) => {
+                    self.start = Start::Waiting(job);
+                    return Poll::Pending;
+                }
+            },
+            start => start,
+        };
+        Poll::Ready(())
+    }
+
+    /// Note that the job has finished, so there's nothing to cancel.
+    pub(crate) fn done(&mut self) {
+        self.start = Start::Done;
+    }
+}
+
+impl Drop for Submission {
+    /// If the job hasn't started yet, it never will.
+    fn drop(&mut self) {
+        if let Start::Queued(id) = self.start {
+            self.pool.cancel(id);
+        }
+    }
+}
+
+/// Return the pool used by the `spawn_blocking` function.
+pub(crate) fn global() -> &'static Pool {
+    static GLOBAL: OnceLock<Pool> = OnceLock::new();
+    GLOBAL.get_or_init(Pool::new)
+}
+
+impl Inner {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /// Queue `job`, and return its id in the queue. If the queue is full,
+    /// return the job instead, and arrange for `waker` to be woken when
+    /// there may be room.
+    fn push(self: &Arc<Self>, job: Job, waker: Option<&Waker>) -> Result<u64, Job> {
+        let mut state = self.lock();
+        if state.queue.len() >= self.config.queue_limit && !self.can_run_now(&state) {
+            // A future polled again while it waits is already in the list.
+            if let Some(waker) = waker {
+                if !state.space_waiters.iter().any(|w| w.will_wake(waker)) {
+                    state.space_waiters.push(waker.clone());
+                }
+            }
+            return Err(job);
+        }
+
+        let id = state.next_id;
+        state.next_id += 1;
+        state.queue.push_back((id, job));
+
+        if state.idle > 0 {
+            state.idle -= 1;
+            state.wakeups += 1;
+            self.work.notify_one();
+        } else if state.threads < self.config.max_threads {
+            state.threads += 1;
+            let inner = self.clone();
+            let spawned = thread::Builder::new()
+                .name(self.config.thread_name.clone())
+                .spawn(move || inner.work());
+            if let Err(error) = spawned {
+                // The threads we have will get to the job eventually; but if
+                // there aren't any, it would never run. Take it back, and
+                // let go of the lock before panicking, so as not to poison
+                // it for everyone else.
+                state.threads -= 1;
+                if state.threads == 0 {
+                    let job = state.queue.pop_back();
+                    drop(state);
+                    drop(job);
+                    panic!("failed to start a thread: {}", error);
+                }
+            }
+        }
+        Ok(id)
+    }
+
+    /// Return true if a job would be taken straight off the queue by an
+    /// idle or new thread, even if the queue is full.
+    fn can_run_now(&self, state: &State) -> bool {
+        state.idle > 0 || state.threads < self.config.max_threads
+    }
+
+    /// Remove the job with the given id from the queue. Return false if it
+    /// has already been taken by a thread.
+    fn cancel(&self, id: u64) -> bool {
+        let mut state = self.lock();
+        match state.queue.iter().position(|(queued, _)| *queued == id) {
+            Some(index) => {
+                let job = state.queue.remove(index);
+                let waiters = std::mem::take(&mut state.space_waiters);
+                drop(state);
+                drop(job);
+                waiters.into_iter().for_each(Waker::wake);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The body of each of the pool's threads.
+    fn work(&self) {
+        let mut state = self.lock();
+        loop {
+            if let Some((_, job)) = state.queue.pop_front() {
+                // There's room in the queue now. Whoever was waiting for it
+                // may be gone, so wake them all and let them race for it.
+                let waiters = std::mem::take(&mut state.space_waiters);
+                drop(state);
+                waiters.into_iter().for_each(Waker::wake);
+                job();
+                state = self.lock();
+                continue;
+            }
+
+            state.idle += 1;
+            loop {
+                if state.shutdown && state.wakeups == 0 {
+                    state.idle -= 1;
+                    state.threads -= 1;
+                    return;
+                }
+                let (guard, wait) = self.work.wait_timeout(state, self.config.keep_alive).unwrap();
+                state = guard;
+                if state.wakeups > 0 {
+                    // `push` has already taken us off the idle count.
+                    state.wakeups -= 1;
+                    break;
+                }
+                if wait.timed_out() {
+                    state.idle -= 1;
+                    state.threads -= 1;
+                    return;
+                }
+            }
+        }
+    }
+}
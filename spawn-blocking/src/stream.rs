@@ -0,0 +1,164 @@
+//! Running a blocking iterator on a pool thread, and consuming its items as
+//! an async `Stream`.
+//!
+//! The worker thread sends items through a bounded channel. When the
+//! channel is full, the worker blocks until the stream's consumer catches
+//! up, so a slow consumer holds back a fast iterator instead of letting
+//! items pile up. Dropping the stream closes the channel; the worker stops
+//! as soon as the item it's producing is done, and drops the iterator.
+
+use crate::pool::{self, Job, Submission};
+use crate::JoinError;
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// Run `iter` on the shared pool of blocking threads, and return a stream
+/// of its items.
+///
+/// The iterator occupies one of the pool's threads until it's exhausted or
+/// the stream is dropped. It gets ahead of the consumer by at most the
+/// pool's `stream_buffer` buffered items, plus the one it's waiting to
+/// send. If it panics, the stream's last item is a `JoinError` holding the
+/// panic's payload.
+pub fn spawn_blocking_stream<I>(iter: I) -> BlockingStream<I::Item>
+where I: IntoIterator + Send + 'static,
+      I::Item: Send + 'static,
+{
+    pool::global().spawn_blocking_stream(iter)
+}
+
+/// A stream of items from an iterator on a pool thread. See
+/// `spawn_blocking_stream`.
+pub struct BlockingStream<T> {
+    channel: Arc<Channel<T>>,
+    submission: Submission,
+}
+
+struct Channel<T> {
+    state: Mutex<State<T>>,
+
+    /// Signalled when there's room for the worker to send an item, or the
+    /// stream has been dropped.
+    space: Condvar,
+    capacity: usize,
+}
+
+struct State<T> {
+    items: VecDeque<T>,
+
+    /// Set when the iterator is exhausted or panicked.
+    finished: bool,
+    panic: Option<JoinError>,
+
+    /// Set when the stream is dropped.
+    closed: bool,
+
+    waker: Option<Waker>,
+}
+
+impl<T: Send + 'static> BlockingStream<T> {
+    pub(crate) fn new<I>(pool: Arc<pool::Inner>, capacity: usize, iter: I) -> BlockingStream<T>
+    where I: IntoIterator<Item = T> + Send + 'static,
+    {
+        let channel = Arc::new(Channel {
+            state: Mutex::new(State {
+                items: VecDeque::new(),
+                finished: false,
+                panic: None,
+                closed: false,
+                waker: None,
+            }),
+            space: Condvar::new(),
+            capacity,
+        });
+
+        let job: Job = Box::new({
+            let channel = channel.clone();
+            move || {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                    for item in iter {
+                        if !channel.send(item) {
+                            break;
+                        }
+                    }
+                }));
+                channel.finish(result.err().map(|payload| JoinError { payload }));
+            }
+        });
+
+        BlockingStream { channel, submission: Submission::new(pool, job) }
+    }
+}
+
+impl<T> Channel<T> {
+    /// Send `item` to the stream, waiting for room if the buffer is full.
+    /// Return false if the stream has been dropped.
+    fn send(&self, item: T) -> bool {
+        let mut state = self.state.lock().unwrap();
+        while state.items.len() >= self.capacity && !state.closed {
+            state = self.space.wait(state).unwrap();
+        }
+        if state.closed {
+            return false;
+        }
+        state.items.push_back(item);
+        let waker = state.waker.take();
+        drop(state);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        true
+    }
+
+    fn finish(&self, panic: Option<JoinError>) {
+        let waker = {
+            let mut state = self.state.lock().unwrap();
+            state.finished = true;
+            state.panic = panic;
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> Stream for BlockingStream<T> {
+    type Item = Result<T, JoinError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.submission.poll_queued(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        let mut state = this.channel.state.lock().unwrap();
+        if let Some(item) = state.items.pop_front() {
+            drop(state);
+            this.channel.space.notify_one();
+            return Poll::Ready(Some(Ok(item)));
+        }
+        if state.finished {
+            this.submission.done();
+            return Poll::Ready(state.panic.take().map(Err));
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl<T> Drop for BlockingStream<T> {
+    fn drop(&mut self) {
+        let mut state = self.channel.state.lock().unwrap();
+        state.closed = true;
+        // Drop any buffered items now, rather than when the worker lets go
+        // of the channel.
+        state.items.clear();
+        drop(state);
+        self.channel.space.notify_one();
+    }
+}
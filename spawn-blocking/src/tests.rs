@@ -13,7 +13,7 @@ async fn verify_password(password: &str, hash: &str, key: &str)
             .with_password(password)
             .with_secret_key(key)
             .verify()
-    }).await
+    }).resume_unwind().await
 }
 
 static PASSWORD: &str = "P@ssw0rd";
@@ -36,7 +36,7 @@ fn argonautica() {
 fn many_serial() {
     async_std::task::block_on(async {
         for i in 0..1000 {
-            assert_eq!(spawn_blocking(move || i).await, i);
+            assert_eq!(spawn_blocking(move || i).await.unwrap(), i);
         }
     });
 }
@@ -49,7 +49,434 @@ fn many_parallel() {
             .collect();
 
         for (i, f) in futures {
-            assert_eq!(f.await, i);
+            assert_eq!(f.await.unwrap(), i);
         }
     });
 }
+
+/// Return a closure that blocks until the returned sender is sent to or
+/// dropped, and the sender.
+fn gate() -> (impl FnOnce() + Send + 'static, std::sync::mpsc::Sender<()>) {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    (move || { let _ = receiver.recv(); }, sender)
+}
+
+/// Wait for a thread to take jobs off `pool`'s queue until there are
+/// `queued` left.
+fn wait_for_queue(pool: &Pool, queued: usize) {
+    for _ in 0..1000 {
+        if pool.queued_jobs() == queued {
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+    panic!("the pool still has {} jobs queued", pool.queued_jobs());
+}
+
+#[test]
+fn pool_limits_threads() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let pool = Builder::new().max_threads(3).build();
+    let running = Arc::new(AtomicUsize::new(0));
+    let most = Arc::new(AtomicUsize::new(0));
+    let jobs: Vec<_> = (0..50)
+        .map(|i| {
+            let running = running.clone();
+            let most = most.clone();
+            pool.spawn_blocking(move || {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(std::time::Duration::from_millis(2));
+                running.fetch_sub(1, Ordering::SeqCst);
+                i
+            })
+        })
+        .collect();
+    assert!(pool.threads() <= 3);
+
+    async_std::task::block_on(async {
+        for (i, job) in jobs.into_iter().enumerate() {
+            assert_eq!(job.await.unwrap(), i);
+        }
+    });
+    assert!(most.load(Ordering::SeqCst) <= 3);
+    assert_eq!(pool.queued_jobs(), 0);
+}
+
+#[test]
+fn idle_threads_exit() {
+    let pool = Builder::new()
+        .max_threads(4)
+        .keep_alive(std::time::Duration::from_millis(20))
+        .thread_name("idle-test")
+        .build();
+    async_std::task::block_on(async {
+        let name = pool.spawn_blocking(|| std::thread::current().name().map(String::from));
+        assert_eq!(name.await.unwrap().as_deref(), Some("idle-test"));
+    });
+    std::thread::sleep(std::time::Duration::from_millis(200));
+    assert_eq!(pool.threads(), 0);
+
+    // Work restarts them.
+    assert_eq!(async_std::task::block_on(pool.spawn_blocking(|| 5)).unwrap(), 5);
+}
+
+#[test]
+fn full_queue_applies_backpressure() {
+    let pool = Builder::new().max_threads(1).queue_limit(1).build();
+    let (blocker, release) = gate();
+    let first = pool.spawn_blocking(blocker);
+    wait_for_queue(&pool, 0);
+
+    // The first job is running and the second is queued; the third is
+    // held by its future until there's room.
+    let second = pool.spawn_blocking(|| 2);
+    let third = pool.spawn_blocking(|| 3);
+    assert_eq!(pool.queued_jobs(), 1);
+
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        release.send(()).unwrap();
+    });
+    async_std::task::block_on(async {
+        assert_eq!(third.await.unwrap(), 3);
+        assert_eq!(second.await.unwrap(), 2);
+        first.await.unwrap();
+    });
+}
+
+#[test]
+fn repeated_polls_register_one_waker() {
+    use std::task::Wake;
+
+    struct Noop;
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    let pool = Builder::new().max_threads(1).queue_limit(1).build();
+    let (blocker, release) = gate();
+    let first = pool.spawn_blocking(blocker);
+    wait_for_queue(&pool, 0);
+    let second = pool.spawn_blocking(|| 2);
+    let mut third = pool.spawn_blocking(|| 3);
+
+    // However often the held-back future is polled, it waits in line once.
+    let waker = Arc::new(Noop).into();
+    let mut cx = Context::from_waker(&waker);
+    for _ in 0..100 {
+        assert!(Pin::new(&mut third).poll(&mut cx).is_pending());
+    }
+    assert_eq!(pool.space_waiters(), 1);
+
+    release.send(()).unwrap();
+    async_std::task::block_on(async {
+        first.await.unwrap();
+        assert_eq!(second.await.unwrap(), 2);
+        assert_eq!(third.await.unwrap(), 3);
+    });
+}
+
+#[test]
+fn dropping_cancels_queued_jobs() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    let pool = Builder::new().max_threads(1).build();
+    let (blocker, release) = gate();
+    let first = pool.spawn_blocking(blocker);
+    wait_for_queue(&pool, 0);
+
+    let ran = Arc::new(AtomicBool::new(false));
+    let cancelled = pool.spawn_blocking({
+        let ran = ran.clone();
+        move || ran.store(true, Ordering::SeqCst)
+    });
+    assert_eq!(pool.queued_jobs(), 1);
+    drop(cancelled);
+    assert_eq!(pool.queued_jobs(), 0);
+
+    release.send(()).unwrap();
+    async_std::task::block_on(async {
+        first.await.unwrap();
+        pool.spawn_blocking(|| ()).await.unwrap();
+    });
+    assert!(!ran.load(Ordering::SeqCst));
+}
+
+#[test]
+fn jobs_outlive_their_pool() {
+    let pool = Builder::new().max_threads(1).build();
+    let (blocker, release) = gate();
+    let first = pool.spawn_blocking(blocker);
+    let second = pool.spawn_blocking(|| "still ran");
+    drop(pool);
+    release.send(()).unwrap();
+    async_std::task::block_on(async {
+        first.await.unwrap();
+        assert_eq!(second.await.unwrap(), "still ran");
+    });
+}
+
+#[test]
+fn panics_become_errors() {
+    let pool = Builder::new().max_threads(1).build();
+    async_std::task::block_on(async {
+        let error = pool.spawn_blocking(|| -> i32 { panic!("oh no") }).await.unwrap_err();
+        assert_eq!(error.panic_message(), Some("oh no"));
+        assert_eq!(error.to_string(), "blocking task panicked: oh no");
+
+        let error = pool.spawn_blocking(|| panic!("{} {}", "formatted", 1)).await.unwrap_err();
+        assert_eq!(error.panic_message(), Some("formatted 1"));
+
+        let error = pool.spawn_blocking(|| std::panic::panic_any(17u8)).await.unwrap_err();
+        assert_eq!(error.panic_message(), None);
+        assert_eq!(format!("{:?}", error), "JoinError { panic_message: None }");
+        assert_eq!(error.into_panic().downcast_ref::<u8>(), Some(&17));
+
+        // The pool's thread survives.
+        assert_eq!(pool.spawn_blocking(|| 4).await.unwrap(), 4);
+    });
+    assert_eq!(pool.threads(), 1);
+}
+
+#[test]
+fn panics_can_be_resumed() {
+    let result = std::panic::catch_unwind(|| {
+        async_std::task::block_on(spawn_blocking(|| -> () { panic!("resumed") }).resume_unwind())
+    });
+    let payload = result.unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"resumed"));
+
+    let value = async_std::task::block_on(spawn_blocking(|| 9).resume_unwind());
+    assert_eq!(value, 9);
+}
+
+/// A waker that sets a flag and unparks the thread that made it.
+struct Flag {
+    woken: std::sync::atomic::AtomicBool,
+    thread: std::thread::Thread,
+}
+
+impl std::task::Wake for Flag {
+    fn wake(self: Arc<Self>) {
+        self.woken.store(true, std::sync::atomic::Ordering::SeqCst);
+        self.thread.unpark();
+    }
+}
+
+/// Poll `job` to completion by hand, with a new waker for every poll.
+/// After some pending polls, wait to be woken rather than polling straight
+/// away; if a wake-up goes to an old waker instead, this times out.
+fn poll_with_changing_wakers<T: Send>(mut job: SpawnBlocking<T>, seed: usize) -> T {
+    use std::sync::atomic::Ordering;
+    use std::task::{Context, Poll, Waker};
+
+    for poll in seed.. {
+        let flag = Arc::new(Flag {
+            woken: std::sync::atomic::AtomicBool::new(false),
+            thread: std::thread::current(),
+        });
+        let waker = Waker::from(flag.clone());
+        if let Poll::Ready(value) = Pin::new(&mut job).poll(&mut Context::from_waker(&waker)) {
+            return value.unwrap();
+        }
+        if poll % 3 == 0 {
+            let start = std::time::Instant::now();
+            while !flag.woken.load(Ordering::SeqCst) {
+                assert!(start.elapsed() < std::time::Duration::from_secs(10), "wake-up was lost");
+                std::thread::park_timeout(std::time::Duration::from_millis(10));
+            }
+        }
+    }
+    unreachable!()
+}
+
+#[test]
+fn completion_races_polls() {
+    let pool = Builder::new().max_threads(4).build();
+    for i in 0..2000 {
+        let job = pool.spawn_blocking(move || {
+            for _ in 0..i % 50 {
+                std::hint::spin_loop();
+            }
+            i
+        });
+        assert_eq!(poll_with_changing_wakers(job, i), i);
+    }
+}
+
+#[test]
+fn completion_races_polls_on_many_threads() {
+    let pool = Arc::new(Builder::new().max_threads(4).build());
+    let pollers: Vec<_> = (0..8)
+        .map(|thread| {
+            let pool = pool.clone();
+            std::thread::spawn(move || {
+                for i in 0..500 {
+                    let value = thread * 1000 + i;
+                    let job = pool.spawn_blocking(move || {
+                        if i % 7 == 0 {
+                            std::thread::yield_now();
+                        }
+                        vec![value; i % 4]
+                    });
+                    assert_eq!(poll_with_changing_wakers(job, i), vec![value; i % 4]);
+                }
+            })
+        })
+        .collect();
+    for poller in pollers {
+        poller.join().unwrap();
+    }
+}
+
+#[test]
+fn unclaimed_values_are_dropped() {
+    let pool = Builder::new().max_threads(1).build();
+    let value = Arc::new(());
+    let job = pool.spawn_blocking({
+        let value = value.clone();
+        move || value
+    });
+    // With one thread, the first job is done once the second is.
+    async_std::task::block_on(pool.spawn_blocking(|| ())).unwrap();
+    assert_eq!(Arc::strong_count(&value), 2);
+    drop(job);
+    assert_eq!(Arc::strong_count(&value), 1);
+}
+
+/// Return all of `stream`'s items.
+fn collect<T>(mut stream: BlockingStream<T>) -> Vec<Result<T, JoinError>> {
+    use async_std::stream::StreamExt;
+
+    async_std::task::block_on(async {
+        let mut items = vec![];
+        while let Some(item) = stream.next().await {
+            items.push(item);
+        }
+        items
+    })
+}
+
+#[test]
+fn streams_yield_every_item() {
+    use async_std::stream::StreamExt;
+
+    async_std::task::block_on(async {
+        let mut stream = spawn_blocking_stream(0..1000);
+        let mut expected = 0;
+        while let Some(item) = stream.next().await {
+            assert_eq!(item.unwrap(), expected);
+            expected += 1;
+        }
+        assert_eq!(expected, 1000);
+        assert!(stream.next().await.is_none());
+
+    });
+
+    let words: Vec<_> = collect(spawn_blocking_stream(vec!["a", "b"])).into_iter().map(Result::unwrap).collect();
+    assert_eq!(words, ["a", "b"]);
+}
+
+/// An iterator that counts the items it produces, and notes when it's
+/// dropped.
+struct Counting {
+    produced: Arc<std::sync::atomic::AtomicUsize>,
+    dropped: Arc<std::sync::atomic::AtomicBool>,
+}
+
+impl Counting {
+    fn new() -> (Counting, Arc<std::sync::atomic::AtomicUsize>, Arc<std::sync::atomic::AtomicBool>) {
+        let counting = Counting { produced: Default::default(), dropped: Default::default() };
+        let produced = counting.produced.clone();
+        let dropped = counting.dropped.clone();
+        (counting, produced, dropped)
+    }
+}
+
+impl Iterator for Counting {
+    type Item = usize;
+    fn next(&mut self) -> Option<usize> {
+        Some(self.produced.fetch_add(1, std::sync::atomic::Ordering::SeqCst))
+    }
+}
+
+impl Drop for Counting {
+    fn drop(&mut self) {
+        self.dropped.store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
+/// Wait for `condition` to hold, for up to ten seconds.
+fn wait_for(condition: impl Fn() -> bool) {
+    let start = std::time::Instant::now();
+    while !condition() {
+        assert!(start.elapsed() < std::time::Duration::from_secs(10), "timed out");
+        std::thread::sleep(std::time::Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn streams_apply_backpressure_and_cancel() {
+    use async_std::stream::StreamExt;
+    use std::sync::atomic::Ordering;
+
+    let pool = Builder::new().stream_buffer(4).build();
+    let (counting, produced, dropped) = Counting::new();
+    let mut stream = pool.spawn_blocking_stream(counting);
+
+    // The iterator fills the buffer, makes one more item, and waits.
+    wait_for(|| produced.load(Ordering::SeqCst) == 5);
+    std::thread::sleep(std::time::Duration::from_millis(20));
+    assert_eq!(produced.load(Ordering::SeqCst), 5);
+
+    let first: Vec<_> = async_std::task::block_on(async {
+        let mut first = vec![];
+        for _ in 0..3 {
+            first.push(stream.next().await.unwrap().unwrap());
+        }
+        first
+    });
+    assert_eq!(first, [0, 1, 2]);
+    wait_for(|| produced.load(Ordering::SeqCst) == 8);
+
+    // Dropping the stream stops the iterator and frees the thread.
+    drop(stream);
+    wait_for(|| dropped.load(Ordering::SeqCst));
+    assert_eq!(produced.load(Ordering::SeqCst), 8);
+    assert_eq!(async_std::task::block_on(pool.spawn_blocking(|| 1)).unwrap(), 1);
+}
+
+#[test]
+fn streams_that_never_start() {
+    use std::sync::atomic::Ordering;
+
+    let pool = Builder::new().max_threads(1).build();
+    let (blocker, release) = gate();
+    let first = pool.spawn_blocking(blocker);
+    wait_for_queue(&pool, 0);
+
+    let (counting, produced, dropped) = Counting::new();
+    let stream = pool.spawn_blocking_stream(counting);
+    assert_eq!(pool.queued_jobs(), 1);
+    drop(stream);
+    assert_eq!(pool.queued_jobs(), 0);
+    assert!(dropped.load(Ordering::SeqCst));
+
+    release.send(()).unwrap();
+    async_std::task::block_on(first).unwrap();
+    assert_eq!(produced.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn streams_report_panics() {
+    let items = collect(spawn_blocking_stream((0..10).map(|i| {
+        if i < 3 { i } else { panic!("boom at {}", i) }
+    })));
+    assert_eq!(items.len(), 4);
+    for (i, item) in items[..3].iter().enumerate() {
+        assert_eq!(*item.as_ref().unwrap(), i);
+    }
+    assert_eq!(items[3].as_ref().unwrap_err().panic_message(), Some("boom at 3"));
+}